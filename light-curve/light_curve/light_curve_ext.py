@@ -7,6 +7,7 @@ def __register_submodules():
     import sys
 
     sys.modules["light_curve.light_curve_ext.ln_prior"] = ln_prior
+    sys.modules["light_curve.light_curve_ext.transform"] = transform
 
 
 __register_submodules()
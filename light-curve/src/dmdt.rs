@@ -7,18 +7,24 @@ use crate::errors::Exception::ValueError;
 use conv::{ApproxFrom, ApproxInto, ConvAsUtil};
 use enumflags2::{BitFlags, bitflags};
 use light_curve_dmdt as lcdmdt;
-use light_curve_dmdt::{Grid, GridTrait};
+use light_curve_dmdt::{CellIndex, Grid, GridTrait};
 use ndarray::IntoNdProducer;
+use ndarray_npy::{NpzWriter, WritableElement};
 use numpy::prelude::*;
 use numpy::{Element, PyArray1, PyUntypedArray, ToPyArray};
 use pyo3::prelude::*;
-use pyo3::types::{PyBytes, PyTuple};
-use rand::SeedableRng;
+use pyo3::types::{PyBytes, PyDict, PySet, PyTuple};
+use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::ops::{DerefMut, Range};
-use std::sync::{Arc, Mutex, RwLock};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::DerefMut;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use unzip3::Unzip3;
 
@@ -30,20 +36,587 @@ enum DropNObsType {
     Float(f64),
 }
 
+/// Weighting of pairs of observations of a light curve, see [WeightKind]
+#[derive(FromPyObject)]
+enum WeightArg<'py> {
+    #[pyo3(transparent, annotation = "str")]
+    Kind(String),
+    #[pyo3(transparent, annotation = "ndarray")]
+    Array(Bound<'py, PyAny>),
+}
+
+/// Weighting of pairs of observations of a collection of light curves, see [WeightKind]
+#[derive(FromPyObject)]
+enum WeightsArg<'py> {
+    #[pyo3(transparent, annotation = "str")]
+    Kind(String),
+    #[pyo3(transparent, annotation = "list[ndarray]")]
+    Arrays(Vec<Bound<'py, PyAny>>),
+}
+
 #[bitflags]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum NormFlag {
     Dt,
     Max,
+    Pairs,
+    L2,
+    Row,
+    Log1p,
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+impl NormFlag {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Dt => "dt",
+            Self::Max => "max",
+            Self::Pairs => "pairs",
+            Self::L2 => "l2",
+            Self::Row => "row",
+            Self::Log1p => "log1p",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum ErrorFunction {
     Exact,
     Eps1Over1e3,
 }
 
+/// Type of dt or dm grid
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum GridType {
+    Linear,
+    Log,
+    /// Evenly spaced in asinh of the value, which is linear near zero and logarithmic for
+    /// absolute values much larger than unity
+    Asinh,
+    /// Arbitrary borders
+    Array,
+}
+
+impl GridType {
+    fn of<T: lcdmdt::Float>(grid: &Grid<T>) -> Self {
+        match grid {
+            Grid::Linear(_) => Self::Linear,
+            Grid::Lg(_) => Self::Log,
+            _ => Self::Array,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Log => "log",
+            Self::Asinh => "asinh",
+            Self::Array => "asis",
+        }
+    }
+}
+
+/// Definition of dm of a pair of observations, `m` is a magnitude for `MagDiff` and a flux otherwise
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+enum DmKind {
+    /// m_j - m_i
+    #[default]
+    MagDiff,
+    /// f_j / f_i
+    FluxRatio,
+    /// Difference of asinh magnitudes (Lupton et al. 1999) with the given softening flux
+    AsinhMagDiff { softening: f64 },
+    /// (f_j - f_i) / max |f|
+    NormFluxDiff,
+}
+
+impl DmKind {
+    fn new(dm_kind: &str, asinh_softening: f64) -> Res<Self> {
+        if !(asinh_softening > 0.0 && asinh_softening.is_finite()) {
+            return Err(Exception::ValueError(format!(
+                "asinh_softening must be positive and finite, got {asinh_softening}"
+            )));
+        }
+        match dm_kind {
+            "mag-diff" => Ok(Self::MagDiff),
+            "flux-ratio" => Ok(Self::FluxRatio),
+            "asinh-mag-diff" => Ok(Self::AsinhMagDiff {
+                softening: asinh_softening,
+            }),
+            "norm-flux-diff" => Ok(Self::NormFluxDiff),
+            _ => Err(Exception::ValueError(format!(
+                "dm_kind {dm_kind:?} is unknown, known kinds are: \"mag-diff\", \"flux-ratio\", \"asinh-mag-diff\", \"norm-flux-diff\""
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::MagDiff => "mag-diff",
+            Self::FluxRatio => "flux-ratio",
+            Self::AsinhMagDiff { .. } => "asinh-mag-diff",
+            Self::NormFluxDiff => "norm-flux-diff",
+        }
+    }
+
+    /// Values to take pair differences of, and their squared uncertainties if `err2` is not
+    /// empty. `FluxRatio` values are fluxes themselves
+    fn values<'a, T: ndarray::NdFloat>(
+        &self,
+        m: &'a [T],
+        err2: &'a [T],
+    ) -> (Cow<'a, [T]>, Cow<'a, [T]>) {
+        let to_float = |x: f64| -> T { num_traits::NumCast::from(x).unwrap() };
+        match *self {
+            Self::MagDiff | Self::FluxRatio => (m.into(), err2.into()),
+            Self::AsinhMagDiff { softening } => {
+                let two_b = to_float(2.0 * softening);
+                let a = to_float(2.5 * std::f64::consts::LOG10_E);
+                let mu: Vec<_> = m.iter().map(|&f| -a * (f / two_b).asinh()).collect();
+                let mu_err2: Vec<_> = m
+                    .iter()
+                    .zip(err2)
+                    .map(|(&f, &e2)| a.powi(2) * e2 / (two_b.powi(2) + f.powi(2)))
+                    .collect();
+                (mu.into(), mu_err2.into())
+            }
+            Self::NormFluxDiff => {
+                let max = m.iter().fold(T::zero(), |acc, &f| acc.max(f.abs()));
+                let scale = if max.is_zero() { T::one() } else { max };
+                let m: Vec<_> = m.iter().map(|&f| f / scale).collect();
+                let err2: Vec<_> = err2.iter().map(|&e2| e2 / scale.powi(2)).collect();
+                (m.into(), err2.into())
+            }
+        }
+    }
+}
+
+/// Weighting of pairs of observations, see [GenericDmDt::pair_weight]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum WeightKind {
+    /// Inverse squared uncertainty of the pair dm
+    InverseVariance,
+    /// Inverse time difference of the pair
+    InverseDt,
+    /// Signal-to-noise ratio of the pair dm, derived from signal-to-noise ratios of observations
+    Snr,
+    /// Product of user-supplied weights of observations
+    Observations,
+}
+
+impl WeightKind {
+    fn new(weight: &str) -> Res<Self> {
+        match weight {
+            "inverse-variance" => Ok(Self::InverseVariance),
+            "inverse-dt" => Ok(Self::InverseDt),
+            "snr" => Ok(Self::Snr),
+            _ => Err(Exception::ValueError(format!(
+                "weight {weight:?} is unknown, known weights are: \"inverse-variance\", \"inverse-dt\", \"snr\" or an array of observation weights"
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::InverseVariance => "inverse-variance",
+            Self::InverseDt => "inverse-dt",
+            Self::Snr => "snr",
+            Self::Observations => "observations",
+        }
+    }
+
+    /// Whether observation uncertainties are needed, so the weighting is not available for
+    /// non-smeared maps
+    fn requires_sigma(&self) -> bool {
+        matches!(self, Self::InverseVariance | Self::Snr)
+    }
+}
+
+/// Weighting of pairs of observations of a single light curve
+#[derive(Copy, Clone, Debug)]
+struct Weights<'a, T> {
+    kind: WeightKind,
+    /// Weights of observations for [WeightKind::Observations], empty otherwise
+    w: &'a [T],
+}
+
+/// Channel of multi-band dm-dt map
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Channel {
+    /// Pairs of observations of the same band
+    Band(usize),
+    /// Pairs of observations of two bands, dm is the second band magnitude minus the first band
+    /// magnitude and dt is the absolute time difference
+    Color(usize, usize),
+}
+
+/// Channels of multi-band dm-dt maps and band indices of observations of every light curve
+///
+/// Observations of bands which are not used by any channel have `None` band index
+#[derive(Clone, Debug)]
+struct BandChannels {
+    channels: Vec<Channel>,
+    bands: Vec<Vec<Option<usize>>>,
+}
+
+/// Elements of `a` with given indices, indices out of bounds are skipped, so it gives an empty
+/// vector for an empty `a`
+fn take_index<X: Copy>(a: &[X], idx: &[usize]) -> Vec<X> {
+    idx.iter().filter_map(|&i| a.get(i).copied()).collect()
+}
+
+/// Output format of multiple dm-dt maps
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum OutputFormat {
+    /// Dense array with the first axis enumerating maps
+    Dense,
+    /// Compressed sparse row matrix with a flattened map per row
+    Sparse,
+}
+
+impl OutputFormat {
+    fn new(output: &str) -> Res<Self> {
+        match output {
+            "dense" => Ok(Self::Dense),
+            "sparse" => Ok(Self::Sparse),
+            _ => Err(Exception::ValueError(format!(
+                "output must be 'dense' or 'sparse', got {output:?}"
+            ))),
+        }
+    }
+}
+
+/// Maps in compressed sparse row format, every row is a flattened map
+struct CsrMaps<T> {
+    data: Vec<T>,
+    indices: Vec<i64>,
+    indptr: Vec<i64>,
+    shape: (usize, usize),
+}
+
+impl<T> CsrMaps<T>
+where
+    T: Element + num_traits::Zero + Copy,
+{
+    /// Indices and values of non-zero elements of the flattened map
+    fn sparse_row(map: ndarray::ArrayViewD<T>) -> (Vec<i64>, Vec<T>) {
+        map.iter()
+            .enumerate()
+            .filter(|(_, x)| !x.is_zero())
+            .map(|(i, &x)| (i as i64, x))
+            .unzip()
+    }
+
+    fn from_rows(rows: Vec<(Vec<i64>, Vec<T>)>, n_cols: usize) -> Self {
+        let mut indptr = Vec::with_capacity(rows.len() + 1);
+        indptr.push(0);
+        let mut data = vec![];
+        let mut indices = vec![];
+        let n_rows = rows.len();
+        for (row_indices, row_data) in rows {
+            indices.extend(row_indices);
+            data.extend(row_data);
+            indptr.push(data.len() as i64);
+        }
+        Self {
+            data,
+            indices,
+            indptr,
+            shape: (n_rows, n_cols),
+        }
+    }
+
+    /// Sparse maps from a dense array with the first axis enumerating maps
+    fn from_dense(maps: ndarray::ArrayViewD<T>) -> Self {
+        let n_cols = maps.shape()[1..].iter().product();
+        let rows = maps.outer_iter().map(Self::sparse_row).collect();
+        Self::from_rows(rows, n_cols)
+    }
+
+    /// Tuple of (data, indices, indptr, shape), compatible with `scipy.sparse.csr_array`
+    fn into_py(self, py: Python) -> Res<Bound<PyAny>> {
+        let items = [
+            self.data.into_pyarray(py).into_any(),
+            self.indices.into_pyarray(py).into_any(),
+            self.indptr.into_pyarray(py).into_any(),
+            self.shape.into_pyobject(py)?.into_any(),
+        ];
+        Ok(PyTuple::new(py, items)?.into_any())
+    }
+}
+
+/// Batch of maps yielded by batch iterators
+enum BatchMaps<T> {
+    Dense(ndarray::ArrayD<T>),
+    Sparse(CsrMaps<T>),
+}
+
+impl<T> BatchMaps<T>
+where
+    T: Element + num_traits::Zero + Copy,
+{
+    fn new(maps: ndarray::ArrayD<T>, output: OutputFormat) -> Self {
+        match output {
+            OutputFormat::Dense => Self::Dense(maps),
+            OutputFormat::Sparse => Self::Sparse(CsrMaps::from_dense(maps.view())),
+        }
+    }
+
+    fn into_py(self, py: Python) -> Res<Bound<PyAny>> {
+        match self {
+            Self::Dense(maps) => Ok(maps.into_pyarray(py).into_any()),
+            Self::Sparse(maps) => maps.into_py(py),
+        }
+    }
+}
+
+/// File format of dataset batches, see [DmDt::write_dataset]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum DatasetFormat {
+    /// .npy file of maps
+    Npy,
+    /// Uncompressed .npz file of maps and light curve indices
+    Npz,
+}
+
+impl DatasetFormat {
+    fn new(format: &str) -> Res<Self> {
+        match format {
+            "npy" => Ok(Self::Npy),
+            "npz" => Ok(Self::Npz),
+            _ => Err(Exception::ValueError(format!(
+                "format {format:?} is unknown, known formats are: \"npy\", \"npz\""
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Npy => "npy",
+            Self::Npz => "npz",
+        }
+    }
+
+    /// Writes maps of the i-th batch and indices of their light curves, returns the file name
+    fn write_batch<T: WritableElement>(
+        &self,
+        dir: &Path,
+        i: usize,
+        maps: ndarray::ArrayViewD<T>,
+        index: &[usize],
+    ) -> Res<String> {
+        let file = format!("batch_{i:06}.{}", self.name());
+        let write_error =
+            |err: &dyn std::fmt::Display| Exception::OSError(format!("cannot write {file}: {err}"));
+        match self {
+            Self::Npy => {
+                ndarray_npy::write_npy(dir.join(&file), &maps).map_err(|err| write_error(&err))?
+            }
+            Self::Npz => {
+                let index: ndarray::Array1<i64> = index.iter().map(|&i| i as i64).collect();
+                let mut npz = NpzWriter::new(BufWriter::new(File::create(dir.join(&file))?));
+                npz.add_array("maps", &maps)
+                    .map_err(|err| write_error(&err))?;
+                npz.add_array("index", &index)
+                    .map_err(|err| write_error(&err))?;
+                npz.finish().map_err(|err| write_error(&err))?.flush()?;
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Removes batch files and the index of a dataset previously written to `dir`, so a new dataset
+/// with fewer batches does not leave stale files. Other files are kept
+fn remove_dataset_files(dir: &Path) -> Res<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let is_batch = name
+            .strip_prefix("batch_")
+            .and_then(|name| {
+                name.strip_suffix(".npy")
+                    .or_else(|| name.strip_suffix(".npz"))
+            })
+            .is_some_and(|i| !i.is_empty() && i.bytes().all(|b| b.is_ascii_digit()));
+        if is_batch || name == "index.json" {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// A file of dataset batch, see [DmDt::write_dataset]
+#[derive(Serialize)]
+struct DatasetBatch {
+    file: String,
+    /// Indices of light curves of the maps
+    index: Vec<usize>,
+}
+
+/// Description of a dataset, which is written to its index file, see [DmDt::write_dataset]
+#[derive(Serialize)]
+struct DatasetIndex {
+    format: &'static str,
+    dtype: &'static str,
+    map_shape: Vec<usize>,
+    n_lcs: usize,
+    batch_size: usize,
+    shuffle: bool,
+    random_seed: Option<u64>,
+    dmdt: DmDt,
+    batches: Vec<DatasetBatch>,
+}
+
+/// Standard normal random value, Box-Muller transform
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    f64::sqrt(-2.0 * u1.ln()) * f64::cos(std::f64::consts::TAU * u2)
+}
+
+/// Random augmentations of light curves used by batch iterators, zero values turn them off
+#[derive(Copy, Clone, Debug)]
+struct Augmentation {
+    /// Standard deviation of Gaussian noise added to `m` in units of sigma, or in units of `m` if
+    /// no uncertainties are given
+    noise: f64,
+    /// Standard deviation of Gaussian shifts of individual time moments
+    time_shift: f64,
+    /// Time is multiplied by a factor log-uniformly distributed in [1 / (1 + x), 1 + x]
+    time_stretch: f64,
+    /// Half-width of the uniform distribution of magnitude offsets, independent for every band.
+    /// Fluxes are multiplied by the corresponding factors instead, see [Augmentation::apply]
+    mag_offset: f64,
+    /// Part of the time span to cut away, the rest is a window with random position
+    crop: f64,
+}
+
+impl Augmentation {
+    fn new(
+        noise: f64,
+        time_shift: f64,
+        time_stretch: f64,
+        mag_offset: f64,
+        crop: f64,
+    ) -> Res<Self> {
+        for (name, value) in [
+            ("noise", noise),
+            ("time_shift", time_shift),
+            ("time_stretch", time_stretch),
+            ("mag_offset", mag_offset),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(Exception::ValueError(format!(
+                    "{name} must be non-negative and finite, got {value}"
+                )));
+            }
+        }
+        if !(0.0..1.0).contains(&crop) {
+            return Err(Exception::ValueError(format!(
+                "crop must be in [0.0, 1.0), got {crop}"
+            )));
+        }
+        Ok(Self {
+            noise,
+            time_shift,
+            time_stretch,
+            mag_offset,
+            crop,
+        })
+    }
+
+    fn is_identity(&self) -> bool {
+        self.noise == 0.0
+            && self.time_shift == 0.0
+            && self.time_stretch == 0.0
+            && self.mag_offset == 0.0
+            && self.crop == 0.0
+    }
+
+    /// Applies augmentations to a sorted light curve, `err2` and `band` may be empty
+    ///
+    /// `m` is a magnitude for [DmKind::MagDiff] and a flux otherwise, so magnitude offset `x` is
+    /// applied to fluxes and their uncertainties as a factor of `10^(-0.4 x)`
+    fn apply<T, R>(
+        &self,
+        rng: &mut R,
+        dm_kind: DmKind,
+        t: &mut Vec<T>,
+        m: &mut Vec<T>,
+        err2: &mut Vec<T>,
+        band: &mut Vec<Option<usize>>,
+    ) where
+        T: ndarray::NdFloat,
+        R: Rng,
+    {
+        fn select<T: Copy>(
+            idx: &[usize],
+            t: &mut Vec<T>,
+            m: &mut Vec<T>,
+            err2: &mut Vec<T>,
+            band: &mut Vec<Option<usize>>,
+        ) {
+            *t = take_index(t, idx);
+            *m = take_index(m, idx);
+            *err2 = take_index(err2, idx);
+            *band = take_index(band, idx);
+        }
+        let to_float = |x: f64| -> T { num_traits::NumCast::from(x).unwrap() };
+
+        if self.crop > 0.0 && !t.is_empty() {
+            let (first, last) = (t[0], t[t.len() - 1]);
+            let window = to_float(1.0 - self.crop) * (last - first);
+            let start = first + to_float(rng.random::<f64>()) * (last - first - window);
+            let idx: Vec<_> = (0..t.len())
+                .filter(|&i| t[i] >= start && t[i] <= start + window)
+                .collect();
+            select(&idx, t, m, err2, band);
+        }
+        if self.time_stretch > 0.0 {
+            let factor = to_float(f64::exp(
+                rng.random_range(-1.0_f64..=1.0) * f64::ln_1p(self.time_stretch),
+            ));
+            t.iter_mut().for_each(|x| *x *= factor);
+        }
+        if self.time_shift > 0.0 {
+            t.iter_mut()
+                .for_each(|x| *x += to_float(self.time_shift * standard_normal(rng)));
+            let mut idx: Vec<_> = (0..t.len()).collect();
+            idx.sort_by(|&a, &b| t[a].partial_cmp(&t[b]).unwrap());
+            select(&idx, t, m, err2, band);
+        }
+        if self.mag_offset > 0.0 {
+            let n_bands = band.iter().flatten().max().map_or(1, |&b| b + 1);
+            let offsets: Vec<_> = (0..n_bands)
+                .map(|_| to_float(rng.random_range(-self.mag_offset..=self.mag_offset)))
+                .collect();
+            let offset = |i: usize| offsets[band.get(i).copied().flatten().unwrap_or(0)];
+            if dm_kind == DmKind::MagDiff {
+                for (i, x) in m.iter_mut().enumerate() {
+                    *x += offset(i);
+                }
+            } else {
+                let factor = |i: usize| to_float(10.0).powf(to_float(-0.4) * offset(i));
+                for (i, x) in m.iter_mut().enumerate() {
+                    *x *= factor(i);
+                }
+                for (i, e2) in err2.iter_mut().enumerate() {
+                    *e2 *= factor(i).powi(2);
+                }
+            }
+        }
+        if self.noise > 0.0 {
+            for (i, x) in m.iter_mut().enumerate() {
+                let sigma = err2.get(i).map_or(T::one(), |e| e.sqrt());
+                *x += to_float(self.noise * standard_normal(rng)) * sigma;
+            }
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct GenericDmDt<T>
 where
@@ -53,6 +626,8 @@ where
     norm: BitFlags<NormFlag>,
     error_func: ErrorFunction,
     n_jobs: usize,
+    #[serde(default)]
+    dm_kind: DmKind,
 }
 
 impl<'py, T> GenericDmDt<T>
@@ -65,18 +640,54 @@ where
         a
     }
 
-    fn normalize(&self, a: &mut ndarray::Array2<T>, t: &[T]) {
+    /// Normalizes the map, `dt_points` gives the number, or the total weight, of pairs per each
+    /// dt cell
+    ///
+    /// Normalisations are applied in the order: log1p, dt, pairs, row, l2, max
+    fn normalize(
+        &self,
+        a: &mut ndarray::Array2<T>,
+        dt_points: impl FnOnce() -> ndarray::Array1<T>,
+    ) {
+        if self.norm.contains(NormFlag::Log1p) {
+            a.mapv_inplace(|x| x.ln_1p());
+        }
+        let dt = self
+            .norm
+            .intersects(NormFlag::Dt | NormFlag::Pairs)
+            .then(dt_points);
         if self.norm.contains(NormFlag::Dt) {
-            let dt = self.dmdt.dt_points(t);
-            let dt_no_zeros = dt.mapv(|x| {
-                if x == 0 {
-                    T::one()
-                } else {
-                    x.approx_into().unwrap()
-                }
-            });
+            // safe to unwrap because dt is computed for this flag
+            let dt_no_zeros = dt
+                .as_ref()
+                .unwrap()
+                .mapv(|x| if x.is_zero() { T::one() } else { x });
             *a /= &dt_no_zeros.to_shape((a.nrows(), 1)).unwrap();
         }
+        if self.norm.contains(NormFlag::Pairs) {
+            let n_pairs = dt.as_ref().unwrap().sum();
+            if !n_pairs.is_zero() {
+                a.mapv_inplace(|x| x / n_pairs);
+            }
+        }
+        if self.norm.contains(NormFlag::Row) {
+            for mut row in a.rows_mut() {
+                let sum = row.sum();
+                if !sum.is_zero() {
+                    row.mapv_inplace(|x| x / sum);
+                }
+            }
+        }
+        if self.norm.contains(NormFlag::L2) {
+            let norm = a
+                .iter()
+                .map(|&x| x * x)
+                .fold(T::zero(), |acc, x| acc + x)
+                .sqrt();
+            if !norm.is_zero() {
+                a.mapv_inplace(|x| x / norm);
+            }
+        }
         if self.norm.contains(NormFlag::Max) {
             let max = *a.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
             if !max.is_zero() {
@@ -153,29 +764,380 @@ where
         Ok(result)
     }
 
+    /// Sparse maps of `n` light curves computed in parallel, `map` gives the dense map or
+    /// multi-channel maps of the i-th light curve
+    fn sparse_many(
+        &self,
+        n: usize,
+        n_channels: usize,
+        map: impl Fn(usize) -> Res<ndarray::ArrayD<T>> + Sync,
+    ) -> Res<CsrMaps<T>> {
+        let (dt_size, dm_size) = self.dmdt.shape();
+        let rows = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.n_jobs)
+            .build()
+            .unwrap()
+            .install(|| {
+                (0..n)
+                    .into_par_iter()
+                    .map(|i| map(i).map(|a| CsrMaps::sparse_row(a.view())))
+                    .collect::<Res<Vec<_>>>()
+            })?;
+        Ok(CsrMaps::from_rows(rows, n_channels * dt_size * dm_size))
+    }
+
     fn py_points(
         &self,
         py: Python<'py>,
         t: Arr<'py, T>,
         m: Arr<'py, T>,
+        w: Option<Arr<'py, T>>,
+        weight: Option<WeightKind>,
         sorted: Option<bool>,
+        band_channels: Option<&BandChannels>,
     ) -> Res<Bound<'py, PyUntypedArray>> {
-        Ok(self
-            .points(
-                ContCowArray::from_view(t.as_array(), true).as_slice(),
-                ContCowArray::from_view(m.as_array(), true).as_slice(),
-                sorted,
-            )?
-            .into_pyarray(py)
-            .as_untyped()
-            .clone())
+        let t = ContCowArray::from_view(t.as_array(), true);
+        let m = ContCowArray::from_view(m.as_array(), true);
+        let w = w.map(|w| ContCowArray::from_view(w.as_array(), true));
+        let weights = weight.map(|kind| Weights {
+            kind,
+            w: w.as_ref().map(|w| w.as_slice()).unwrap_or(&[]),
+        });
+        let array = match band_channels {
+            Some(bc) => self
+                .points_channels(
+                    t.as_slice(),
+                    m.as_slice(),
+                    &bc.bands[0],
+                    &bc.channels,
+                    weights,
+                    sorted,
+                )?
+                .into_pyarray(py)
+                .as_untyped()
+                .clone(),
+            None => self
+                .points(t.as_slice(), m.as_slice(), weights, sorted)?
+                .into_pyarray(py)
+                .as_untyped()
+                .clone(),
+        };
+        Ok(array)
     }
 
-    fn points(&self, t: &[T], m: &[T], sorted: Option<bool>) -> Res<ndarray::Array2<T>> {
+    fn points(
+        &self,
+        t: &[T],
+        m: &[T],
+        weights: Option<Weights<T>>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array2<T>> {
+        Self::check_lengths(t, m, None)?;
         check_sorted(t, sorted)?;
+        Self::check_weights(t, weights)?;
+
+        let w = self.observation_weights(weights, m, &[]);
+        let (m, _) = self.dm_kind.values(m, &[]);
+        Ok(self.map_points(t, &m, &w, weights.map(|w| w.kind)))
+    }
 
+    /// Map of a sorted light curve, `m` is transformed with [DmKind::values] and `w` is given
+    /// by [GenericDmDt::observation_weights]
+    fn map_points(
+        &self,
+        t: &[T],
+        m: &[T],
+        w: &[T],
+        weight: Option<WeightKind>,
+    ) -> ndarray::Array2<T> {
+        if self.dm_kind == DmKind::FluxRatio || weight.is_some() {
+            return self.pairs_points(t, m, w, t, m, w, true, weight);
+        }
         let mut result = self.dmdt.points(t, m).mapv(|x| x.approx_into().unwrap());
-        self.normalize(&mut result, t);
+        self.normalize(&mut result, || {
+            self.dmdt.dt_points(t).mapv(|x| x.approx_into().unwrap())
+        });
+        result
+    }
+
+    fn check_weights(t: &[T], weights: Option<Weights<T>>) -> Res<()> {
+        let Some(Weights {
+            kind: WeightKind::Observations,
+            w,
+        }) = weights
+        else {
+            return Ok(());
+        };
+        if t.len() != w.len() {
+            return Err(Exception::ValueError(format!(
+                "weight must have the same length as t, {} != {}",
+                w.len(),
+                t.len()
+            )));
+        }
+        if !w.iter().all(|&x| x >= T::zero() && x.is_finite()) {
+            return Err(Exception::ValueError(
+                "weight values must be non-negative and finite".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Per-observation values used by [GenericDmDt::pair_weight]: signal-to-noise ratios for
+    /// [WeightKind::Snr], user weights for [WeightKind::Observations] and nothing otherwise.
+    /// `m` and `err2` are not transformed with [DmKind::values], signal-to-noise ratio is
+    /// `2.5 lg(e) / sigma` for magnitudes and `|f| / sigma` for fluxes
+    fn observation_weights<'a>(
+        &self,
+        weights: Option<Weights<'a, T>>,
+        m: &[T],
+        err2: &[T],
+    ) -> Cow<'a, [T]> {
+        match weights {
+            Some(Weights {
+                kind: WeightKind::Observations,
+                w,
+            }) => w.into(),
+            Some(Weights {
+                kind: WeightKind::Snr,
+                ..
+            }) => {
+                let signal = |m: T| -> T {
+                    match self.dm_kind {
+                        DmKind::MagDiff => {
+                            num_traits::NumCast::from(2.5 * std::f64::consts::LOG10_E).unwrap()
+                        }
+                        _ => m.abs(),
+                    }
+                };
+                m.iter()
+                    .zip(err2)
+                    .map(|(&m, &e2)| signal(m) / e2.sqrt())
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+            _ => Cow::Borrowed(&[]),
+        }
+    }
+
+    /// Weight of the pair of the i-th observation of the first light curve and the j-th
+    /// observation of the second one, `w1` and `w2` are given by
+    /// [GenericDmDt::observation_weights]. Unweighted pairs have unity weight
+    ///
+    /// Signal-to-noise ratio of a pair is `1 / sqrt(1 / snr_i^2 + 1 / snr_j^2)`
+    fn pair_weight(
+        weight: Option<WeightKind>,
+        w1: &[T],
+        w2: &[T],
+        i: usize,
+        j: usize,
+        dt: T,
+        dm_err2: T,
+    ) -> T {
+        match weight {
+            None => T::one(),
+            Some(WeightKind::InverseVariance) => dm_err2.recip(),
+            Some(WeightKind::InverseDt) => dt.recip(),
+            Some(WeightKind::Snr) => (w1[i].powi(2).recip() + w2[j].powi(2).recip())
+                .sqrt()
+                .recip(),
+            Some(WeightKind::Observations) => w1[i] * w2[j],
+        }
+    }
+
+    /// `err2` is `None` for light curves without uncertainties
+    fn check_lengths(t: &[T], m: &[T], err2: Option<&[T]>) -> Res<()> {
+        if t.len() != m.len() {
+            return Err(Exception::ValueError(format!(
+                "m must have the same length as t, {} != {}",
+                m.len(),
+                t.len()
+            )));
+        }
+        if let Some(err2) = err2 {
+            if t.len() != err2.len() {
+                return Err(Exception::ValueError(format!(
+                    "sigma must have the same length as t, {} != {}",
+                    err2.len(),
+                    t.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_band(t: &[T], band: &[Option<usize>]) -> Res<()> {
+        if t.len() != band.len() {
+            return Err(Exception::ValueError(format!(
+                "band must have the same length as t, {} != {}",
+                band.len(),
+                t.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Calls `f(i, j)` for every pair of observations of two light curves, or for every `i < j`
+    /// pair of observations of the same light curve if `same` is true
+    fn for_each_pair(n1: usize, n2: usize, same: bool, mut f: impl FnMut(usize, usize)) {
+        for i in 0..n1 {
+            for j in if same { i + 1 } else { 0 }..n2 {
+                f(i, j);
+            }
+        }
+    }
+
+    /// dm of a pair of values transformed with [DmKind::values]
+    fn pair_dm(&self, m1: T, m2: T) -> T {
+        match self.dm_kind {
+            DmKind::FluxRatio => m2 / m1,
+            _ => m2 - m1,
+        }
+    }
+
+    /// dm of a pair of values transformed with [DmKind::values] and its squared uncertainty
+    fn pair_dm_err2(&self, m1: T, err2_1: T, m2: T, err2_2: T) -> (T, T) {
+        match self.dm_kind {
+            DmKind::FluxRatio => {
+                let ratio = m2 / m1;
+                (
+                    ratio,
+                    ratio.powi(2) * (err2_1 / m1.powi(2) + err2_2 / m2.powi(2)),
+                )
+            }
+            _ => (m2 - m1, err2_1 + err2_2),
+        }
+    }
+
+    /// Map of pairs of observations, see [GenericDmDt::for_each_pair]. dt is the absolute time
+    /// difference and dm is given by [GenericDmDt::pair_dm] of the first and the second values.
+    /// Every pair brings its [GenericDmDt::pair_weight], pairs of non-finite weight are skipped
+    #[allow(clippy::too_many_arguments)]
+    fn pairs_points(
+        &self,
+        t1: &[T],
+        m1: &[T],
+        w1: &[T],
+        t2: &[T],
+        m2: &[T],
+        w2: &[T],
+        same: bool,
+        weight: Option<WeightKind>,
+    ) -> ndarray::Array2<T> {
+        let mut result = ndarray::Array2::zeros(self.dmdt.shape());
+        let mut dt_points = ndarray::Array1::zeros(self.dmdt.dt_grid.cell_count());
+        Self::for_each_pair(t1.len(), t2.len(), same, |i, j| {
+            let dt = (t2[j] - t1[i]).abs();
+            let CellIndex::Value(idx_dt) = self.dmdt.dt_grid.idx(dt) else {
+                return;
+            };
+            // Uncertainties are not available, but weights requiring them are not allowed here
+            let pair_weight = Self::pair_weight(weight, w1, w2, i, j, dt, T::nan());
+            if !pair_weight.is_finite() {
+                return;
+            }
+            dt_points[idx_dt] += pair_weight;
+            let dm = self.pair_dm(m1[i], m2[j]);
+            if !dm.is_finite() {
+                return;
+            }
+            if let CellIndex::Value(idx_dm) = self.dmdt.dm_grid.idx(dm) {
+                result[(idx_dt, idx_dm)] += pair_weight;
+            }
+        });
+        self.normalize(&mut result, || dt_points);
+        result
+    }
+
+    fn band_points(t: &[T], m: &[T], band: &[Option<usize>], index: usize) -> (Vec<T>, Vec<T>) {
+        t.iter()
+            .zip(m)
+            .zip(band)
+            .filter(|&(_, &b)| b == Some(index))
+            .map(|((&t, &m), _)| (t, m))
+            .unzip()
+    }
+
+    /// Values of observations of the band, empty `x` gives an empty vector
+    fn band_values(x: &[T], band: &[Option<usize>], index: usize) -> Vec<T> {
+        x.iter()
+            .zip(band)
+            .filter(|&(_, &b)| b == Some(index))
+            .map(|(&x, _)| x)
+            .collect()
+    }
+
+    /// Multi-channel dmdt-map of multi-band light curve, shape is (channels, dt, dm)
+    fn points_channels(
+        &self,
+        t: &[T],
+        m: &[T],
+        band: &[Option<usize>],
+        channels: &[Channel],
+        weights: Option<Weights<T>>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array3<T>> {
+        Self::check_lengths(t, m, None)?;
+        check_sorted(t, sorted)?;
+        Self::check_band(t, band)?;
+        Self::check_weights(t, weights)?;
+
+        let w = self.observation_weights(weights, m, &[]);
+        let weight = weights.map(|w| w.kind);
+        let (m, _) = self.dm_kind.values(m, &[]);
+        let (dt_size, dm_size) = self.dmdt.shape();
+        let mut result = ndarray::Array3::zeros((channels.len(), dt_size, dm_size));
+        for (mut map, &channel) in result.outer_iter_mut().zip(channels) {
+            match channel {
+                Channel::Band(index) => {
+                    let (t, m) = Self::band_points(t, &m, band, index);
+                    let w = Self::band_values(&w, band, index);
+                    map.assign(&self.map_points(&t, &m, &w, weight));
+                }
+                Channel::Color(first, second) => {
+                    let (t1, m1) = Self::band_points(t, &m, band, first);
+                    let w1 = Self::band_values(&w, band, first);
+                    let (t2, m2) = Self::band_points(t, &m, band, second);
+                    let w2 = Self::band_values(&w, band, second);
+                    map.assign(&self.pairs_points(&t1, &m1, &w1, &t2, &m2, &w2, false, weight));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// `weights` are aligned with `lcs`
+    fn points_channels_many(
+        &self,
+        lcs: Vec<(&[T], &[T], &[Option<usize>])>,
+        channels: &[Channel],
+        weights: Option<&[Weights<T>]>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array4<T>> {
+        let dmdt_shape = self.dmdt.shape();
+        let mut result =
+            ndarray::Array4::zeros((lcs.len(), channels.len(), dmdt_shape.0, dmdt_shape.1));
+
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.n_jobs)
+            .build()
+            .unwrap()
+            .install(|| {
+                ndarray::Zip::indexed(result.outer_iter_mut())
+                    .and(lcs.into_producer())
+                    .into_par_iter()
+                    .try_for_each::<_, Res<_>>(|(i, mut maps, (t, m, band))| {
+                        maps.assign(&self.points_channels(
+                            t,
+                            m,
+                            band,
+                            channels,
+                            weights.map(|w| w[i]),
+                            sorted,
+                        )?);
+                        Ok(())
+                    })
+            })?;
         Ok(result)
     }
 
@@ -183,8 +1145,14 @@ where
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        weights: Option<(WeightKind, Vec<Bound<'py, PyAny>>)>,
         sorted: Option<bool>,
-    ) -> Res<Bound<'py, PyUntypedArray>> {
+        band_channels: Option<&BandChannels>,
+        output: OutputFormat,
+    ) -> Res<Bound<'py, PyAny>> {
+        let (weight, w_) = Self::wrap_weights(weights)?;
+        let weights = Self::typed_weights(weight, &w_, lcs.len());
+        let weights = weights.as_deref();
         let wrapped_lcs = lcs
             .into_iter()
             .enumerate()
@@ -210,18 +1178,99 @@ where
                 )
             })
             .collect::<Vec<_>>();
-        let typed_lcs = array_lcs
-            .iter()
-            .map(|(t, m)| (t.as_slice(), m.as_slice()))
-            .collect();
-        Ok(self
-            .points_many(typed_lcs, sorted)?
-            .into_pyarray(py)
-            .as_untyped()
-            .clone())
+        let typed_lcs = array_lcs.iter().map(|(t, m)| (t.as_slice(), m.as_slice()));
+        if output == OutputFormat::Sparse {
+            let typed_lcs: Vec<_> = typed_lcs.collect();
+            let n_channels = band_channels.map_or(1, |bc| bc.channels.len());
+            return self
+                .sparse_many(typed_lcs.len(), n_channels, |i| {
+                    let (t, m) = typed_lcs[i];
+                    match band_channels {
+                        Some(bc) => Ok(self
+                            .points_channels(
+                                t,
+                                m,
+                                &bc.bands[i],
+                                &bc.channels,
+                                weights.map(|w| w[i]),
+                                sorted,
+                            )?
+                            .into_dyn()),
+                        None => Ok(self.points(t, m, weights.map(|w| w[i]), sorted)?.into_dyn()),
+                    }
+                })?
+                .into_py(py);
+        }
+        let array = match band_channels {
+            Some(bc) => self
+                .points_channels_many(
+                    typed_lcs
+                        .zip(&bc.bands)
+                        .map(|((t, m), band)| (t, m, band.as_slice()))
+                        .collect(),
+                    &bc.channels,
+                    weights,
+                    sorted,
+                )?
+                .into_pyarray(py)
+                .into_any(),
+            None => self
+                .points_many(typed_lcs.collect(), weights, sorted)?
+                .into_pyarray(py)
+                .into_any(),
+        };
+        Ok(array)
     }
 
-    fn points_many(&self, lcs: Vec<(&[T], &[T])>, sorted: Option<bool>) -> Res<ndarray::Array3<T>> {
+    /// Per-observation weights of every light curve, converted from Python arrays
+    fn wrap_weights(
+        weights: Option<(WeightKind, Vec<Bound<'py, PyAny>>)>,
+    ) -> Res<(Option<WeightKind>, Vec<ContArray<T>>)> {
+        let Some((kind, w_)) = weights else {
+            return Ok((None, vec![]));
+        };
+        let w_ = w_
+            .into_iter()
+            .enumerate()
+            .map(|(i, w)| match w.downcast::<PyArray1<T>>() {
+                Ok(w) => Ok(w.readonly().as_array().into()),
+                Err(_) => Err(Exception::TypeError(format!(
+                    "weights[{}] has mismatched dtype with the lc[0][0] which is {}",
+                    i,
+                    std::any::type_name::<T>(),
+                ))),
+            })
+            .collect::<Res<Vec<_>>>()?;
+        Ok((Some(kind), w_))
+    }
+
+    /// Weightings of `n` light curves, `w_` is either empty or aligned with the light curves
+    fn typed_weights(
+        weight: Option<WeightKind>,
+        w_: &[ContArray<T>],
+        n: usize,
+    ) -> Option<Vec<Weights<'_, T>>> {
+        let kind = weight?;
+        let weights = if w_.is_empty() {
+            vec![Weights { kind, w: &[] }; n]
+        } else {
+            w_.iter()
+                .map(|w| Weights {
+                    kind,
+                    w: w.as_slice(),
+                })
+                .collect()
+        };
+        Some(weights)
+    }
+
+    /// `weights` are aligned with `lcs`
+    fn points_many(
+        &self,
+        lcs: Vec<(&[T], &[T])>,
+        weights: Option<&[Weights<T>]>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array3<T>> {
         let dmdt_shape = self.dmdt.shape();
         let mut result = ndarray::Array3::zeros((lcs.len(), dmdt_shape.0, dmdt_shape.1));
 
@@ -230,11 +1279,11 @@ where
             .build()
             .unwrap()
             .install(|| {
-                ndarray::Zip::from(result.outer_iter_mut())
+                ndarray::Zip::indexed(result.outer_iter_mut())
                     .and(lcs.into_producer())
                     .into_par_iter()
-                    .try_for_each::<_, Res<_>>(|(mut map, (t, m))| {
-                        map.assign(&self.points(t, m, sorted)?);
+                    .try_for_each::<_, Res<_>>(|(i, mut map, (t, m))| {
+                        map.assign(&self.points(t, m, weights.map(|w| w[i]), sorted)?);
                         Ok(())
                     })
             })?;
@@ -245,12 +1294,20 @@ where
     fn generic_dmdt_points_batches(
         &self,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        band_channels: Option<BandChannels>,
         sorted: Option<bool>,
         batch_size: usize,
         yield_index: bool,
         shuffle: bool,
         drop_nobs: DropNObsType,
+        augmentation: Augmentation,
         random_seed: Option<u64>,
+        shard: Option<(usize, usize)>,
+        epoch: Option<u64>,
+        start: usize,
+        prefetch: usize,
+        workers: usize,
+        output: OutputFormat,
     ) -> Res<GenericDmDtBatches<T, TmLc<T>>> {
         let typed_lcs = lcs
             .into_iter()
@@ -262,7 +1319,11 @@ where
                     (Ok(t), Ok(m)) => {
                         let t: ContArray<_> = t.as_array().into();
                         check_sorted(t.as_slice(), sorted)?;
+                        if let Some(bc) = &band_channels {
+                            Self::check_band(t.as_slice(), &bc.bands[i])?;
+                        }
                         let m: ContArray<_> = m.as_array().into();
+                        Self::check_lengths(t.as_slice(), m.as_slice(), None)?;
                         Ok((t, m))
                     }
                     _ => Err(Exception::TypeError(format!(
@@ -276,33 +1337,63 @@ where
         GenericDmDtBatches::new(
             self.clone(),
             typed_lcs,
+            band_channels,
             batch_size,
             yield_index,
             shuffle,
             drop_nobs,
+            augmentation,
             random_seed,
+            shard,
+            epoch,
+            start,
+            prefetch,
+            workers,
+            output,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn py_gausses(
         &self,
         py: Python<'py>,
         t: Arr<'py, T>,
         m: Arr<'py, T>,
         sigma: Arr<'py, T>,
+        w: Option<Arr<'py, T>>,
+        weight: Option<WeightKind>,
         sorted: Option<bool>,
+        band_channels: Option<&BandChannels>,
     ) -> Res<Bound<'py, PyUntypedArray>> {
+        let t = ContCowArray::from_view(t.as_array(), true);
+        let m = ContCowArray::from_view(m.as_array(), true);
         let err2 = Self::sigma_to_err2(sigma);
-        Ok(self
-            .gausses(
-                ContCowArray::from_view(t.as_array(), true).as_slice(),
-                ContCowArray::from_view(m.as_array(), true).as_slice(),
-                err2.as_slice(),
-                sorted,
-            )?
-            .into_pyarray(py)
-            .as_untyped()
-            .clone())
+        let w = w.map(|w| ContCowArray::from_view(w.as_array(), true));
+        let weights = weight.map(|kind| Weights {
+            kind,
+            w: w.as_ref().map(|w| w.as_slice()).unwrap_or(&[]),
+        });
+        let array = match band_channels {
+            Some(bc) => self
+                .gausses_channels(
+                    t.as_slice(),
+                    m.as_slice(),
+                    err2.as_slice(),
+                    &bc.bands[0],
+                    &bc.channels,
+                    weights,
+                    sorted,
+                )?
+                .into_pyarray(py)
+                .as_untyped()
+                .clone(),
+            None => self
+                .gausses(t.as_slice(), m.as_slice(), err2.as_slice(), weights, sorted)?
+                .into_pyarray(py)
+                .as_untyped()
+                .clone(),
+        };
+        Ok(array)
     }
 
     fn gausses(
@@ -310,15 +1401,210 @@ where
         t: &[T],
         m: &[T],
         err2: &[T],
+        weights: Option<Weights<T>>,
         sorted: Option<bool>,
     ) -> Res<ndarray::Array2<T>> {
+        Self::check_lengths(t, m, Some(err2))?;
         check_sorted(t, sorted)?;
+        Self::check_weights(t, weights)?;
+
+        let w = self.observation_weights(weights, m, err2);
+        let (m, err2) = self.dm_kind.values(m, err2);
+        Ok(self.map_gausses(t, &m, &err2, &w, weights.map(|w| w.kind)))
+    }
 
+    /// Smeared map of a sorted light curve, `m` and `err2` are transformed with
+    /// [DmKind::values] and `w` is given by [GenericDmDt::observation_weights]
+    fn map_gausses(
+        &self,
+        t: &[T],
+        m: &[T],
+        err2: &[T],
+        w: &[T],
+        weight: Option<WeightKind>,
+    ) -> ndarray::Array2<T> {
+        if self.dm_kind == DmKind::FluxRatio || weight.is_some() {
+            return self.pairs_gausses(t, m, err2, w, t, m, err2, w, true, weight);
+        }
         let mut result = match self.error_func {
             ErrorFunction::Exact => self.dmdt.gausses::<lcdmdt::ExactErf>(t, m, err2),
             ErrorFunction::Eps1Over1e3 => self.dmdt.gausses::<lcdmdt::Eps1Over1e3Erf>(t, m, err2),
         };
-        self.normalize(&mut result, t);
+        self.normalize(&mut result, || {
+            self.dmdt.dt_points(t).mapv(|x| x.approx_into().unwrap())
+        });
+        result
+    }
+
+    /// Smeared map of pairs of observations, see [GenericDmDt::pairs_points]. Pair dm
+    /// uncertainty is given by [GenericDmDt::pair_dm_err2]
+    #[allow(clippy::too_many_arguments)]
+    fn pairs_gausses(
+        &self,
+        t1: &[T],
+        m1: &[T],
+        err2_1: &[T],
+        w1: &[T],
+        t2: &[T],
+        m2: &[T],
+        err2_2: &[T],
+        w2: &[T],
+        same: bool,
+        weight: Option<WeightKind>,
+    ) -> ndarray::Array2<T> {
+        let (mut result, dt_points) = match self.error_func {
+            ErrorFunction::Exact => self.pairs_gausses_impl::<lcdmdt::ExactErf>(
+                t1, m1, err2_1, w1, t2, m2, err2_2, w2, same, weight,
+            ),
+            ErrorFunction::Eps1Over1e3 => self.pairs_gausses_impl::<lcdmdt::Eps1Over1e3Erf>(
+                t1, m1, err2_1, w1, t2, m2, err2_2, w2, same, weight,
+            ),
+        };
+        self.normalize(&mut result, || dt_points);
+        result
+    }
+
+    /// Smeared map and the total weight of pairs per each dt cell
+    #[allow(clippy::too_many_arguments)]
+    fn pairs_gausses_impl<Erf>(
+        &self,
+        t1: &[T],
+        m1: &[T],
+        err2_1: &[T],
+        w1: &[T],
+        t2: &[T],
+        m2: &[T],
+        err2_2: &[T],
+        w2: &[T],
+        same: bool,
+        weight: Option<WeightKind>,
+    ) -> (ndarray::Array2<T>, ndarray::Array1<T>)
+    where
+        Erf: lcdmdt::ErrorFunction<T>,
+    {
+        let dm_borders = self.dmdt.dm_grid.get_borders();
+        let mut result = ndarray::Array2::zeros(self.dmdt.shape());
+        let mut dt_points = ndarray::Array1::zeros(self.dmdt.dt_grid.cell_count());
+        Self::for_each_pair(t1.len(), t2.len(), same, |i, j| {
+            let dt = (t2[j] - t1[i]).abs();
+            let CellIndex::Value(idx_dt) = self.dmdt.dt_grid.idx(dt) else {
+                return;
+            };
+            let (dm, dm_err2) = self.pair_dm_err2(m1[i], err2_1[i], m2[j], err2_2[j]);
+            let pair_weight = Self::pair_weight(weight, w1, w2, i, j, dt, dm_err2);
+            if !pair_weight.is_finite() {
+                return;
+            }
+            dt_points[idx_dt] += pair_weight;
+            let sigma = dm_err2.sqrt();
+            if !dm.is_finite() || !sigma.is_finite() {
+                return;
+            }
+            let mut cdf = Erf::normal_cdf(dm_borders[0], dm, sigma);
+            for (cell, &border) in result
+                .row_mut(idx_dt)
+                .iter_mut()
+                .zip(dm_borders.iter().skip(1))
+            {
+                let next_cdf = Erf::normal_cdf(border, dm, sigma);
+                *cell += pair_weight * (next_cdf - cdf);
+                cdf = next_cdf;
+            }
+        });
+        (result, dt_points)
+    }
+
+    fn band_gausses(
+        t: &[T],
+        m: &[T],
+        err2: &[T],
+        band: &[Option<usize>],
+        index: usize,
+    ) -> (Vec<T>, Vec<T>, Vec<T>) {
+        t.iter()
+            .zip(m)
+            .zip(err2)
+            .zip(band)
+            .filter(|&(_, &b)| b == Some(index))
+            .map(|(((&t, &m), &err2), _)| (t, m, err2))
+            .unzip3()
+    }
+
+    /// Multi-channel smeared dmdt-map of multi-band light curve, shape is (channels, dt, dm)
+    fn gausses_channels(
+        &self,
+        t: &[T],
+        m: &[T],
+        err2: &[T],
+        band: &[Option<usize>],
+        channels: &[Channel],
+        weights: Option<Weights<T>>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array3<T>> {
+        Self::check_lengths(t, m, Some(err2))?;
+        check_sorted(t, sorted)?;
+        Self::check_band(t, band)?;
+        Self::check_weights(t, weights)?;
+
+        let w = self.observation_weights(weights, m, err2);
+        let weight = weights.map(|w| w.kind);
+        let (m, err2) = self.dm_kind.values(m, err2);
+        let (dt_size, dm_size) = self.dmdt.shape();
+        let mut result = ndarray::Array3::zeros((channels.len(), dt_size, dm_size));
+        for (mut map, &channel) in result.outer_iter_mut().zip(channels) {
+            match channel {
+                Channel::Band(index) => {
+                    let (t, m, err2) = Self::band_gausses(t, &m, &err2, band, index);
+                    let w = Self::band_values(&w, band, index);
+                    map.assign(&self.map_gausses(&t, &m, &err2, &w, weight));
+                }
+                Channel::Color(first, second) => {
+                    let (t1, m1, err2_1) = Self::band_gausses(t, &m, &err2, band, first);
+                    let w1 = Self::band_values(&w, band, first);
+                    let (t2, m2, err2_2) = Self::band_gausses(t, &m, &err2, band, second);
+                    let w2 = Self::band_values(&w, band, second);
+                    map.assign(&self.pairs_gausses(
+                        &t1, &m1, &err2_1, &w1, &t2, &m2, &err2_2, &w2, false, weight,
+                    ));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// `weights` are aligned with `lcs`
+    fn gausses_channels_many(
+        &self,
+        lcs: Vec<(&[T], &[T], &[T], &[Option<usize>])>,
+        channels: &[Channel],
+        weights: Option<&[Weights<T>]>,
+        sorted: Option<bool>,
+    ) -> Res<ndarray::Array4<T>> {
+        let dmdt_shape = self.dmdt.shape();
+        let mut result =
+            ndarray::Array4::zeros((lcs.len(), channels.len(), dmdt_shape.0, dmdt_shape.1));
+
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.n_jobs)
+            .build()
+            .unwrap()
+            .install(|| {
+                ndarray::Zip::indexed(result.outer_iter_mut())
+                    .and(lcs.into_producer())
+                    .into_par_iter()
+                    .try_for_each::<_, Res<_>>(|(i, mut maps, (t, m, err2, band))| {
+                        maps.assign(&self.gausses_channels(
+                            t,
+                            m,
+                            err2,
+                            band,
+                            channels,
+                            weights.map(|w| w[i]),
+                            sorted,
+                        )?);
+                        Ok(())
+                    })
+            })?;
         Ok(result)
     }
 
@@ -326,8 +1612,14 @@ where
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        weights: Option<(WeightKind, Vec<Bound<'py, PyAny>>)>,
         sorted: Option<bool>,
-    ) -> Res<Bound<'py, PyUntypedArray>> {
+        band_channels: Option<&BandChannels>,
+        output: OutputFormat,
+    ) -> Res<Bound<'py, PyAny>> {
+        let (weight, w_) = Self::wrap_weights(weights)?;
+        let weights = Self::typed_weights(weight, &w_, lcs.len());
+        let weights = weights.as_deref();
         let wrapped_lcs = lcs
             .into_iter()
             .enumerate()
@@ -358,18 +1650,58 @@ where
             .collect::<Vec<_>>();
         let typed_lcs = array_lcs
             .iter()
-            .map(|(t, m, err2)| (t.as_slice(), m.as_slice(), err2.as_slice()))
-            .collect();
-        Ok(self
-            .gausses_many(typed_lcs, sorted)?
-            .into_pyarray(py)
-            .as_untyped()
-            .clone())
+            .map(|(t, m, err2)| (t.as_slice(), m.as_slice(), err2.as_slice()));
+        if output == OutputFormat::Sparse {
+            let typed_lcs: Vec<_> = typed_lcs.collect();
+            let n_channels = band_channels.map_or(1, |bc| bc.channels.len());
+            return self
+                .sparse_many(typed_lcs.len(), n_channels, |i| {
+                    let (t, m, err2) = typed_lcs[i];
+                    match band_channels {
+                        Some(bc) => Ok(self
+                            .gausses_channels(
+                                t,
+                                m,
+                                err2,
+                                &bc.bands[i],
+                                &bc.channels,
+                                weights.map(|w| w[i]),
+                                sorted,
+                            )?
+                            .into_dyn()),
+                        None => Ok(self
+                            .gausses(t, m, err2, weights.map(|w| w[i]), sorted)?
+                            .into_dyn()),
+                    }
+                })?
+                .into_py(py);
+        }
+        let array = match band_channels {
+            Some(bc) => self
+                .gausses_channels_many(
+                    typed_lcs
+                        .zip(&bc.bands)
+                        .map(|((t, m, err2), band)| (t, m, err2, band.as_slice()))
+                        .collect(),
+                    &bc.channels,
+                    weights,
+                    sorted,
+                )?
+                .into_pyarray(py)
+                .into_any(),
+            None => self
+                .gausses_many(typed_lcs.collect(), weights, sorted)?
+                .into_pyarray(py)
+                .into_any(),
+        };
+        Ok(array)
     }
 
+    /// `weights` are aligned with `lcs`
     fn gausses_many(
         &self,
         lcs: Vec<(&[T], &[T], &[T])>,
+        weights: Option<&[Weights<T>]>,
         sorted: Option<bool>,
     ) -> Res<ndarray::Array3<T>> {
         let dmdt_shape = self.dmdt.shape();
@@ -380,11 +1712,11 @@ where
             .build()
             .unwrap()
             .install(|| {
-                ndarray::Zip::from(result.outer_iter_mut())
+                ndarray::Zip::indexed(result.outer_iter_mut())
                     .and(lcs.into_producer())
                     .into_par_iter()
-                    .try_for_each::<_, Res<_>>(|(mut map, (t, m, err2))| {
-                        map.assign(&self.gausses(t, m, err2, sorted)?);
+                    .try_for_each::<_, Res<_>>(|(i, mut map, (t, m, err2))| {
+                        map.assign(&self.gausses(t, m, err2, weights.map(|w| w[i]), sorted)?);
                         Ok(())
                     })
             })?;
@@ -395,12 +1727,20 @@ where
     fn generic_dmdt_gausses_batches(
         &self,
         lcs: Vec<(Bound<PyAny>, Bound<PyAny>, Bound<PyAny>)>,
+        band_channels: Option<BandChannels>,
         sorted: Option<bool>,
         batch_size: usize,
         yield_index: bool,
         shuffle: bool,
         drop_nobs: DropNObsType,
+        augmentation: Augmentation,
         random_seed: Option<u64>,
+        shard: Option<(usize, usize)>,
+        epoch: Option<u64>,
+        start: usize,
+        prefetch: usize,
+        workers: usize,
+        output: OutputFormat,
     ) -> Res<GenericDmDtBatches<T, Tmerr2Lc<T>>> {
         let typed_lcs = lcs
             .into_iter()
@@ -414,8 +1754,12 @@ where
                     (Ok(t), Ok(m), Ok(sigma)) => {
                         let t: ContArray<_> = t.as_array().into();
                         check_sorted(t.as_slice(), sorted)?;
+                        if let Some(bc) = &band_channels {
+                            Self::check_band(t.as_slice(), &bc.bands[i])?;
+                        }
                         let m: ContArray<_> = m.as_array().into();
                         let err2 = Self::sigma_to_err2(sigma);
+                        Self::check_lengths(t.as_slice(), m.as_slice(), Some(err2.as_slice()))?;
                         Ok((t, m, err2))
                     }
                     _ => Err(Exception::TypeError(format!(
@@ -429,11 +1773,19 @@ where
         GenericDmDtBatches::new(
             self.clone(),
             typed_lcs,
+            band_channels,
             batch_size,
             yield_index,
             shuffle,
             drop_nobs,
+            augmentation,
             random_seed,
+            shard,
+            epoch,
+            start,
+            prefetch,
+            workers,
+            output,
         )
     }
 }
@@ -444,26 +1796,74 @@ where
 {
     dmdt: GenericDmDt<T>,
     lcs: Vec<LC>,
+    band_channels: Option<BandChannels>,
     batch_size: usize,
     yield_index: bool,
     shuffle: bool,
     drop_nobs: Option<DropNObsType>,
+    augmentation: Augmentation,
     rng: Mutex<Xoshiro256PlusPlus>,
+    /// Initial state of `rng`, epoch generators are derived from it
+    base_rng: Xoshiro256PlusPlus,
+    shard: Option<(usize, usize)>,
+    output: OutputFormat,
+    /// Epoch of the next iterator, `None` if epochs are not used
+    epoch: Mutex<Option<u64>>,
+    /// Batch index to start the next iterator with
+    start: Mutex<usize>,
+    prefetch: usize,
+    workers: usize,
 }
 
 impl<T, LC> GenericDmDtBatches<T, LC>
 where
     T: lcdmdt::Float,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         dmdt: GenericDmDt<T>,
         lcs: Vec<LC>,
+        band_channels: Option<BandChannels>,
         batch_size: usize,
         yield_index: bool,
         shuffle: bool,
         drop_nobs: DropNObsType,
+        augmentation: Augmentation,
         random_seed: Option<u64>,
+        shard: Option<(usize, usize)>,
+        epoch: Option<u64>,
+        start: usize,
+        prefetch: usize,
+        workers: usize,
+        output: OutputFormat,
     ) -> Res<Self> {
+        if batch_size == 0 {
+            return Err(Exception::ValueError(String::from(
+                "batch_size must be positive",
+            )));
+        }
+        if prefetch == 0 {
+            return Err(Exception::ValueError(String::from(
+                "prefetch must be positive",
+            )));
+        }
+        if workers == 0 {
+            return Err(Exception::ValueError(String::from(
+                "workers must be positive",
+            )));
+        }
+        if let Some((rank, world_size)) = shard {
+            if rank >= world_size {
+                return Err(Exception::ValueError(format!(
+                    "shard must be (rank, world_size) with 0 <= rank < world_size, got ({rank}, {world_size})"
+                )));
+            }
+            if shuffle && random_seed.is_none() {
+                return Err(Exception::ValueError(String::from(
+                    "random_seed must be specified to shuffle sharded light curves",
+                )));
+            }
+        }
         let rng = match random_seed {
             Some(seed) => Xoshiro256PlusPlus::seed_from_u64(seed),
             None => Xoshiro256PlusPlus::from_rng(&mut rand::rng()),
@@ -484,14 +1884,170 @@ where
         Ok(Self {
             dmdt,
             lcs,
+            band_channels,
             batch_size,
             yield_index,
             shuffle,
             drop_nobs,
+            augmentation,
+            base_rng: rng.clone(),
             rng: Mutex::new(rng),
+            shard,
+            output,
+            epoch: Mutex::new(epoch),
+            start: Mutex::new(start),
+            prefetch,
+            workers,
         })
     }
 
+    /// Epoch of a new iterator, epoch counter is incremented
+    fn next_epoch(&self) -> Option<u64> {
+        let mut epoch = self.epoch.lock().unwrap();
+        let current = *epoch;
+        if let Some(epoch) = epoch.as_mut() {
+            *epoch += 1;
+        }
+        current
+    }
+
+    /// Random generator of the given epoch, it doesn't depend on the previous iterations
+    fn epoch_rng(&self, epoch: u64) -> Xoshiro256PlusPlus {
+        let mut rng = self.base_rng.clone();
+        for _ in 0..epoch {
+            rng.jump();
+        }
+        rng
+    }
+
+    /// Order of light curves of a new iterator and a random generator to derive generators of
+    /// its batches, if they are needed
+    fn iteration(
+        &self,
+        parent_rng: &mut Xoshiro256PlusPlus,
+    ) -> (Vec<usize>, Option<Xoshiro256PlusPlus>) {
+        let lcs_order: Vec<_> = match self.shuffle {
+            false => (0..self.lcs.len()).collect(),
+            true => rand::seq::index::sample(parent_rng, self.lcs.len(), self.lcs.len()).into_vec(),
+        };
+        let (lcs_order, rank) = match self.shard {
+            Some((rank, world_size)) => (
+                lcs_order
+                    .into_iter()
+                    .skip(rank)
+                    .step_by(world_size)
+                    .collect(),
+                rank,
+            ),
+            None => (lcs_order, 0),
+        };
+        let rng = match self.drop_nobs.is_some() || !self.augmentation.is_identity() {
+            true => {
+                let mut rng = Xoshiro256PlusPlus::from_rng(parent_rng);
+                // Independent streams for every shard
+                for _ in 0..rank {
+                    rng.long_jump();
+                }
+                Some(rng)
+            }
+            false => None,
+        };
+        (lcs_order, rng)
+    }
+
+    /// Computes batches of a new iteration with `workers` threads and writes them to numbered
+    /// files in `dir`, `worker` computes maps of a batch. Random generators of batches are
+    /// derived in the same way as for an iterator, so the files have the same maps
+    fn write_dataset(
+        &self,
+        dir: &Path,
+        format: DatasetFormat,
+        worker: impl Fn(&[usize], Option<Xoshiro256PlusPlus>) -> Res<ndarray::ArrayD<T>> + Sync,
+    ) -> Res<Vec<DatasetBatch>>
+    where
+        T: WritableElement,
+    {
+        std::fs::create_dir_all(dir)?;
+        remove_dataset_files(dir)?;
+        let (lcs_order, mut rng) = self.iteration(self.rng.lock().unwrap().deref_mut());
+        let mut tasks = vec![vec![]; self.workers];
+        for (i, indexes) in lcs_order.chunks(self.batch_size).enumerate() {
+            let batch_rng = rng.as_mut().map(|rng| Xoshiro256PlusPlus::from_rng(rng));
+            tasks[i % self.workers].push((i, indexes, batch_rng));
+        }
+        let worker = &worker;
+        let files = std::thread::scope(|scope| {
+            let handles: Vec<_> = tasks
+                .into_iter()
+                .map(|tasks| {
+                    scope.spawn(move || {
+                        tasks
+                            .into_iter()
+                            .map(|(i, indexes, rng)| {
+                                let maps = worker(indexes, rng)?;
+                                let file = format.write_batch(dir, i, maps.view(), indexes)?;
+                                Ok((i, file))
+                            })
+                            .collect::<Res<Vec<_>>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().map_err(|_| {
+                        Exception::RuntimeError(String::from("dataset writing thread panicked"))
+                    })?
+                })
+                .collect::<Res<Vec<_>>>()
+        })?;
+        let mut files: Vec<_> = files.into_iter().flatten().collect();
+        files.sort_unstable_by_key(|&(i, _)| i);
+        Ok(files
+            .into_iter()
+            .zip(lcs_order.chunks(self.batch_size))
+            .map(|((_, file), indexes)| DatasetBatch {
+                file,
+                index: indexes.to_vec(),
+            })
+            .collect())
+    }
+
+    /// Band indices of the observations of the i-th light curve, empty for single-channel maps
+    fn band(&self, i: usize) -> &[Option<usize>] {
+        self.band_channels
+            .as_ref()
+            .map_or(&[], |bc| bc.bands[i].as_slice())
+    }
+
+    /// Light curve with dropped observations and applied augmentations, `err2` and `band` may
+    /// be empty
+    fn augmented<R: Rng>(
+        &self,
+        rng: &mut R,
+        t: &[T],
+        m: &[T],
+        err2: &[T],
+        band: &[Option<usize>],
+    ) -> Res<AugmentedLc<T>>
+    where
+        T: ndarray::NdFloat,
+    {
+        let idx = match self.drop_nobs {
+            Some(_) => self.dropped_index(rng, t.len())?,
+            None => (0..t.len()).collect(),
+        };
+        let (mut t, mut m, mut err2, mut band) = (
+            take_index(t, &idx),
+            take_index(m, &idx),
+            take_index(err2, &idx),
+            take_index(band, &idx),
+        );
+        self.augmentation
+            .apply(rng, self.dmdt.dm_kind, &mut t, &mut m, &mut err2, &mut band);
+        Ok((t, m, err2, band))
+    }
+
     fn dropped_index<R: rand::Rng>(&self, rng: &mut R, length: usize) -> Res<Vec<usize>> {
         let drop_nobs = match self.drop_nobs {
             Some(drop_nobs) => drop_nobs,
@@ -538,9 +2094,11 @@ macro_rules! dmdt_batches {
         struct $name_iter {
             dmdt_batches: Arc<$generic>,
             lcs_order: Vec<usize>,
-            range: Range<usize>,
-            worker_thread: RwLock<Option<JoinHandle<Res<ndarray::Array3<$t>>>>>,
-            rng: Option<Xoshiro256PlusPlus>,
+            epoch: Option<u64>,
+            start: usize,
+            batch_index: usize,
+            receivers: Mutex<Vec<Receiver<Res<BatchMaps<$t>>>>>,
+            worker_threads: Vec<JoinHandle<()>>,
         }
 
         impl $name_iter {
@@ -549,67 +2107,84 @@ macro_rules! dmdt_batches {
             }
 
             fn new(dmdt_batches: Arc<$generic>) -> Self {
-                let range = 0..usize::min(dmdt_batches.batch_size, dmdt_batches.lcs.len());
-
-                let lcs_order = match dmdt_batches.shuffle {
-                    false => (0..dmdt_batches.lcs.len()).collect(),
-                    true => rand::seq::index::sample(
-                        dmdt_batches.rng.lock().unwrap().deref_mut(),
-                        dmdt_batches.lcs.len(),
-                        dmdt_batches.lcs.len(),
-                    )
-                    .into_vec(),
-                };
+                let epoch = dmdt_batches.next_epoch();
+                let start = std::mem::take(dmdt_batches.start.lock().unwrap().deref_mut());
 
-                let mut rng = match dmdt_batches.drop_nobs {
-                    Some(_) => {
-                        let mut parent_rng = dmdt_batches.rng.lock().unwrap();
-                        Self::child_rng(Some(parent_rng.deref_mut()))
-                    }
-                    None => None,
+                // Epochs have their own independent generators, otherwise the shared one is used
+                let (lcs_order, rng) = match epoch {
+                    Some(epoch) => dmdt_batches.iteration(&mut dmdt_batches.epoch_rng(epoch)),
+                    None => dmdt_batches.iteration(dmdt_batches.rng.lock().unwrap().deref_mut()),
                 };
 
-                let worker_thread = RwLock::new(Some(Self::run_worker_thread(
-                    &dmdt_batches,
-                    &lcs_order[range.start..range.end],
-                    Self::child_rng(rng.as_mut()),
-                )));
+                let (receivers, worker_threads) =
+                    Self::run_worker_threads(&dmdt_batches, &lcs_order, start, rng);
 
                 Self {
                     dmdt_batches,
                     lcs_order,
-                    range,
-                    worker_thread,
-                    rng,
+                    epoch,
+                    start,
+                    batch_index: start,
+                    receivers: Mutex::new(receivers),
+                    worker_threads,
                 }
             }
 
-            fn run_worker_thread(
+            /// Distributes batches starting from `start` over worker threads in round-robin
+            /// order, so the i-th batch is received from the ((i - start) % workers)-th channel.
+            /// Random generators of batches are derived in the batch order including the skipped
+            /// batches, so the results depend neither on the number of workers nor on `start`
+            fn run_worker_threads(
                 dmdt_batches: &Arc<$generic>,
-                indexes: &[usize],
-                rng: Option<Xoshiro256PlusPlus>,
-            ) -> JoinHandle<Res<ndarray::Array3<$t>>> {
-                let dmdt_batches = dmdt_batches.clone();
-                let indexes = indexes.to_vec();
-                std::thread::spawn(move || Self::worker(dmdt_batches, &indexes, rng))
+                lcs_order: &[usize],
+                start: usize,
+                mut rng: Option<Xoshiro256PlusPlus>,
+            ) -> (Vec<Receiver<Res<BatchMaps<$t>>>>, Vec<JoinHandle<()>>) {
+                let workers = dmdt_batches.workers;
+                let mut tasks = vec![vec![]; workers];
+                for (i, indexes) in lcs_order.chunks(dmdt_batches.batch_size).enumerate() {
+                    let batch_rng = Self::child_rng(rng.as_mut());
+                    if i >= start {
+                        tasks[(i - start) % workers].push((indexes.to_vec(), batch_rng));
+                    }
+                }
+                let capacity = dmdt_batches.prefetch.div_ceil(workers);
+                tasks
+                    .into_iter()
+                    .map(|tasks| {
+                        let dmdt_batches = dmdt_batches.clone();
+                        let (sender, receiver) = sync_channel(capacity);
+                        let handle = std::thread::spawn(move || {
+                            for (indexes, rng) in tasks {
+                                let maps = Self::worker(dmdt_batches.clone(), &indexes, rng)
+                                    .map(|a| BatchMaps::new(a, dmdt_batches.output));
+                                // Receiver is dropped if the iterator is dropped
+                                if sender.send(maps).is_err() {
+                                    break;
+                                }
+                            }
+                        });
+                        (receiver, handle)
+                    })
+                    .unzip()
             }
 
             fn worker(
                 dmdt_batches: Arc<$generic>,
                 indexes: &[usize],
                 rng: Option<Xoshiro256PlusPlus>,
-            ) -> Res<ndarray::Array3<$t>> {
+            ) -> Res<ndarray::ArrayD<$t>> {
                 $worker(dmdt_batches, indexes, rng)
             }
         }
 
         impl Drop for $name_iter {
             fn drop(&mut self) {
-                let mut worker_thread = match self.worker_thread.write() {
-                    Ok(wt) => wt,
-                    Err(_) => return,
-                };
-                std::mem::take(&mut *worker_thread)
+                // Dropping receivers stops worker threads waiting for a free slot in the channel
+                if let Ok(receivers) = self.receivers.get_mut() {
+                    receivers.clear();
+                }
+                std::mem::take(&mut self.worker_threads)
                     .into_iter()
                     .for_each(|t| drop(t.join()));
             }
@@ -617,66 +2192,52 @@ macro_rules! dmdt_batches {
 
         #[pymethods]
         impl $name_iter {
+            /// Index of the next batch, pass it as `start` to resume the iteration
+            #[getter]
+            fn position(&self) -> usize {
+                self.batch_index
+            }
+
+            /// Epoch of the iteration, `None` if epochs are not used
+            #[getter]
+            fn epoch(&self) -> Option<u64> {
+                self.epoch
+            }
+
             fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
                 slf
             }
 
             fn __next__(mut slf: PyRefMut<Self>) -> Res<Option<Bound<PyAny>>> {
-                if slf
-                    .worker_thread
-                    .read()
-                    .map_err(|_| ValueError(String::from("Error getting worker_thread")))?
-                    .is_none()
-                {
+                let batch_size = slf.dmdt_batches.batch_size;
+                let start = slf.batch_index * batch_size;
+                if start >= slf.lcs_order.len() {
                     return Ok(None);
                 }
+                let range = start..usize::min(start + batch_size, slf.lcs_order.len());
 
-                let current_range = match slf.dmdt_batches.yield_index {
-                    true => Some(slf.range.clone()),
-                    false => None,
-                };
-
-                slf.range = slf.range.end
-                    ..usize::min(
-                        slf.range.end + slf.dmdt_batches.batch_size,
-                        slf.dmdt_batches.lcs.len(),
-                    );
-
-                // Replace with None temporary to not have two workers running simultaneously
-                let array = {
-                    let mut worker_thread = slf
-                        .worker_thread
-                        .write()
-                        .map_err(|_| ValueError(String::from("Error getting worker_thread")))?;
-                    std::mem::take(&mut *worker_thread)
-                        .unwrap() // safe to unwrap because we checked that worker_thread is not None
-                        .join()
-                        .map_err(|_| ValueError(String::from("Error joining working_thread")))??
+                let maps = {
+                    let receivers = slf
+                        .receivers
+                        .lock()
+                        .map_err(|_| ValueError(String::from("Error getting receivers")))?;
+                    receivers[(slf.batch_index - slf.start) % receivers.len()]
+                        .recv()
+                        .map_err(|_| {
+                            ValueError(String::from("Error receiving batch from worker thread"))
+                        })??
                 };
-                if !slf.range.is_empty() {
-                    let rng = Self::child_rng(slf.rng.as_mut());
-                    // We know that it is None
-                    let mut worker_thread = slf
-                        .worker_thread
-                        .write()
-                        .map_err(|_| ValueError(String::from("Error getting worker_thread")))?;
-                    *worker_thread = Some(Self::run_worker_thread(
-                        &slf.dmdt_batches,
-                        &slf.lcs_order[slf.range.start..slf.range.end],
-                        rng,
-                    ));
-                }
+                slf.batch_index += 1;
 
-                let py_array = array.into_pyarray(slf.py()).into_any();
-                match current_range {
-                    Some(range) => {
+                let py_maps = maps.into_py(slf.py())?;
+                match slf.dmdt_batches.yield_index {
+                    true => {
                         let py_index =
-                            PyArray1::from_slice(slf.py(), &slf.lcs_order[range.start..range.end])
-                                .into_any();
-                        let tuple = PyTuple::new(slf.py(), &[py_index, py_array])?.into_any();
+                            PyArray1::from_slice(slf.py(), &slf.lcs_order[range]).into_any();
+                        let tuple = PyTuple::new(slf.py(), &[py_index, py_maps])?.into_any();
                         Ok(Some(tuple))
                     }
-                    None => Ok(Some(py_array)),
+                    false => Ok(Some(py_maps)),
                 }
             }
         }
@@ -692,6 +2253,7 @@ macro_rules! py_dmdt_batches {
 }
 
 type TmLc<T> = (ContArray<T>, ContArray<T>);
+type AugmentedLc<T> = (Vec<T>, Vec<T>, Vec<T>, Vec<Option<usize>>);
 type Tmerr2Lc<T> = (ContArray<T>, ContArray<T>, ContArray<T>);
 
 py_dmdt_batches!(
@@ -703,25 +2265,31 @@ py_dmdt_batches!(
                 (
                     t.as_slice(),
                     m.as_slice(),
+                    dmdt_batches.band(i),
                 )
             })
             .collect();
-        let dropped_owned_lcs = match (dmdt_batches.drop_nobs, rng) {
-            (Some(_), Some(mut rng)) => {
-                let owned_lcs: Vec<(Vec<_>, Vec<_>)> = lcs.iter().map(|(t, m)| {
-                    Ok(dmdt_batches.dropped_index(&mut rng, t.len())?.iter().map(|&i| (t[i], m[i])).unzip())
+        let augmented_owned_lcs = match rng {
+            Some(mut rng) => {
+                let owned_lcs: Vec<_> = lcs.iter().map(|(t, m, band)| {
+                    dmdt_batches.augmented(&mut rng, t, m, &[], band)
                 }).collect::<Res<_>>()?;
                 Some(owned_lcs)
             },
-            (None, None) => None,
-            (_, _) => return Err(Exception::RuntimeError(String::from("cannot be here, please report an issue")))
+            None => None,
         };
-        if let Some(owned_lcs) = &dropped_owned_lcs {
+        if let Some(owned_lcs) = &augmented_owned_lcs {
             for (ref_lc, lc) in lcs.iter_mut().zip(owned_lcs) {
-                *ref_lc = (lc.0.as_slice(), lc.1.as_slice());
+                *ref_lc = (lc.0.as_slice(), lc.1.as_slice(), lc.3.as_slice());
+            }
+        }
+        match &dmdt_batches.band_channels {
+            Some(bc) => Ok(dmdt_batches.dmdt.points_channels_many(lcs, &bc.channels, None, Some(true))?.into_dyn()),
+            None => {
+                let lcs = lcs.into_iter().map(|(t, m, _band)| (t, m)).collect();
+                Ok(dmdt_batches.dmdt.points_many(lcs, None, Some(true))?.into_dyn())
             }
         }
-        dmdt_batches.dmdt.points_many(lcs, Some(true))
     },
     GenericDmDtBatches<f32, TmLc<f32>>,
     DmDtPointsBatchesF32,
@@ -743,25 +2311,31 @@ py_dmdt_batches!(
                     t.as_slice(),
                     m.as_slice(),
                     err2.as_slice(),
+                    dmdt_batches.band(i),
                 )
             })
             .collect();
-        let dropped_owned_lcs = match (dmdt_batches.drop_nobs, rng) {
-            (Some(_), Some(mut rng)) => {
-                let owned_lcs: Vec<(Vec<_>, Vec<_>, Vec<_>)> = lcs.iter().map(|(t, m, err2)| {
-                    Ok(dmdt_batches.dropped_index(&mut rng, t.len())?.iter().map(|&i| (t[i], m[i], err2[i])).unzip3())
+        let augmented_owned_lcs = match rng {
+            Some(mut rng) => {
+                let owned_lcs: Vec<_> = lcs.iter().map(|(t, m, err2, band)| {
+                    dmdt_batches.augmented(&mut rng, t, m, err2, band)
                 }).collect::<Res<_>>()?;
                 Some(owned_lcs)
             },
-            (None, None) => None,
-            (_, _) => return Err(Exception::RuntimeError(String::from("cannot be here, please report an issue")))
+            None => None,
         };
-        if let Some(owned_lcs) = &dropped_owned_lcs {
+        if let Some(owned_lcs) = &augmented_owned_lcs {
             for (ref_lc, lc) in lcs.iter_mut().zip(owned_lcs) {
-                *ref_lc = (lc.0.as_slice(), lc.1.as_slice(), lc.2.as_slice());
+                *ref_lc = (lc.0.as_slice(), lc.1.as_slice(), lc.2.as_slice(), lc.3.as_slice());
+            }
+        }
+        match &dmdt_batches.band_channels {
+            Some(bc) => Ok(dmdt_batches.dmdt.gausses_channels_many(lcs, &bc.channels, None, Some(true))?.into_dyn()),
+            None => {
+                let lcs = lcs.into_iter().map(|(t, m, err2, _band)| (t, m, err2)).collect();
+                Ok(dmdt_batches.dmdt.gausses_many(lcs, None, Some(true))?.into_dyn())
             }
         }
-        dmdt_batches.dmdt.gausses_many(lcs, Some(true))
     },
     GenericDmDtBatches<f32, Tmerr2Lc<f32>>,
     DmDtGaussesBatchesF32,
@@ -800,12 +2374,24 @@ py_dmdt_batches!(
 ///     - 'linear' says to build a linear grid from the first and last values
 ///       of `dt`, using the same number of edges
 ///     - 'log' is the same as 'linear' but for building logarithmic grid
+///     - 'asinh' is the same as 'linear' but for building grid evenly spaced
+///       in asinh, i.e. linear for absolute values much less than unity and
+///       logarithmic for absolute values much larger than unity
 ///     - 'asis' means using the given array as a grid
 /// dm_type : str, optional
 ///     Type of `dm` grid, see `dt_type` for details
 /// norm : list of str, optional
-///     Types of normalisation, cab be any combination of "dt" and "max",
-///     default is an empty list `[]` which means no normalisation
+///     Types of normalisation, can be any combination of:
+///     - "dt" divides every dt row by the number of pairs in this dt interval
+///     - "pairs" divides the map by the total number of pairs in the dt
+///       range, which gives a probability map
+///     - "row" divides every dt row by its sum, which gives the conditional
+///       distribution P(dm|dt)
+///     - "l2" divides the map by its L2 norm
+///     - "max" divides the map by its maximum value
+///     - "log1p" replaces values `x` with `log(1 + x)`
+///     They are applied in the order: "log1p", "dt", "pairs", "row", "l2",
+///     "max". Default is an empty list `[]` which means no normalisation
 /// n_jobs : int, optional
 ///     Number of parallel threads to run bulk methods such as `points_many()`
 ///     or `gausses_batches()` default is `-1` which means to use as many
@@ -813,10 +2399,26 @@ py_dmdt_batches!(
 /// approx_erf : bool, optional
 ///     Use approximation normal CDF in `gausses*` methods, reduces accuracy,
 ///     but has better performance, default is `False`
+/// dm_kind : str, optional
+///     Definition of dm of a pair of observations `i < j`, `m` arguments of
+///     all methods are magnitudes for the default kind and fluxes otherwise:
+///     - 'mag-diff' (default) is the magnitude difference `m_j - m_i`
+///     - 'flux-ratio' is the flux ratio `f_j / f_i`
+///     - 'asinh-mag-diff' is the difference of asinh magnitudes (Lupton et
+///       al. 1999) with the softening flux `asinh_softening`, which is
+///       suitable for negative fluxes of difference imaging
+///     - 'norm-flux-diff' is the flux difference normalised by the maximum
+///       absolute flux of the light curve, `(f_j - f_i) / max |f|`
+///     Uncertainties of `gausses*` methods are propagated accordingly
+/// asinh_softening : float, optional
+///     Softening flux of 'asinh-mag-diff' dm, default is `1.0`
 ///
 /// Attributes
 /// ----------
 /// n_jobs : int
+/// dm_kind : str
+/// norm : list of str
+/// approx_erf : bool
 /// shape : (int, int)
 ///     Shape of a single dmdt map, `(dt_size, dm_size)`
 /// dt_grid : np.array of float64
@@ -825,6 +2427,10 @@ py_dmdt_batches!(
 /// dm_grid : np.array of float64
 /// min_dm : float
 /// max_dm : float
+/// dt_type : str
+///     Type of dt grid: 'linear', 'log', 'asinh' or 'asis'
+/// dm_type : str
+///     Type of dm grid, see `dt_type`
 ///
 /// Methods
 /// -------
@@ -832,28 +2438,43 @@ py_dmdt_batches!(
 ///     Construct `DmDt` with logarithmic dt grid [10^min_lgdt, 10^max_lgdt)
 ///     and linear dm grid [-max_abs_dm, max_abs_dm), `kwargs` are passed to
 ///     `__new__()`
-/// points(t, m, sorted=None)
+/// from_json(s)
+///     Construct `DmDt` from JSON string produced by `to_json()`
+/// to_json()
+///     Serialize to JSON string
+/// points(t, m, band=None, channels=None, sorted=None)
 ///     Produces dmdt-maps from light curve
-/// gausses(t, m, sigma, sorted=None)
+/// gausses(t, m, sigma, band=None, channels=None, sorted=None)
 ///     Produces smeared dmdt-map from noisy light curve
 /// count_dt(t, sorted=None)
 ///     Total number of observations per each dt interval
-/// points_many(lcs, sorted=None)
+/// points_many(lcs, bands=None, channels=None, sorted=None, output='dense')
 ///     Produces dmdt-maps from a list of light curves
-/// gausses_many(lcs, sorted=None)
+/// gausses_many(lcs, bands=None, channels=None, sorted=None, output='dense')
 ///     Produces smeared dmdt-maps from a list of light curves
 /// count_dt_many(t_, sorted=None)
 ///     Number of observations in each dt for a list of arrays
-/// points_batches(lcs, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, random_seed=None)
+/// points_batches(lcs, bands=None, channels=None, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, noise=0.0, time_shift=0.0, time_stretch=0.0, mag_offset=0.0, crop=0.0, random_seed=None, shard=None, epoch=None, start=0, prefetch=1, workers=1, output='dense')
 ///     Gives a reusable iterable which yields dmdt-maps
-/// gausses_batches(lcs, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, random_seed=None)
+/// gausses_batches(lcs, bands=None, channels=None, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, noise=0.0, time_shift=0.0, time_stretch=0.0, mag_offset=0.0, crop=0.0, random_seed=None, shard=None, epoch=None, start=0, prefetch=1, workers=1, output='dense')
 ///     Gives a reusable iterable which yields smeared dmdt-maps
+/// write_dataset(lcs, path, bands=None, channels=None, sorted=None, batch_size=1, shuffle=False, random_seed=None, workers=1, format='npy')
+///     Writes dmdt-maps to a directory of .npy or .npz batch files
+///
+/// Multi-band light curves are supported by `band`/`bands` arguments, which
+/// give multi-channel maps: a channel per band, and optionally color channels
+/// made of pairs of observations in two different bands
 ///
 #[pyclass(module = "light_curve.light_curve_ext")]
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DmDt {
     dmdt_f64: GenericDmDt<f64>,
     dmdt_f32: GenericDmDt<f32>,
+    /// Grid types, `None` for objects unpickled from older versions, use [DmDt::grid_types]
+    #[serde(default)]
+    dt_type: Option<GridType>,
+    #[serde(default)]
+    dm_type: Option<GridType>,
 }
 
 impl DmDt {
@@ -890,6 +2511,19 @@ impl DmDt {
             .map_err(|err| Exception::ValueError(err.to_string()))
     }
 
+    fn array_to_asinh_grid<T>(a: ndarray::ArrayView1<f64>) -> Res<Grid<T>>
+    where
+        T: lcdmdt::Float + ApproxFrom<f64>,
+    {
+        let n = a.len() - 1;
+        let start = f64::asinh(*a.get(0).unwrap());
+        let end = f64::asinh(*a.get(n).unwrap());
+        let borders = ndarray::Array1::from_shape_fn(a.len(), |i| {
+            f64::sinh(start + (end - start) * (i as f64) / (n as f64))
+        });
+        Self::array_to_generic_grid(borders.view())
+    }
+
     // clippy has false-positive report for 1..-1 ranges inside s!
     // https://github.com/rust-lang/rust-clippy/issues/5808
     #[allow(clippy::reversed_empty_ranges)]
@@ -934,29 +2568,202 @@ impl DmDt {
         Self::array_to_generic_grid(a)
     }
 
-    fn grids(dx: ndarray::ArrayView1<f64>, dx_type: &str) -> Res<(Grid<f32>, Grid<f64>)> {
-        let grid_f64: Grid<f64> = match dx_type {
-            "auto" => Self::array_to_auto_grid(dx)?,
-            "linear" => Self::array_to_linear_grid(dx),
-            "log" => Self::array_to_log_grid(dx),
-            "asis" => Self::array_to_generic_grid(dx)?,
+    fn grid<T>(dx: ndarray::ArrayView1<f64>, grid_type: GridType) -> Res<Grid<T>>
+    where
+        T: lcdmdt::Float + ApproxFrom<f64>,
+    {
+        match grid_type {
+            GridType::Linear => Ok(Self::array_to_linear_grid(dx)),
+            GridType::Log => Ok(Self::array_to_log_grid(dx)),
+            GridType::Asinh => Self::array_to_asinh_grid(dx),
+            GridType::Array => Self::array_to_generic_grid(dx),
+        }
+    }
+
+    fn grids(dx: ndarray::ArrayView1<f64>, dx_type: &str) -> Res<(GridType, Grid<f32>, Grid<f64>)> {
+        let grid_type = match dx_type {
+            "auto" => GridType::of(&Self::array_to_auto_grid::<f64>(dx)?),
+            "linear" => GridType::Linear,
+            "log" => GridType::Log,
+            "asinh" => GridType::Asinh,
+            "asis" => GridType::Array,
             _ => {
                 return Err(Exception::ValueError(
-                    "dt_type must be 'auto', 'linear', 'log' or 'asis'".to_owned(),
+                    "dt_type and dm_type must be 'auto', 'linear', 'log', 'asinh' or 'asis'"
+                        .to_owned(),
                 ));
             }
         };
-        let grid_f32: Grid<f32> = match grid_f64 {
-            Grid::Array(_) => Self::array_to_generic_grid(dx)?,
-            Grid::Linear(_) => Self::array_to_linear_grid(dx),
-            Grid::Lg(_) => Self::array_to_log_grid(dx),
-            _ => {
-                return Err(Exception::NotImplementedError(
-                    "this type of grid is not implemented".into(),
-                ));
+        Ok((
+            grid_type,
+            Self::grid(dx, grid_type)?,
+            Self::grid(dx, grid_type)?,
+        ))
+    }
+
+    /// Types of dt and dm grids, they are derived from the grids if not stored
+    fn grid_types(&self) -> (GridType, GridType) {
+        (
+            self.dt_type
+                .unwrap_or_else(|| GridType::of(&self.dmdt_f64.dmdt.dt_grid)),
+            self.dm_type
+                .unwrap_or_else(|| GridType::of(&self.dmdt_f64.dmdt.dm_grid)),
+        )
+    }
+
+    /// Copy with explicit grid types, which are unknown for objects unpickled from old versions
+    fn with_grid_types(&self) -> Self {
+        let (dt_type, dm_type) = self.grid_types();
+        Self {
+            dt_type: Some(dt_type),
+            dm_type: Some(dm_type),
+            ..self.clone()
+        }
+    }
+
+    /// Parses channel definitions and band labels of observations of every light curve
+    ///
+    /// Labels are mapped to indices in the order of their appearance in `channels`, which are
+    /// the sorted unique labels by default
+    fn band_channels(
+        py: Python,
+        bands: Option<Vec<Bound<PyAny>>>,
+        channels: Option<Bound<PyAny>>,
+    ) -> Res<Option<BandChannels>> {
+        let Some(bands) = bands else {
+            return match channels {
+                Some(_) => Err(Exception::ValueError(
+                    "channels can be given with band labels only".to_owned(),
+                )),
+                None => Ok(None),
+            };
+        };
+        let labels = PyDict::new(py);
+        let label_index = |label: &Bound<PyAny>| -> Res<usize> {
+            match labels.get_item(label)? {
+                Some(index) => Ok(index.extract()?),
+                None => {
+                    let index = labels.len();
+                    labels.set_item(label, index)?;
+                    Ok(index)
+                }
+            }
+        };
+        let channels = match channels {
+            Some(channels) => channels
+                .try_iter()?
+                .map(|channel| {
+                    let channel = channel?;
+                    match channel.downcast::<PyTuple>() {
+                        Ok(pair) if pair.len() == 2 => Ok(Channel::Color(
+                            label_index(&pair.get_item(0)?)?,
+                            label_index(&pair.get_item(1)?)?,
+                        )),
+                        _ => Ok(Channel::Band(label_index(&channel)?)),
+                    }
+                })
+                .collect::<Res<Vec<_>>>()?,
+            None => {
+                let unique = PySet::empty(py)?;
+                for band in &bands {
+                    for label in band.try_iter()? {
+                        unique.add(label?)?;
+                    }
+                }
+                py.import("builtins")?
+                    .getattr("sorted")?
+                    .call1((unique,))?
+                    .try_iter()?
+                    .map(|label| Ok(Channel::Band(label_index(&label?)?)))
+                    .collect::<Res<Vec<_>>>()?
             }
         };
-        Ok((grid_f32, grid_f64))
+        if channels.is_empty() {
+            return Err(Exception::ValueError(
+                "channels must not be empty".to_owned(),
+            ));
+        }
+        let bands = bands
+            .iter()
+            .map(|band| {
+                band.try_iter()?
+                    .map(|label| {
+                        Ok(labels
+                            .get_item(label?)?
+                            .map(|index| index.extract())
+                            .transpose()?)
+                    })
+                    .collect::<Res<Vec<_>>>()
+            })
+            .collect::<Res<Vec<_>>>()?;
+        Ok(Some(BandChannels { channels, bands }))
+    }
+
+    /// Band channels of a collection of light curves, `bands` must be aligned with `lcs`
+    fn band_channels_many(
+        py: Python,
+        lcs_len: usize,
+        bands: Option<Vec<Bound<PyAny>>>,
+        channels: Option<Bound<PyAny>>,
+    ) -> Res<Option<BandChannels>> {
+        if let Some(bands) = &bands {
+            if bands.len() != lcs_len {
+                return Err(Exception::ValueError(format!(
+                    "bands must have the same length as lcs, {} != {}",
+                    bands.len(),
+                    lcs_len
+                )));
+            }
+        }
+        Self::band_channels(py, bands, channels)
+    }
+
+    fn weight_kind(kind: &str, with_sigma: bool) -> Res<WeightKind> {
+        let kind = WeightKind::new(kind)?;
+        if kind.requires_sigma() && !with_sigma {
+            return Err(Exception::ValueError(format!(
+                "weight {:?} requires observation uncertainties, use gausses methods",
+                kind.name()
+            )));
+        }
+        Ok(kind)
+    }
+
+    /// Parses weighting of a single light curve, giving its kind and weights of observations
+    fn weight<'py>(
+        weight: Option<WeightArg<'py>>,
+        with_sigma: bool,
+    ) -> Res<(Option<WeightKind>, Option<Bound<'py, PyAny>>)> {
+        match weight {
+            None => Ok((None, None)),
+            Some(WeightArg::Kind(kind)) => Ok((Some(Self::weight_kind(&kind, with_sigma)?), None)),
+            Some(WeightArg::Array(w)) => Ok((Some(WeightKind::Observations), Some(w))),
+        }
+    }
+
+    /// Parses weighting of a collection of light curves, arrays of weights must be aligned with
+    /// `lcs`
+    fn weights_many(
+        lcs_len: usize,
+        weights: Option<WeightsArg>,
+        with_sigma: bool,
+    ) -> Res<Option<(WeightKind, Vec<Bound<PyAny>>)>> {
+        match weights {
+            None => Ok(None),
+            Some(WeightsArg::Kind(kind)) => {
+                Ok(Some((Self::weight_kind(&kind, with_sigma)?, vec![])))
+            }
+            Some(WeightsArg::Arrays(w_)) => {
+                if w_.len() != lcs_len {
+                    return Err(Exception::ValueError(format!(
+                        "weights must have the same length as lcs, {} != {}",
+                        w_.len(),
+                        lcs_len
+                    )));
+                }
+                Ok(Some((WeightKind::Observations, w_)))
+            }
+        }
     }
 
     fn from_dmdts(
@@ -965,14 +2772,20 @@ impl DmDt {
         norm: Vec<String>,
         n_jobs: i64,
         approx_erf: bool,
+        dm_kind: DmKind,
+        grid_types: (GridType, GridType),
     ) -> Res<Self> {
         let norm = norm
             .iter()
             .map(|s| match s.as_str() {
                 "dt" => Ok(NormFlag::Dt),
                 "max" => Ok(NormFlag::Max),
+                "pairs" => Ok(NormFlag::Pairs),
+                "l2" => Ok(NormFlag::L2),
+                "row" => Ok(NormFlag::Row),
+                "log1p" => Ok(NormFlag::Log1p),
                 _ => Err(Exception::ValueError(format!(
-                    "normalisation name {s:?} is unknown, known names are: \"dt\", \"norm\""
+                    "normalisation name {s:?} is unknown, known names are: \"dt\", \"max\", \"pairs\", \"l2\", \"row\", \"log1p\""
                 ))),
             })
             .collect::<Res<BitFlags<NormFlag>>>()?;
@@ -986,17 +2799,21 @@ impl DmDt {
             n_jobs as usize
         };
         Ok(Self {
+            dt_type: Some(grid_types.0),
+            dm_type: Some(grid_types.1),
             dmdt_f32: GenericDmDt {
                 dmdt: dmdt_f32,
                 norm,
                 error_func,
                 n_jobs,
+                dm_kind,
             },
             dmdt_f64: GenericDmDt {
                 dmdt: dmdt_f64,
                 norm,
                 error_func,
                 n_jobs,
+                dm_kind,
             },
         })
     }
@@ -1014,7 +2831,9 @@ impl DmDt {
         dm_type = "auto",
         norm=vec![],
         n_jobs = -1,
-        approx_erf = false
+        approx_erf = false,
+        dm_kind = "mag-diff",
+        asinh_softening = 1.0
     ))]
     fn __new__<'py>(
         dt: Arr<'py, f64>,
@@ -1024,9 +2843,12 @@ impl DmDt {
         norm: Vec<String>,
         n_jobs: i64,
         approx_erf: bool,
+        dm_kind: &str,
+        asinh_softening: f64,
     ) -> Res<Self> {
-        let (dt_grid_f32, dt_grid_f64) = Self::grids(dt.as_array(), dt_type)?;
-        let (dm_grid_f32, dm_grid_f64) = Self::grids(dm.as_array(), dm_type)?;
+        let dm_kind = DmKind::new(dm_kind, asinh_softening)?;
+        let (dt_type, dt_grid_f32, dt_grid_f64) = Self::grids(dt.as_array(), dt_type)?;
+        let (dm_type, dm_grid_f32, dm_grid_f64) = Self::grids(dm.as_array(), dm_type)?;
 
         let dmdt_f32: lcdmdt::DmDt<f32> = lcdmdt::DmDt {
             dt_grid: dt_grid_f32,
@@ -1037,7 +2859,15 @@ impl DmDt {
             dm_grid: dm_grid_f64,
         };
 
-        Self::from_dmdts(dmdt_f32, dmdt_f64, norm, n_jobs, approx_erf)
+        Self::from_dmdts(
+            dmdt_f32,
+            dmdt_f64,
+            norm,
+            n_jobs,
+            approx_erf,
+            dm_kind,
+            (dt_type, dm_type),
+        )
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -1051,7 +2881,9 @@ impl DmDt {
         dm_size,
         norm=vec![],
         n_jobs = -1,
-        approx_erf = false
+        approx_erf = false,
+        dm_kind = "mag-diff",
+        asinh_softening = 1.0
     ))]
     fn from_borders(
         min_lgdt: f64,
@@ -1062,7 +2894,10 @@ impl DmDt {
         norm: Vec<String>,
         n_jobs: i64,
         approx_erf: bool,
+        dm_kind: &str,
+        asinh_softening: f64,
     ) -> Res<Self> {
+        let dm_kind = DmKind::new(dm_kind, asinh_softening)?;
         let dmdt_f32 = lcdmdt::DmDt::from_lgdt_dm_limits(
             min_lgdt as f32,
             max_lgdt as f32,
@@ -1073,7 +2908,15 @@ impl DmDt {
         let dmdt_f64 =
             lcdmdt::DmDt::from_lgdt_dm_limits(min_lgdt, max_lgdt, lgdt_size, max_abs_dm, dm_size);
 
-        Self::from_dmdts(dmdt_f32, dmdt_f64, norm, n_jobs, approx_erf)
+        Self::from_dmdts(
+            dmdt_f32,
+            dmdt_f64,
+            norm,
+            n_jobs,
+            approx_erf,
+            dm_kind,
+            (GridType::Log, GridType::Linear),
+        )
     }
 
     #[getter]
@@ -1094,6 +2937,78 @@ impl DmDt {
         }
     }
 
+    #[getter]
+    fn dm_kind(&self) -> &'static str {
+        self.dmdt_f64.dm_kind.name()
+    }
+
+    #[getter]
+    fn dt_type(&self) -> &'static str {
+        self.grid_types().0.name()
+    }
+
+    #[getter]
+    fn dm_type(&self) -> &'static str {
+        self.grid_types().1.name()
+    }
+
+    #[getter]
+    fn norm(&self) -> Vec<&'static str> {
+        self.dmdt_f64.norm.iter().map(|flag| flag.name()).collect()
+    }
+
+    #[getter]
+    fn approx_erf(&self) -> bool {
+        matches!(self.dmdt_f64.error_func, ErrorFunction::Eps1Over1e3)
+    }
+
+    /// Serialize to JSON string, including grids and all options
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.with_grid_types()).unwrap()
+    }
+
+    /// Deserialize from JSON string produced by `to_json()`
+    #[staticmethod]
+    fn from_json(s: &str) -> Res<Self> {
+        serde_json::from_str(s).map_err(|err| {
+            Exception::ValueError(format!("Cannot deserialize DmDt from JSON: {err}"))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        let (dt_type, dm_type) = self.grid_types();
+        let (dt_size, dm_size) = self.shape();
+        format!(
+            "DmDt(dt_type='{}', min_dt={:?}, max_dt={:?}, dt_size={}, dm_type='{}', min_dm={:?}, max_dm={:?}, dm_size={}, norm=[{}], dm_kind='{}', approx_erf={})",
+            dt_type.name(),
+            self.min_dt(),
+            self.max_dt(),
+            dt_size,
+            dm_type.name(),
+            self.min_dm(),
+            self.max_dm(),
+            dm_size,
+            self.norm()
+                .iter()
+                .map(|name| format!("'{name}'"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.dm_kind(),
+            if self.approx_erf() { "True" } else { "False" },
+        )
+    }
+
+    /// Compares grids and options, but not `n_jobs`
+    fn __eq__(&self, other: &Self) -> bool {
+        let (a, b) = (&self.dmdt_f64, &other.dmdt_f64);
+        self.grid_types() == other.grid_types()
+            && a.dmdt.dt_grid.get_borders() == b.dmdt.dt_grid.get_borders()
+            && a.dmdt.dm_grid.get_borders() == b.dmdt.dm_grid.get_borders()
+            && a.norm == b.norm
+            && a.error_func == b.error_func
+            && a.dm_kind == b.dm_kind
+    }
+
     #[getter]
     fn shape(&self) -> (usize, usize) {
         self.dmdt_f64.dmdt.shape()
@@ -1205,6 +3120,26 @@ impl DmDt {
     ///     Time moments, must be sorted
     /// m : 1d-ndarray of float
     ///     Magnitudes
+    /// band : array-like or None, optional
+    ///     Passband labels of the observations, any hashable objects like
+    ///     strings or integers. If given, multi-channel dmdt-map is produced,
+    ///     see `channels`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-map, every channel is either a band
+    ///     label, which gives a map of the pairs of observations in this band,
+    ///     or a pair of labels `(a, b)`, which gives a color map of the pairs
+    ///     of observations in bands `a` and `b`, with dt being an absolute
+    ///     time difference and dm being `m_b - m_a`. Default is `None` which
+    ///     means one channel per band, ordered by sorted labels
+    /// weight : str or 1d-ndarray of float or None, optional
+    ///     Weighting of pairs of observations, every pair brings its weight
+    ///     instead of unity, and 'dt' and 'pairs' normalisations use the
+    ///     total weight instead of the number of pairs. 'inverse-dt' weights
+    ///     a pair by `1 / dt`, an array of non-negative weights of
+    ///     observations weights a pair by the product of its observation
+    ///     weights. Pairs of infinite weight, e.g. of zero dt, are skipped.
+    ///     See `gausses()` for the weightings using uncertainties. Default is
+    ///     `None` which means unity weights
     /// sorted : bool or None, optional
     ///     `True` guarantees that the light curve is sorted
     /// cast : bool
@@ -1214,24 +3149,58 @@ impl DmDt {
     ///
     /// Returns
     /// -------
-    /// 2d-ndarray of float
+    /// 2d-ndarray of float, or 3d-ndarray of float of shape
+    /// `(n_channels, dt_size, dm_size)` if `band` is given
     ///
-    #[pyo3(signature = (t, m, *, sorted=None, cast=false))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (t, m, *, band=None, channels=None, weight=None, sorted=None, cast=false))]
     fn points<'py>(
         &self,
         py: Python<'py>,
         t: Bound<'py, PyAny>,
         m: Bound<'py, PyAny>,
+        band: Option<Bound<'py, PyAny>>,
+        channels: Option<Bound<'py, PyAny>>,
+        weight: Option<WeightArg<'py>>,
         sorted: Option<bool>,
         cast: bool,
     ) -> Res<Bound<'py, PyUntypedArray>> {
-        dtype_dispatch!(
-            |t, m| self.dmdt_f32.py_points(py, t, m, sorted),
-            |t, m| self.dmdt_f64.py_points(py, t, m, sorted),
-            t,
-            =m;
-            cast=cast
-        )
+        let band_channels = Self::band_channels(py, band.map(|band| vec![band]), channels)?;
+        let (weight_kind, weight) = Self::weight(weight, false)?;
+        if let Some(weight) = weight {
+            dtype_dispatch!(
+                |t, m, weight| self.dmdt_f32.py_points(
+                    py,
+                    t,
+                    m,
+                    Some(weight),
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                |t, m, weight| self.dmdt_f64.py_points(
+                    py,
+                    t,
+                    m,
+                    Some(weight),
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                t,
+                =m,
+                =weight;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| self.dmdt_f32.py_points(py, t, m, None, weight_kind, sorted, band_channels.as_ref()),
+                |t, m| self.dmdt_f64.py_points(py, t, m, None, weight_kind, sorted, band_channels.as_ref()),
+                t,
+                =m;
+                cast=cast
+            )
+        }
     }
 
     /// Produces dmdt-map from a collection of light curves
@@ -1243,26 +3212,68 @@ impl DmDt {
     /// lcs : list of (ndarray, ndarray)
     ///     List or tuple of tuple pairs (t, m) represented individual light
     ///     curves. All arrays must have the same dtype
+    /// bands : list of array-like or None, optional
+    ///     Passband labels of the observations of every light curve, see
+    ///     `band` argument of `points()`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-maps, see `points()`. Default
+    ///     channels are defined by all labels of all light curves
+    /// weights : str or list of 1d-ndarray or None, optional
+    ///     Weighting of pairs of observations, either a weighting name or
+    ///     weights of observations of every light curve, see `weight`
+    ///     argument of `points()`
     /// sorted : bool or None, optional
     ///     `True` guarantees that all light curves is sorted
+    /// output : str, optional
+    ///     'dense' (default) gives a dense array, 'sparse' gives a compressed
+    ///     sparse row matrix with a flattened map per row, which saves memory
+    ///     for short light curves
     ///
     /// Returns
     /// -------
-    /// 3d-ndarray of float
+    /// 3d-ndarray of float, or 4d-ndarray of float of shape
+    /// `(n_lcs, n_channels, dt_size, dm_size)` if `bands` is given. If
+    /// `output` is 'sparse', a tuple of `(data, indices, indptr, shape)`,
+    /// which can be converted with
+    /// `scipy.sparse.csr_array((data, indices, indptr), shape=shape)`, its
+    /// rows can be reshaped to `(dt_size, dm_size)` or
+    /// `(n_channels, dt_size, dm_size)`
     ///
-    #[pyo3(signature = (lcs, *, sorted=None))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (lcs, *, bands=None, channels=None, weights=None, sorted=None, output="dense"))]
     fn points_many<'py>(
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        bands: Option<Vec<Bound<'py, PyAny>>>,
+        channels: Option<Bound<'py, PyAny>>,
+        weights: Option<WeightsArg<'py>>,
         sorted: Option<bool>,
-    ) -> Res<Bound<'py, PyUntypedArray>> {
+        output: &str,
+    ) -> Res<Bound<'py, PyAny>> {
         if lcs.is_empty() {
             Err(Exception::ValueError("lcs is empty".to_owned()))
         } else {
+            let band_channels = Self::band_channels_many(py, lcs.len(), bands, channels)?;
+            let weights = Self::weights_many(lcs.len(), weights, false)?;
+            let output = OutputFormat::new(output)?;
             dtype_dispatch!(
-                |_first_t| self.dmdt_f32.py_points_many(py, lcs, sorted),
-                |_first_t| self.dmdt_f64.py_points_many(py, lcs, sorted),
+                |_first_t| self.dmdt_f32.py_points_many(
+                    py,
+                    lcs,
+                    weights,
+                    sorted,
+                    band_channels.as_ref(),
+                    output
+                ),
+                |_first_t| self.dmdt_f64.py_points_many(
+                    py,
+                    lcs,
+                    weights,
+                    sorted,
+                    band_channels.as_ref(),
+                    output
+                ),
                 lcs[0].0
             )
         }
@@ -1281,6 +3292,11 @@ impl DmDt {
     /// lcs : list of (ndarray, ndarray)
     ///     List or tuple of tuple pairs (t, m) represented individual light
     ///     curves. All arrays must have the same dtype
+    /// bands : list of array-like or None, optional
+    ///     Passband labels of the observations of every light curve, see
+    ///     `points_many()`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-maps, see `points_many()`
     /// sorted : bool or None, optional
     ///     `True` guarantees that all light curves is sorted, default is
     ///     `None`
@@ -1298,54 +3314,146 @@ impl DmDt {
     ///     integer, it is a number of observations to drop. If it is a
     ///     floating point between 0 and 1, it is a part of observation to
     ///     drop. Default is `0`, which means usage of the original data
+    /// noise : float, optional
+    ///     Standard deviation of Gaussian noise added to `m`, in units of `m`,
+    ///     i.e. magnitudes or fluxes depending on `dm_kind`. Default is
+    ///     `0.0`, which means no noise
+    /// time_shift : float, optional
+    ///     Standard deviation of Gaussian random shifts of individual time
+    ///     moments. Note that dmdt-maps don't depend on a shift of the whole
+    ///     light curve. Default is `0.0`
+    /// time_stretch : float, optional
+    ///     Multiply time by a random factor, log-uniformly distributed between
+    ///     `1 / (1 + time_stretch)` and `1 + time_stretch`. Default is `0.0`
+    /// mag_offset : float, optional
+    ///     Add a random magnitude offset uniformly distributed in
+    ///     `[-mag_offset, mag_offset]`, independently for every passband.
+    ///     Fluxes of 'flux-ratio', 'asinh-mag-diff' and 'norm-flux-diff'
+    ///     `dm_kind` and their errors are multiplied by `10^(-0.4 offset)`
+    ///     instead. Maps of single-band light curves don't depend on the
+    ///     offset unless `dm_kind` is 'asinh-mag-diff', so it is mostly
+    ///     useful for color channels. Default is `0.0`
+    /// crop : float, optional
+    ///     Part of the time span of every light curve to cut away, the rest
+    ///     is a sub-window at random position. Must be in `[0.0, 1.0)`,
+    ///     default is `0.0` which means no cropping
     /// random_seed : int or None, optional
-    ///     Random seed for shuffling and dropping. Default is `None` which
-    ///     means random seed
+    ///     Random seed for shuffling, dropping and augmentations. Default is
+    ///     `None` which means random seed
+    /// shard : (int, int) or None, optional
+    ///     A pair of `(rank, world_size)` to iterate over the rank-th of
+    ///     `world_size` disjoint subsets of light curves, e.g. for
+    ///     data-parallel training. Every light curve belongs to the subset
+    ///     of its position in the (shuffled) order modulo `world_size`, so
+    ///     all ranks must use the same `random_seed` and epoch. Default is
+    ///     `None` which means all light curves
+    /// epoch : int or None, optional
+    ///     Epoch of the first iteration, every next iteration increments it.
+    ///     Shuffling, dropping and augmentations of an epoch depend on
+    ///     `random_seed` and the epoch only, not on the previous iterations.
+    ///     Default is `None` which means that every iteration continues the
+    ///     random stream of the previous one
+    /// start : int, optional
+    ///     Index of the batch to start the first iteration with, e.g.
+    ///     `position` attribute of an interrupted iterator. The skipped
+    ///     batches are not computed, and the following ones are the same as
+    ///     for a full iteration. Default is `0`
+    /// prefetch : int, optional
+    ///     The number of batches to compute in advance and keep queued while
+    ///     the previous ones are consumed. Default is 1
+    /// workers : int, optional
+    ///     The number of background threads computing batches concurrently,
+    ///     the order of batches and the results for a given `random_seed` do
+    ///     not depend on it. Each thread uses `n_jobs` threads for a batch.
+    ///     Default is 1
+    /// output : str, optional
+    ///     'dense' (default) or 'sparse' format of batches, see
+    ///     `points_many()`
     ///
     /// Returns
     /// -------
-    /// Iterable of 3d-ndarray or (1d-ndarray, 3d-ndarray)
+    /// Iterable of 3d-ndarray or (1d-ndarray, 3d-ndarray), 4d-ndarray is used
+    /// instead of 3d-ndarray if `bands` is given. Its iterators have
+    /// `position` and `epoch` attributes
     ///
     #[allow(clippy::too_many_arguments)]
     #[pyo3(
         signature = (
             lcs,
             *,
+            bands=None,
+            channels=None,
             sorted=None,
             batch_size=1,
             yield_index=false,
             shuffle=false,
             drop_nobs=DropNObsType::Int(0),
+            noise=0.0,
+            time_shift=0.0,
+            time_stretch=0.0,
+            mag_offset=0.0,
+            crop=0.0,
             random_seed=None,
+            shard=None,
+            epoch=None,
+            start=0,
+            prefetch=1,
+            workers=1,
+            output="dense",
         ),
-        text_signature = "(lcs, *, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, random_seed=None)",
+        text_signature = "(lcs, *, bands=None, channels=None, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, noise=0.0, time_shift=0.0, time_stretch=0.0, mag_offset=0.0, crop=0.0, random_seed=None, shard=None, epoch=None, start=0, prefetch=1, workers=1, output='dense')",
     )]
     fn points_batches<'py>(
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        bands: Option<Vec<Bound<'py, PyAny>>>,
+        channels: Option<Bound<'py, PyAny>>,
         sorted: Option<bool>,
         batch_size: usize,
         yield_index: bool,
         shuffle: bool,
         drop_nobs: DropNObsType,
+        noise: f64,
+        time_shift: f64,
+        time_stretch: f64,
+        mag_offset: f64,
+        crop: f64,
         random_seed: Option<u64>,
+        shard: Option<(usize, usize)>,
+        epoch: Option<u64>,
+        start: usize,
+        prefetch: usize,
+        workers: usize,
+        output: &str,
     ) -> Res<Bound<'py, PyAny>> {
         if lcs.is_empty() {
             Err(Exception::ValueError("lcs is empty".to_owned()))
         } else {
+            let band_channels = Self::band_channels_many(py, lcs.len(), bands, channels)?;
+            let augmentation =
+                Augmentation::new(noise, time_shift, time_stretch, mag_offset, crop)?;
+            let output = OutputFormat::new(output)?;
             dtype_dispatch!(
                 |_first_t| Ok(Bound::new(
                     py,
                     DmDtPointsBatchesF32 {
                         dmdt_batches: Arc::new(self.dmdt_f32.generic_dmdt_points_batches(
                             lcs,
+                            band_channels,
                             sorted,
                             batch_size,
                             yield_index,
                             shuffle,
                             drop_nobs,
+                            augmentation,
                             random_seed,
+                            shard,
+                            epoch,
+                            start,
+                            prefetch,
+                            workers,
+                            output,
                         )?),
                     }
                 )?
@@ -1355,12 +3463,20 @@ impl DmDt {
                     DmDtPointsBatchesF64 {
                         dmdt_batches: Arc::new(self.dmdt_f64.generic_dmdt_points_batches(
                             lcs,
+                            band_channels,
                             sorted,
                             batch_size,
                             yield_index,
                             shuffle,
                             drop_nobs,
+                            augmentation,
                             random_seed,
+                            shard,
+                            epoch,
+                            start,
+                            prefetch,
+                            workers,
+                            output,
                         )?),
                     }
                 )?
@@ -1380,6 +3496,18 @@ impl DmDt {
     ///     Magnitudes
     /// sigma : 1d-ndarray of float
     ///     Uncertainties
+    /// band : array-like or None, optional
+    ///     Passband labels of the observations, see `points()`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-map, see `points()`
+    /// weight : str or 1d-ndarray of float or None, optional
+    ///     Weighting of pairs of observations, see `points()`. Additionally,
+    ///     'inverse-variance' weights a pair by the inverse squared
+    ///     uncertainty of its dm, and 'snr' weights a pair by its
+    ///     signal-to-noise ratio `1 / sqrt(1 / snr_i^2 + 1 / snr_j^2)`, where
+    ///     the signal-to-noise ratio of an observation is `1.0857 / sigma`
+    ///     for magnitudes and `|m| / sigma` for fluxes, see `dm_kind`.
+    ///     Default is `None` which means unity weights
     /// sorted : bool or None, optional
     ///     `True` guarantees that the light curve is sorted
     /// cast : bool
@@ -1388,26 +3516,81 @@ impl DmDt {
     //      performance.
     /// Returns
     /// -------
-    /// 2d-array of float
+    /// 2d-array of float, or 3d-ndarray of float of shape
+    /// `(n_channels, dt_size, dm_size)` if `band` is given
     ///
-    #[pyo3(signature = (t, m, sigma, *, sorted=None, cast=false))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (t, m, sigma, *, band=None, channels=None, weight=None, sorted=None, cast=false))]
     fn gausses<'py>(
         &self,
         py: Python<'py>,
         t: Bound<'py, PyAny>,
         m: Bound<'py, PyAny>,
         sigma: Bound<'py, PyAny>,
+        band: Option<Bound<'py, PyAny>>,
+        channels: Option<Bound<'py, PyAny>>,
+        weight: Option<WeightArg<'py>>,
         sorted: Option<bool>,
         cast: bool,
     ) -> Res<Bound<'py, PyUntypedArray>> {
-        dtype_dispatch!(
-            |t, m, sigma| self.dmdt_f32.py_gausses(py, t, m, sigma, sorted),
-            |t, m, sigma| self.dmdt_f64.py_gausses(py, t, m, sigma, sorted),
-            t,
-            =m,
-            =sigma;
-            cast=cast
-        )
+        let band_channels = Self::band_channels(py, band.map(|band| vec![band]), channels)?;
+        let (weight_kind, weight) = Self::weight(weight, true)?;
+        if let Some(weight) = weight {
+            dtype_dispatch!(
+                |t, m, sigma, weight| self.dmdt_f32.py_gausses(
+                    py,
+                    t,
+                    m,
+                    sigma,
+                    Some(weight),
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                |t, m, sigma, weight| self.dmdt_f64.py_gausses(
+                    py,
+                    t,
+                    m,
+                    sigma,
+                    Some(weight),
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                t,
+                =m,
+                =sigma,
+                =weight;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m, sigma| self.dmdt_f32.py_gausses(
+                    py,
+                    t,
+                    m,
+                    sigma,
+                    None,
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                |t, m, sigma| self.dmdt_f64.py_gausses(
+                    py,
+                    t,
+                    m,
+                    sigma,
+                    None,
+                    weight_kind,
+                    sorted,
+                    band_channels.as_ref()
+                ),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        }
     }
 
     /// Produces smeared dmdt-map from a collection of light curves
@@ -1419,26 +3602,61 @@ impl DmDt {
     /// lcs : list of (ndarray, ndarray, ndarray)
     ///     List or tuple of tuple pairs (t, m, sigma) represented individual
     ///     light curves. All arrays must have the same dtype
+    /// bands : list of array-like or None, optional
+    ///     Passband labels of the observations of every light curve, see
+    ///     `points_many()`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-maps, see `points_many()`
+    /// weights : str or list of 1d-ndarray or None, optional
+    ///     Weighting of pairs of observations, either a weighting name or
+    ///     weights of observations of every light curve, see `weight`
+    ///     argument of `gausses()`
     /// sorted : bool or None, optional
     ///     `True` guarantees that all light curves are sorted
+    /// output : str, optional
+    ///     'dense' (default) or 'sparse', see `points_many()`
     ///
     /// Returns
     /// -------
-    /// 3d-ndarray of float
+    /// 3d-ndarray of float, or 4d-ndarray of float of shape
+    /// `(n_lcs, n_channels, dt_size, dm_size)` if `bands` is given, or a
+    /// tuple of sparse matrix components, see `points_many()`
     ///
-    #[pyo3(signature = (lcs, *, sorted=None))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (lcs, *, bands=None, channels=None, weights=None, sorted=None, output="dense"))]
     fn gausses_many<'py>(
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        bands: Option<Vec<Bound<'py, PyAny>>>,
+        channels: Option<Bound<'py, PyAny>>,
+        weights: Option<WeightsArg<'py>>,
         sorted: Option<bool>,
-    ) -> Res<Bound<'py, PyUntypedArray>> {
+        output: &str,
+    ) -> Res<Bound<'py, PyAny>> {
         if lcs.is_empty() {
             Err(Exception::ValueError("lcs is empty".to_owned()))
         } else {
+            let band_channels = Self::band_channels_many(py, lcs.len(), bands, channels)?;
+            let weights = Self::weights_many(lcs.len(), weights, true)?;
+            let output = OutputFormat::new(output)?;
             dtype_dispatch!(
-                |_first_t| self.dmdt_f32.py_gausses_many(py, lcs, sorted),
-                |_first_t| self.dmdt_f64.py_gausses_many(py, lcs, sorted),
+                |_first_t| self.dmdt_f32.py_gausses_many(
+                    py,
+                    lcs,
+                    weights,
+                    sorted,
+                    band_channels.as_ref(),
+                    output
+                ),
+                |_first_t| self.dmdt_f64.py_gausses_many(
+                    py,
+                    lcs,
+                    weights,
+                    sorted,
+                    band_channels.as_ref(),
+                    output
+                ),
                 lcs[0].0
             )
         }
@@ -1457,6 +3675,11 @@ impl DmDt {
     /// lcs : list of (ndarray, ndarray, ndarray)
     ///     List or tuple of tuple pairs (t, m, sigma) represented individual
     ///     light curves. All arrays must have the same dtype
+    /// bands : list of array-like or None, optional
+    ///     Passband labels of the observations of every light curve, see
+    ///     `points_many()`
+    /// channels : list or None, optional
+    ///     Channels of multi-channel dmdt-maps, see `points_many()`
     /// sorted : bool or None, optional
     ///     `True` guarantees that all light curves is sorted, default is
     ///     `None`
@@ -1474,50 +3697,139 @@ impl DmDt {
     ///     integer, it is a number of observations to drop. If it is a
     ///     floating point between 0 and 1, it is a part of observation to
     ///     drop. Default is `0`, which means usage of the original data
+    /// noise : float, optional
+    ///     Standard deviation of Gaussian noise added to `m`, in units of
+    ///     `sigma`. Default is `0.0`, which means no noise
+    /// time_shift : float, optional
+    ///     Standard deviation of Gaussian random shifts of individual time
+    ///     moments. Note that dmdt-maps don't depend on a shift of the whole
+    ///     light curve. Default is `0.0`
+    /// time_stretch : float, optional
+    ///     Multiply time by a random factor, log-uniformly distributed between
+    ///     `1 / (1 + time_stretch)` and `1 + time_stretch`. Default is `0.0`
+    /// mag_offset : float, optional
+    ///     Add a random magnitude offset uniformly distributed in
+    ///     `[-mag_offset, mag_offset]`, independently for every passband.
+    ///     Fluxes of 'flux-ratio', 'asinh-mag-diff' and 'norm-flux-diff'
+    ///     `dm_kind` and their errors are multiplied by `10^(-0.4 offset)`
+    ///     instead. Maps of single-band light curves don't depend on the
+    ///     offset unless `dm_kind` is 'asinh-mag-diff', so it is mostly
+    ///     useful for color channels. Default is `0.0`
+    /// crop : float, optional
+    ///     Part of the time span of every light curve to cut away, the rest
+    ///     is a sub-window at random position. Must be in `[0.0, 1.0)`,
+    ///     default is `0.0` which means no cropping
     /// random_seed : int or None, optional
-    ///     Random seed for shuffling and dropping. Default is `None` which
-    ///     means random seed
+    ///     Random seed for shuffling, dropping and augmentations. Default is
+    ///     `None` which means random seed
+    /// shard : (int, int) or None, optional
+    ///     A pair of `(rank, world_size)` to iterate over the rank-th of
+    ///     `world_size` disjoint subsets of light curves, e.g. for
+    ///     data-parallel training. Every light curve belongs to the subset
+    ///     of its position in the (shuffled) order modulo `world_size`, so
+    ///     all ranks must use the same `random_seed` and epoch. Default is
+    ///     `None` which means all light curves
+    /// epoch : int or None, optional
+    ///     Epoch of the first iteration, every next iteration increments it.
+    ///     Shuffling, dropping and augmentations of an epoch depend on
+    ///     `random_seed` and the epoch only, not on the previous iterations.
+    ///     Default is `None` which means that every iteration continues the
+    ///     random stream of the previous one
+    /// start : int, optional
+    ///     Index of the batch to start the first iteration with, e.g.
+    ///     `position` attribute of an interrupted iterator. The skipped
+    ///     batches are not computed, and the following ones are the same as
+    ///     for a full iteration. Default is `0`
+    /// prefetch : int, optional
+    ///     The number of batches to compute in advance and keep queued while
+    ///     the previous ones are consumed. Default is 1
+    /// workers : int, optional
+    ///     The number of background threads computing batches concurrently,
+    ///     the order of batches and the results for a given `random_seed` do
+    ///     not depend on it. Each thread uses `n_jobs` threads for a batch.
+    ///     Default is 1
+    /// output : str, optional
+    ///     'dense' (default) or 'sparse' format of batches, see
+    ///     `points_many()`
     ///
     #[allow(clippy::too_many_arguments)]
     #[pyo3(
         signature = (
             lcs,
             *,
+            bands=None,
+            channels=None,
             sorted=None,
             batch_size=1,
             yield_index=false,
             shuffle=false,
             drop_nobs=DropNObsType::Int(0),
+            noise=0.0,
+            time_shift=0.0,
+            time_stretch=0.0,
+            mag_offset=0.0,
+            crop=0.0,
             random_seed=None,
+            shard=None,
+            epoch=None,
+            start=0,
+            prefetch=1,
+            workers=1,
+            output="dense",
         ),
-        text_signature = "($self, lcs, *, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, random_seed=None)"
+        text_signature = "($self, lcs, *, bands=None, channels=None, sorted=None, batch_size=1, yield_index=False, shuffle=False, drop_nobs=0, noise=0.0, time_shift=0.0, time_stretch=0.0, mag_offset=0.0, crop=0.0, random_seed=None, shard=None, epoch=None, start=0, prefetch=1, workers=1, output='dense')"
     )]
     fn gausses_batches<'py>(
         &self,
         py: Python<'py>,
         lcs: Vec<(Bound<'py, PyAny>, Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+        bands: Option<Vec<Bound<'py, PyAny>>>,
+        channels: Option<Bound<'py, PyAny>>,
         sorted: Option<bool>,
         batch_size: usize,
         yield_index: bool,
         shuffle: bool,
         drop_nobs: DropNObsType,
+        noise: f64,
+        time_shift: f64,
+        time_stretch: f64,
+        mag_offset: f64,
+        crop: f64,
         random_seed: Option<u64>,
+        shard: Option<(usize, usize)>,
+        epoch: Option<u64>,
+        start: usize,
+        prefetch: usize,
+        workers: usize,
+        output: &str,
     ) -> Res<Bound<'py, PyAny>> {
         if lcs.is_empty() {
             Err(Exception::ValueError("lcs is empty".to_owned()))
         } else {
+            let band_channels = Self::band_channels_many(py, lcs.len(), bands, channels)?;
+            let augmentation =
+                Augmentation::new(noise, time_shift, time_stretch, mag_offset, crop)?;
+            let output = OutputFormat::new(output)?;
             dtype_dispatch!(
                 |_first_t| Ok(Bound::new(
                     py,
                     DmDtGaussesBatchesF32 {
                         dmdt_batches: Arc::new(self.dmdt_f32.generic_dmdt_gausses_batches(
                             lcs,
+                            band_channels,
                             sorted,
                             batch_size,
                             yield_index,
                             shuffle,
                             drop_nobs,
+                            augmentation,
                             random_seed,
+                            shard,
+                            epoch,
+                            start,
+                            prefetch,
+                            workers,
+                            output,
                         )?),
                     }
                 )?
@@ -1527,12 +3839,20 @@ impl DmDt {
                     DmDtGaussesBatchesF64 {
                         dmdt_batches: Arc::new(self.dmdt_f64.generic_dmdt_gausses_batches(
                             lcs,
+                            band_channels,
                             sorted,
                             batch_size,
                             yield_index,
                             shuffle,
                             drop_nobs,
+                            augmentation,
                             random_seed,
+                            shard,
+                            epoch,
+                            start,
+                            prefetch,
+                            workers,
+                            output,
                         )?),
                     }
                 )?
@@ -1542,6 +3862,202 @@ impl DmDt {
         }
     }
 
+    /// Write dmdt-maps of light curves to a directory of batch files
+    ///
+    /// Maps are computed in the same way as by `points_batches()` or
+    /// `gausses_batches()`, depending on whether light curves have
+    /// uncertainties, and written as `batch_000000.npy`, `batch_000001.npy`,
+    /// etc. The directory also gets `index.json` describing the dataset:
+    /// file format, dtype, map shape, the DmDt object, and the file name and
+    /// light curve indexes of every batch. Files of the first iteration of
+    /// `points_batches()` or `gausses_batches()` with the same `random_seed`
+    /// have the same maps. Batch files and `index.json` of a dataset
+    /// previously written to the directory are removed, other files are kept
+    ///
+    /// Parameters
+    /// ----------
+    /// lcs : list of (np.ndarray, np.ndarray) or (np.ndarray, np.ndarray, np.ndarray)
+    ///     List of tuples of `(t, m)` or `(t, m, sigma)` arrays, all arrays
+    ///     must have the same dtype
+    /// path : str or os.PathLike
+    ///     Directory to write files to, it is created if it doesn't exist
+    /// bands : list of np.ndarray or None, optional
+    ///     Passband labels of observations of every light curve, aligned
+    ///     with `lcs`. Required if `channels` is given
+    /// channels : list or None, optional
+    ///     Channel definitions, see `points()`
+    /// sorted : bool or None, optional
+    ///     `True` guarantees that light curves are sorted, default is `None`
+    /// batch_size : int, optional
+    ///     The number of dmdt-maps in every file. The last file can have
+    ///     less. Default is 1
+    /// shuffle : bool, optional
+    ///     If `True`, shuffle light curves before splitting them into
+    ///     batches. Default is `False`
+    /// random_seed : int or None, optional
+    ///     Random seed for shuffling. Default is `None` which means random
+    ///     seed
+    /// workers : int, optional
+    ///     The number of threads computing and writing batches concurrently,
+    ///     the files do not depend on it. Default is 1
+    /// format : str, optional
+    ///     'npy' (default) to write maps only, or 'npz' to write
+    ///     uncompressed archives of `maps` and `index` arrays, the latter
+    ///     are indexes of light curves in `lcs`
+    ///
+    /// Returns
+    /// -------
+    /// None
+    ///
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(
+        signature = (
+            lcs,
+            path,
+            *,
+            bands=None,
+            channels=None,
+            sorted=None,
+            batch_size=1,
+            shuffle=false,
+            random_seed=None,
+            workers=1,
+            format="npy",
+        ),
+        text_signature = "($self, lcs, path, *, bands=None, channels=None, sorted=None, batch_size=1, shuffle=False, random_seed=None, workers=1, format='npy')"
+    )]
+    fn write_dataset<'py>(
+        &self,
+        py: Python<'py>,
+        lcs: Vec<Bound<'py, PyAny>>,
+        path: PathBuf,
+        bands: Option<Vec<Bound<'py, PyAny>>>,
+        channels: Option<Bound<'py, PyAny>>,
+        sorted: Option<bool>,
+        batch_size: usize,
+        shuffle: bool,
+        random_seed: Option<u64>,
+        workers: usize,
+        format: &str,
+    ) -> Res<()> {
+        if lcs.is_empty() {
+            return Err(Exception::ValueError("lcs is empty".to_owned()));
+        }
+        let format = DatasetFormat::new(format)?;
+        let band_channels = Self::band_channels_many(py, lcs.len(), bands, channels)?;
+        let (dt_size, dm_size) = self.shape();
+        let map_shape = match &band_channels {
+            Some(bc) => vec![bc.channels.len(), dt_size, dm_size],
+            None => vec![dt_size, dm_size],
+        };
+        let n_lcs = lcs.len();
+        let augmentation = Augmentation::new(0.0, 0.0, 0.0, 0.0, 0.0)?;
+        // Batches of all dtypes and light curve kinds are written in the same way, they differ by
+        // the batch constructor and the worker
+        macro_rules! write_batches {
+            ($dmdt: expr, $batches: ident, $iter: ty, $dtype: literal, $lcs: expr) => {{
+                let dmdt_batches = Arc::new($dmdt.$batches(
+                    $lcs,
+                    band_channels,
+                    sorted,
+                    batch_size,
+                    false,
+                    shuffle,
+                    DropNObsType::Int(0),
+                    augmentation,
+                    random_seed,
+                    None,
+                    None,
+                    0,
+                    1,
+                    workers,
+                    OutputFormat::Dense,
+                )?);
+                let batches = dmdt_batches.write_dataset(&path, format, |indexes, rng| {
+                    <$iter>::worker(dmdt_batches.clone(), indexes, rng)
+                })?;
+                Ok(($dtype, batches))
+            }};
+        }
+        let (dtype, batches) = match lcs[0].len()? {
+            2 => {
+                let lcs = lcs
+                    .iter()
+                    .map(|lc| lc.extract())
+                    .collect::<PyResult<Vec<(Bound<PyAny>, Bound<PyAny>)>>>()?;
+                dtype_dispatch!(
+                    |_first_t| -> Res<_> {
+                        write_batches!(
+                            self.dmdt_f32,
+                            generic_dmdt_points_batches,
+                            DmDtPointsIterF32,
+                            "<f4",
+                            lcs
+                        )
+                    },
+                    |_first_t| -> Res<_> {
+                        write_batches!(
+                            self.dmdt_f64,
+                            generic_dmdt_points_batches,
+                            DmDtPointsIterF64,
+                            "<f8",
+                            lcs
+                        )
+                    },
+                    lcs[0].0
+                )?
+            }
+            3 => {
+                let lcs = lcs
+                    .iter()
+                    .map(|lc| lc.extract())
+                    .collect::<PyResult<Vec<(Bound<PyAny>, Bound<PyAny>, Bound<PyAny>)>>>()?;
+                dtype_dispatch!(
+                    |_first_t| -> Res<_> {
+                        write_batches!(
+                            self.dmdt_f32,
+                            generic_dmdt_gausses_batches,
+                            DmDtGaussesIterF32,
+                            "<f4",
+                            lcs
+                        )
+                    },
+                    |_first_t| -> Res<_> {
+                        write_batches!(
+                            self.dmdt_f64,
+                            generic_dmdt_gausses_batches,
+                            DmDtGaussesIterF64,
+                            "<f8",
+                            lcs
+                        )
+                    },
+                    lcs[0].0
+                )?
+            }
+            n => {
+                return Err(Exception::ValueError(format!(
+                    "lcs elements must be tuples of (t, m) or (t, m, sigma), lcs[0] has {n} elements"
+                )));
+            }
+        };
+        let index = DatasetIndex {
+            format: format.name(),
+            dtype,
+            map_shape,
+            n_lcs,
+            batch_size,
+            shuffle,
+            random_seed,
+            dmdt: self.with_grid_types(),
+            batches,
+        };
+        let index = serde_json::to_string(&index).map_err(|err| {
+            Exception::RuntimeError(format!("cannot serialize dataset index: {err}"))
+        })?;
+        std::fs::write(path.join("index.json"), index)?;
+        Ok(())
+    }
+
     /// Used by pickle.load / pickle.loads
     fn __setstate__(&mut self, state: Bound<PyBytes>) -> Res<()> {
         *self = serde_pickle::from_slice(state.as_bytes(), serde_pickle::DeOptions::new())
@@ -1,6 +1,6 @@
 use pyo3::PyErr;
 use pyo3::exceptions::{
-    PyIndexError, PyNotImplementedError, PyRuntimeError, PyTypeError, PyValueError,
+    PyIndexError, PyNotImplementedError, PyOSError, PyRuntimeError, PyTypeError, PyValueError,
 };
 use pyo3::import_exception;
 use std::fmt::Debug;
@@ -17,6 +17,7 @@ pub(crate) enum Exception {
     // builtins
     IndexError(String),
     NotImplementedError(String),
+    OSError(String),
     RuntimeError(String),
     TypeError(String),
     ValueError(String),
@@ -33,6 +34,7 @@ impl From<Exception> for PyErr {
             // builtins
             Exception::IndexError(err) => PyIndexError::new_err(err),
             Exception::NotImplementedError(err) => PyNotImplementedError::new_err(err),
+            Exception::OSError(err) => PyOSError::new_err(err),
             Exception::RuntimeError(err) => PyRuntimeError::new_err(err),
             Exception::TypeError(err) => PyTypeError::new_err(err),
             Exception::ValueError(err) => PyValueError::new_err(err),
@@ -51,4 +53,10 @@ impl From<light_curve_feature::EvaluatorError> for Exception {
     }
 }
 
+impl From<std::io::Error> for Exception {
+    fn from(err: std::io::Error) -> Self {
+        Exception::OSError(err.to_string())
+    }
+}
+
 pub(crate) type Res<T> = Result<T, Exception>;
@@ -0,0 +1,490 @@
+use crate::errors::{Exception, Res};
+use crate::fit_derived::FitDerived;
+use crate::fourier::FourierDecomposition;
+use crate::periodogram::PeriodogramEvaluator;
+use crate::transform::{CustomTransformer, StockTransformer};
+
+use light_curve_feature::transformers::{
+    bazin_fit::BazinFitTransformer, linexp_fit::LinexpFitTransformer,
+    villar_fit::VillarFitTransformer,
+};
+use light_curve_feature::{self as lcf, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// Transformer applied to a single output value, `None` means that the transformation is unknown
+/// or cannot be inverted
+pub(crate) type OutputTransformer = Option<CustomTransformer>;
+
+/// Feature evaluator used by Python classes
+///
+/// It is either a `light-curve-feature` evaluator or an evaluator implemented in this crate. The
+/// latter cannot be put into `light-curve-feature` meta-features such as `Bins`, see
+/// [Evaluator::to_lcf]. `Lcf` variant is untagged, so it is serialized exactly as
+/// `light-curve-feature` does, which keeps `to_json()` output of non-transformed features
+/// compatible with the Rust crate.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum Evaluator<T>
+where
+    T: lcf::Float,
+{
+    /// Evaluator with known output transformers, which cannot be inspected otherwise
+    Annotated {
+        evaluator: Box<Evaluator<T>>,
+        output_transformers: Vec<OutputTransformer>,
+    },
+    Composed(Vec<Evaluator<T>>),
+    CustomTransformed(CustomTransformed<T>),
+    FitDerived(FitDerived<T>),
+    FourierDecomposition(FourierDecomposition<T>),
+    Periodogram(PeriodogramEvaluator<T>),
+    #[serde(untagged)]
+    Lcf(lcf::Feature<T>),
+}
+
+impl<T> From<lcf::Feature<T>> for Evaluator<T>
+where
+    T: lcf::Float,
+{
+    fn from(feature: lcf::Feature<T>) -> Self {
+        Self::Lcf(feature)
+    }
+}
+
+impl<T> Evaluator<T>
+where
+    T: lcf::Float,
+{
+    /// Apply custom transformer to the evaluator output
+    pub(crate) fn custom_transformed(self, transformer: CustomTransformer) -> Res<Self> {
+        Ok(Self::CustomTransformed(CustomTransformed::new(
+            self,
+            transformer,
+        )?))
+    }
+
+    /// Annotate the evaluator with output transformers, if they differ from the known ones
+    pub(crate) fn annotated(self, output_transformers: Vec<OutputTransformer>) -> Self {
+        if self.output_transformers() == output_transformers {
+            return self;
+        }
+        let evaluator = match self {
+            Self::Annotated { evaluator, .. } => evaluator,
+            evaluator => Box::new(evaluator),
+        };
+        Self::Annotated {
+            evaluator,
+            output_transformers,
+        }
+    }
+
+    /// Apply `light-curve-feature` transformer, the evaluator must be convertible to
+    /// `light-curve-feature` one
+    ///
+    /// `output_transformer` is the same transformer applied to every output value, `None` if it
+    /// is not element-wise or cannot be inverted
+    pub(crate) fn lcf_transformed(
+        self,
+        transformer: lcf::Transformer<T>,
+        output_transformer: OutputTransformer,
+    ) -> Res<Self> {
+        let feature = self.to_lcf().ok_or_else(|| {
+            Exception::ValueError(
+                "this feature cannot be transformed with light-curve-feature transformer"
+                    .to_string(),
+            )
+        })?;
+        let inner_transformers = self.output_transformers();
+        let transformed = Self::Lcf(
+            lcf::Transformed::new(feature, transformer)
+                .map_err(|err| {
+                    Exception::ValueError(format!(
+                        "feature and transformation are incompatible: {err:?}"
+                    ))
+                })?
+                .into(),
+        );
+        let output_transformers = match output_transformer {
+            Some(outer) if inner_transformers.len() == transformed.size_hint() => {
+                inner_transformers
+                    .into_iter()
+                    .map(|inner| inner.map(|inner| inner.then(outer.clone())))
+                    .collect()
+            }
+            _ => vec![None; transformed.size_hint()],
+        };
+        Ok(transformed.annotated(output_transformers))
+    }
+
+    /// Apply stock transformer, the result is a `light-curve-feature` evaluator if it is possible
+    pub(crate) fn stock_transformed(self, stock_transformer: StockTransformer) -> Res<Self> {
+        let output_transformer = CustomTransformer::Stock(stock_transformer);
+        if self.to_lcf().is_some() {
+            self.lcf_transformed(
+                stock_transformer.lcf_transformer(),
+                Some(output_transformer),
+            )
+        } else {
+            self.custom_transformed(output_transformer)
+        }
+    }
+
+    /// Apply default transformer of parametric fit evaluator
+    pub(crate) fn fit_default_transformed(self) -> Res<Self> {
+        let not_a_fit = || Exception::ValueError("evaluator is not a parametric fit".to_string());
+        match self {
+            Self::FitDerived(fit_derived) => {
+                Ok(Self::FitDerived(fit_derived.default_transformed()?))
+            }
+            Self::Lcf(feature) => {
+                let transformer: lcf::Transformer<T> = match &feature {
+                    lcf::Feature::BazinFit(_) => BazinFitTransformer::default().into(),
+                    lcf::Feature::LinexpFit(_) => LinexpFitTransformer::default().into(),
+                    lcf::Feature::VillarFit(_) => VillarFitTransformer::default().into(),
+                    _ => return Err(not_a_fit()),
+                };
+                Self::Lcf(feature).lcf_transformed(transformer, None)
+            }
+            _ => Err(not_a_fit()),
+        }
+    }
+
+    /// Transformers applied to every output value
+    pub(crate) fn output_transformers(&self) -> Vec<OutputTransformer> {
+        match self {
+            Self::Annotated {
+                output_transformers,
+                ..
+            } => output_transformers.clone(),
+            Self::Composed(evaluators) => evaluators
+                .iter()
+                .flat_map(Self::output_transformers)
+                .collect(),
+            Self::CustomTransformed(transformed) => transformed.output_transformers(),
+            Self::FitDerived(fit_derived) => fit_derived.output_transformers(),
+            Self::FourierDecomposition(fourier) => fourier.output_transformers(),
+            Self::Periodogram(periodogram) => periodogram.output_transformers(),
+            Self::Lcf(feature) => Self::lcf_output_transformers(feature),
+        }
+    }
+
+    /// Output transformers of a `light-curve-feature` evaluator which is not annotated
+    ///
+    /// Evaluators with transformations inside are annotated with their output transformers when
+    /// they are built, see [Evaluator::lcf_transformed] and [Evaluator::compose], so the rest are
+    /// not transformed. The only exception is a transformed evaluator deserialized from
+    /// `light-curve-feature` JSON, its output transformers are unknown.
+    fn lcf_output_transformers(feature: &lcf::Feature<T>) -> Vec<OutputTransformer> {
+        match feature {
+            lcf::Feature::Transformed(_) => vec![None; feature.size_hint()],
+            _ => vec![Some(CustomTransformer::identity()); feature.size_hint()],
+        }
+    }
+
+    /// If the evaluator has a part transformed with [CustomTransformer], which cannot be represented
+    /// by `light-curve-feature` evaluator
+    pub(crate) fn is_custom_transformed(&self) -> bool {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.is_custom_transformed(),
+            Self::Composed(evaluators) => evaluators.iter().any(Self::is_custom_transformed),
+            Self::CustomTransformed(_) => true,
+            Self::FitDerived(_)
+            | Self::FourierDecomposition(_)
+            | Self::Periodogram(_)
+            | Self::Lcf(_) => false,
+        }
+    }
+
+    /// If the evaluator output is already transformed
+    pub(crate) fn is_transformed(&self) -> bool {
+        self.output_transformers().iter().any(
+            |transformer| !matches!(transformer, Some(transformer) if transformer.is_identity()),
+        )
+    }
+
+    /// Compose evaluators, the result is a `light-curve-feature` extractor if it is possible
+    pub(crate) fn compose(evaluators: Vec<Self>) -> Self {
+        match evaluators
+            .iter()
+            .map(Self::to_lcf)
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(features) => {
+                let output_transformers = evaluators
+                    .iter()
+                    .flat_map(Self::output_transformers)
+                    .collect();
+                Self::Lcf(lcf::FeatureExtractor::new(features).into())
+                    .annotated(output_transformers)
+            }
+            None => Self::Composed(evaluators),
+        }
+    }
+
+    /// Convert to `light-curve-feature` evaluator, `None` if this evaluator is specific for this
+    /// crate
+    pub(crate) fn to_lcf(&self) -> Option<lcf::Feature<T>> {
+        self.lcf_feature(false)
+    }
+
+    /// Convert to `light-curve-feature` evaluator to be used inside `light-curve-feature`
+    /// meta-features, which always evaluate periodograms ignoring observation errors, `None` for
+    /// periodograms with outputs not supported by `light-curve-feature`
+    pub(crate) fn to_lcf_unweighted(&self) -> Option<lcf::Feature<T>> {
+        self.lcf_feature(true)
+    }
+
+    fn lcf_feature(&self, unweighted_periodogram: bool) -> Option<lcf::Feature<T>> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.lcf_feature(unweighted_periodogram),
+            Self::Composed(evaluators) => Some(
+                lcf::FeatureExtractor::new(
+                    evaluators
+                        .iter()
+                        .map(|evaluator| evaluator.lcf_feature(unweighted_periodogram))
+                        .collect::<Option<Vec<_>>>()?,
+                )
+                .into(),
+            ),
+            Self::CustomTransformed(_) | Self::FitDerived(_) | Self::FourierDecomposition(_) => {
+                None
+            }
+            Self::Periodogram(periodogram) if unweighted_periodogram => {
+                periodogram.to_lcf_unweighted()
+            }
+            Self::Periodogram(_) => None,
+            Self::Lcf(feature) => Some(feature.clone()),
+        }
+    }
+
+    /// Evaluate time series, `has_errors` tells if observation errors are given, see
+    /// [PeriodogramEvaluator::is_classic]
+    pub(crate) fn eval(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.eval(ts, has_errors),
+            Self::Composed(evaluators) => {
+                let mut values = Vec::with_capacity(self.size_hint());
+                for evaluator in evaluators {
+                    values.extend(evaluator.eval(ts, has_errors)?);
+                }
+                Ok(values)
+            }
+            Self::CustomTransformed(transformed) => transformed.eval(ts, has_errors),
+            Self::FitDerived(fit_derived) => fit_derived.eval(ts),
+            Self::FourierDecomposition(fourier) => fourier.eval(ts, has_errors),
+            Self::Periodogram(periodogram) => periodogram.eval(ts, has_errors),
+            Self::Lcf(feature) => feature.eval(ts),
+        }
+    }
+
+    /// Evaluate multi-band time series, `None` if the evaluator is not a periodogram, see
+    /// [PeriodogramEvaluator::eval_multiband]
+    pub(crate) fn eval_multiband(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        band: &[usize],
+    ) -> Option<Result<Vec<T>, lcf::EvaluatorError>> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.eval_multiband(ts, band),
+            Self::CustomTransformed(transformed) => transformed.eval_multiband(ts, band),
+            Self::Periodogram(periodogram) => Some(periodogram.eval_multiband(ts, band)),
+            Self::Composed(_)
+            | Self::FitDerived(_)
+            | Self::FourierDecomposition(_)
+            | Self::Lcf(_) => None,
+        }
+    }
+
+    pub(crate) fn eval_or_fill(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+        fill_value: T,
+    ) -> Vec<T> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.eval_or_fill(ts, has_errors, fill_value),
+            Self::Composed(evaluators) => evaluators
+                .iter()
+                .flat_map(|evaluator| evaluator.eval_or_fill(ts, has_errors, fill_value))
+                .collect(),
+            Self::Lcf(feature) => feature.eval_or_fill(ts, fill_value),
+            Self::FourierDecomposition(fourier) => fourier.eval_or_fill(ts, has_errors, fill_value),
+            Self::Periodogram(periodogram) => periodogram.eval_or_fill(ts, has_errors, fill_value),
+            _ => match self.eval(ts, has_errors) {
+                Ok(values) => values,
+                Err(_) => vec![fill_value; self.size_hint()],
+            },
+        }
+    }
+
+    pub(crate) fn get_names(&self) -> Vec<&str> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.get_names(),
+            Self::Composed(evaluators) => evaluators.iter().flat_map(Self::get_names).collect(),
+            Self::CustomTransformed(transformed) => transformed.get_names(),
+            Self::FitDerived(fit_derived) => fit_derived.get_names(),
+            Self::FourierDecomposition(fourier) => fourier.get_names(),
+            Self::Periodogram(periodogram) => periodogram.get_names(),
+            Self::Lcf(feature) => feature.get_names(),
+        }
+    }
+
+    pub(crate) fn get_descriptions(&self) -> Vec<&str> {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.get_descriptions(),
+            Self::Composed(evaluators) => {
+                evaluators.iter().flat_map(Self::get_descriptions).collect()
+            }
+            Self::CustomTransformed(transformed) => transformed.get_descriptions(),
+            Self::FitDerived(fit_derived) => fit_derived.get_descriptions(),
+            Self::FourierDecomposition(fourier) => fourier.get_descriptions(),
+            Self::Periodogram(periodogram) => periodogram.get_descriptions(),
+            Self::Lcf(feature) => feature.get_descriptions(),
+        }
+    }
+
+    pub(crate) fn size_hint(&self) -> usize {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.size_hint(),
+            Self::Composed(evaluators) => evaluators.iter().map(Self::size_hint).sum(),
+            Self::CustomTransformed(transformed) => transformed.size_hint(),
+            Self::FitDerived(fit_derived) => fit_derived.size_hint(),
+            Self::FourierDecomposition(fourier) => fourier.size_hint(),
+            Self::Periodogram(periodogram) => periodogram.size_hint(),
+            Self::Lcf(feature) => feature.size_hint(),
+        }
+    }
+
+    pub(crate) fn is_t_required(&self) -> bool {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.is_t_required(),
+            Self::Composed(evaluators) => evaluators.iter().any(Self::is_t_required),
+            Self::CustomTransformed(transformed) => transformed.evaluator.is_t_required(),
+            Self::FitDerived(fit_derived) => fit_derived.fit().is_t_required(),
+            Self::FourierDecomposition(fourier) => fourier.is_t_required(),
+            Self::Periodogram(periodogram) => periodogram.is_t_required(),
+            Self::Lcf(feature) => feature.is_t_required(),
+        }
+    }
+
+    pub(crate) fn is_m_required(&self) -> bool {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.is_m_required(),
+            Self::Composed(evaluators) => evaluators.iter().any(Self::is_m_required),
+            Self::CustomTransformed(transformed) => transformed.evaluator.is_m_required(),
+            Self::FitDerived(fit_derived) => fit_derived.fit().is_m_required(),
+            Self::FourierDecomposition(fourier) => fourier.is_m_required(),
+            Self::Periodogram(periodogram) => periodogram.is_m_required(),
+            Self::Lcf(feature) => feature.is_m_required(),
+        }
+    }
+
+    pub(crate) fn is_w_required(&self) -> bool {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.is_w_required(),
+            Self::Composed(evaluators) => evaluators.iter().any(Self::is_w_required),
+            Self::CustomTransformed(transformed) => transformed.evaluator.is_w_required(),
+            Self::FitDerived(fit_derived) => fit_derived.fit().is_w_required(),
+            Self::FourierDecomposition(fourier) => fourier.is_w_required(),
+            Self::Periodogram(periodogram) => periodogram.is_w_required(),
+            Self::Lcf(feature) => feature.is_w_required(),
+        }
+    }
+
+    pub(crate) fn is_sorting_required(&self) -> bool {
+        match self {
+            Self::Annotated { evaluator, .. } => evaluator.is_sorting_required(),
+            Self::Composed(evaluators) => evaluators.iter().any(Self::is_sorting_required),
+            Self::CustomTransformed(transformed) => transformed.evaluator.is_sorting_required(),
+            Self::FitDerived(fit_derived) => fit_derived.fit().is_sorting_required(),
+            Self::FourierDecomposition(fourier) => fourier.is_sorting_required(),
+            Self::Periodogram(periodogram) => periodogram.is_sorting_required(),
+            Self::Lcf(feature) => feature.is_sorting_required(),
+        }
+    }
+}
+
+/// Evaluator which output is transformed by [CustomTransformer]
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct CustomTransformed<T>
+where
+    T: lcf::Float,
+{
+    evaluator: Box<Evaluator<T>>,
+    transformer: CustomTransformer,
+    names: Vec<String>,
+    descriptions: Vec<String>,
+}
+
+impl<T> CustomTransformed<T>
+where
+    T: lcf::Float,
+{
+    fn new(evaluator: Evaluator<T>, transformer: CustomTransformer) -> Res<Self> {
+        transformer.check_size(evaluator.size_hint())?;
+        let names = transformer.names(
+            evaluator
+                .get_names()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+        let descriptions = transformer.descriptions(
+            evaluator
+                .get_descriptions()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+        Ok(Self {
+            evaluator: Box::new(evaluator),
+            transformer,
+            names,
+            descriptions,
+        })
+    }
+
+    fn eval(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        Ok(self
+            .transformer
+            .transform(self.evaluator.eval(ts, has_errors)?))
+    }
+
+    fn eval_multiband(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        band: &[usize],
+    ) -> Option<Result<Vec<T>, lcf::EvaluatorError>> {
+        self.evaluator
+            .eval_multiband(ts, band)
+            .map(|values| Ok(self.transformer.transform(values?)))
+    }
+
+    fn get_names(&self) -> Vec<&str> {
+        self.names.iter().map(String::as_str).collect()
+    }
+
+    fn get_descriptions(&self) -> Vec<&str> {
+        self.descriptions.iter().map(String::as_str).collect()
+    }
+
+    fn size_hint(&self) -> usize {
+        self.names.len()
+    }
+
+    fn output_transformers(&self) -> Vec<OutputTransformer> {
+        self.evaluator
+            .output_transformers()
+            .into_iter()
+            .zip(self.transformer.per_value(self.size_hint()))
+            .map(|(inner, outer)| inner.map(|inner| inner.then(outer)))
+            .collect()
+    }
+}
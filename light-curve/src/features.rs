@@ -1,23 +1,35 @@
-use crate::check::{check_finite, check_no_nans, is_sorted};
+use crate::check::{check_finite, check_no_nans, check_sorted, is_sorted};
 use crate::cont_array::ContCowArray;
 use crate::errors::{Exception, Res};
+use crate::evaluator::{Evaluator, OutputTransformer};
+use crate::fit_derived::{FitDerived, FitModel, ParamsTransformer};
+use crate::fourier;
 use crate::ln_prior::LnPrior1D;
 use crate::np_array::Arr;
-use crate::transform::{StockTransformer, parse_transform};
+use crate::periodogram::{
+    Algorithm, AliasPeaks, FapMethod, LcfPeriodogram, PeriodRangeGrid, PeriodogramEvaluator,
+};
+use crate::transform::{
+    CustomTransformer, ParsedTransform, PyTransformer, StockTransformer, parse_transform_option,
+};
 
 use const_format::formatcp;
 use conv::ConvUtil;
 use itertools::Itertools;
+use light_curve_feature::transformers::{
+    bazin_fit::BazinFitTransformer, linexp_fit::LinexpFitTransformer,
+    villar_fit::VillarFitTransformer,
+};
 use light_curve_feature::{self as lcf, DataSample, periodogram::FreqGrid, prelude::*};
 use macro_const::macro_const;
-use ndarray::IntoNdProducer;
+use ndarray::{ArrayView1, Axis, IntoNdProducer};
 use num_traits::Zero;
 use numpy::prelude::*;
-use numpy::{AllowTypeChange, PyArray1, PyArrayLike1, PyUntypedArray};
+use numpy::{AllowTypeChange, PyArray1, PyArrayDyn, PyArrayLike1, PyArrayLikeDyn, PyUntypedArray};
 use once_cell::sync::OnceCell;
 use pyo3::exceptions::{PyNotImplementedError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyBytes, PyTuple};
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -46,7 +58,11 @@ const ATTRIBUTES_DOC: &str = r#"Attributes
 names : list of str
     Feature names
 descriptions : list of str
-    Feature descriptions"#;
+    Feature descriptions
+transformers : dict of str to transform.Transformer or None
+    Transformers applied to every feature value, in the order of `names`.
+    None means that the transformation is unknown or cannot be inverted,
+    e.g. the default transformation of parametric fits"#;
 
 const METHOD_CALL_DOC: &str = r#"__call__(self, t, m, sigma=None, *, fill_value=None, sorted=None, check=True, cast=False)
     Extract features and return them as a numpy array
@@ -120,17 +136,45 @@ many(self, lcs, *, fill_value=None, sorted=None, check=True, cast=False, n_jobs=
         details"#;
 }
 
+const METHOD_INVERSE_TRANSFORM_DOC: &str = r#"inverse_transform(self, values, *, fill_value=None)
+    Restore untransformed feature values
+
+    Parameters
+    ----------
+    values : numpy.ndarray
+        1-D array of feature values, as returned by __call__, or 2-D array
+        of them, as returned by many()
+    fill_value : float or None, optional
+        Value to use if the original value cannot be restored, e.g. when it
+        was clipped or its transformation is unknown, see `transformers`.
+        None causes exception for such values
+
+    Returns
+    -------
+    ndarray of np.float64
+        Array of the same shape as `values`"#;
+
 const METHODS_DOC: &str = formatcp!(
     r#"Methods
 -------
 {}
+{}
 {}"#,
     METHOD_CALL_DOC,
     METHOD_MANY_DOC,
+    METHOD_INVERSE_TRANSFORM_DOC,
 );
 
 const COMMON_FEATURE_DOC: &str = formatcp!("\n{}\n\n{}\n", ATTRIBUTES_DOC, METHODS_DOC);
 
+const META_TRANSFORM_DOC: &str = r#"transform : str or bool or transform.Transformer or None, optional
+    Transformer to apply to the feature values:
+     - 'default' or True - apply default transformation of every underlying
+       feature, the underlying features must not be transformed already
+     - other str - name of a stock transformer to apply to all the values
+     - transform.Transformer object - transformer to apply to all the values
+     - None or False - no transformation is applied (default)"#;
+
 fn transform_parameter_doc(default: StockTransformer) -> String {
     let default_name: &str = default.into();
     let variants = StockTransformer::all_variants().format_with("\n     - ", |variant, fmt| {
@@ -139,12 +183,15 @@ fn transform_parameter_doc(default: StockTransformer) -> String {
         fmt(&format_args!("'{name}' - {doc}"))
     });
     format!(
-        r#"transform : str or bool or None
+        r#"transform : str or bool or transform.Transformer or None
     Transformer to apply to the feature values. If str, must be one of:
      - 'default' - use default transformer for the feature, it same as giving
        True. The default for this feature is '{default_name}'
      - {variants}
     If bool, must be True to use default transformer or False to disable.
+    If `transform.Transformer`, it is applied to all the feature values,
+    such a feature cannot be an underlying feature of `Bins` and
+    `Periodogram`, pass the transformer to them instead.
     If None, no transformation is applied"#,
     )
 }
@@ -158,30 +205,123 @@ type PyLightCurve<'a, T> = (Arr<'a, T>, Arr<'a, T>, Option<Arr<'a, T>>);
     module = "light_curve.light_curve_ext"
 )]
 pub struct PyFeatureEvaluator {
-    feature_evaluator_f32: lcf::Feature<f32>,
-    feature_evaluator_f64: lcf::Feature<f64>,
+    feature_evaluator_f32: Evaluator<f32>,
+    feature_evaluator_f64: Evaluator<f64>,
 }
 
 impl PyFeatureEvaluator {
-    fn with_transform(
-        (fe_f32, fe_f64): (lcf::Feature<f32>, lcf::Feature<f64>),
-        (tr_f32, tr_f64): (lcf::Transformer<f32>, lcf::Transformer<f64>),
-    ) -> Res<Self> {
+    fn from_lcf(fe_f32: lcf::Feature<f32>, fe_f64: lcf::Feature<f64>) -> Self {
+        Self {
+            feature_evaluator_f32: fe_f32.into(),
+            feature_evaluator_f64: fe_f64.into(),
+        }
+    }
+
+    /// Underlying `light-curve-feature` evaluators, required by `Bins` and `Periodogram`
+    fn to_lcf(&self) -> Res<(lcf::Feature<f32>, lcf::Feature<f64>)> {
+        match (
+            self.feature_evaluator_f32.to_lcf_unweighted(),
+            self.feature_evaluator_f64.to_lcf_unweighted(),
+        ) {
+            (Some(fe_f32), Some(fe_f64)) => Ok((fe_f32, fe_f64)),
+            _ if self.feature_evaluator_f64.is_custom_transformed() => {
+                Err(Exception::ValueError(format!(
+                    "feature with outputs {:?} has a custom transformer, so it cannot be used as an underlying feature of Bins or Periodogram, pass the transformer as their transform argument instead",
+                    self.feature_evaluator_f64.get_names()
+                )))
+            }
+            _ => Err(Exception::ValueError(format!(
+                "feature with outputs {:?} cannot be used as an underlying feature of Bins or Periodogram",
+                self.feature_evaluator_f64.get_names()
+            ))),
+        }
+    }
+
+    /// Annotate evaluators with transformers of the output values, see [Evaluator::annotated]
+    fn annotated(self, output_transformers: Vec<OutputTransformer>) -> Self {
+        Self {
+            feature_evaluator_f32: self
+                .feature_evaluator_f32
+                .annotated(output_transformers.clone()),
+            feature_evaluator_f64: self.feature_evaluator_f64.annotated(output_transformers),
+        }
+    }
+
+    /// Apply default transformation of the feature class, used by meta-features with
+    /// transform='default'
+    fn default_transformed(py_feature: &Bound<PyFeatureEvaluator>) -> Res<Self> {
+        let class_name = py_feature.get_type().qualname()?;
+        let fe = py_feature.borrow();
+        if fe.feature_evaluator_f64.is_transformed() {
+            return Err(Exception::ValueError(format!(
+                "{class_name} object is already transformed, it cannot be used with transform='default'"
+            )));
+        }
+        let default_transform: String = py_feature
+            .getattr("default_transform")
+            .map_err(|_| {
+                Exception::ValueError(format!(
+                    "{class_name} has no default transformation, it cannot be used with transform='default'"
+                ))
+            })?
+            .extract()?;
+        let (fe_f32, fe_f64) = (
+            fe.feature_evaluator_f32.clone(),
+            fe.feature_evaluator_f64.clone(),
+        );
+        // Parametric fits have their own default transformers
+        if default_transform == "default" {
+            Ok(Self {
+                feature_evaluator_f32: fe_f32.fit_default_transformed()?,
+                feature_evaluator_f64: fe_f64.fit_default_transformed()?,
+            })
+        } else {
+            let stock_transformer = StockTransformer::try_from(default_transform.as_str())?;
+            Ok(Self {
+                feature_evaluator_f32: fe_f32.stock_transformed(stock_transformer)?,
+                feature_evaluator_f64: fe_f64.stock_transformed(stock_transformer)?,
+            })
+        }
+    }
+
+    /// Extract features from Python iterable, applying their default transformations if asked
+    fn extract_features(features: &Bound<PyAny>, default_transform: bool) -> Res<Vec<Self>> {
+        features
+            .try_iter()?
+            .map(|x| {
+                let x = x?;
+                let py_feature = x.downcast::<PyFeatureEvaluator>().map_err(PyErr::from)?;
+                if default_transform {
+                    Self::default_transformed(py_feature)
+                } else {
+                    Ok(py_feature.borrow().clone())
+                }
+            })
+            .collect()
+    }
+
+    /// Apply transformation to the output of meta-feature, "default" must be resolved by caller
+    fn with_meta_transform(self, transform: ParsedTransform) -> Res<Self> {
+        match transform {
+            ParsedTransform::Default => Ok(self),
+            ParsedTransform::Stock(stock_transformer) => Ok(Self {
+                feature_evaluator_f32: self
+                    .feature_evaluator_f32
+                    .stock_transformed(stock_transformer)?,
+                feature_evaluator_f64: self
+                    .feature_evaluator_f64
+                    .stock_transformed(stock_transformer)?,
+            }),
+            ParsedTransform::Custom(transformer) => self.with_custom_transform(transformer),
+        }
+    }
+
+    fn with_custom_transform(self, transformer: CustomTransformer) -> Res<Self> {
         Ok(Self {
-            feature_evaluator_f32: lcf::Transformed::new(fe_f32, tr_f32)
-                .map_err(|err| {
-                    Exception::ValueError(format!(
-                        "feature and transformation are incompatible: {err:?}"
-                    ))
-                })?
-                .into(),
-            feature_evaluator_f64: lcf::Transformed::new(fe_f64, tr_f64)
-                .map_err(|err| {
-                    Exception::ValueError(format!(
-                        "feature and transformation are incompatible: {err:?}"
-                    ))
-                })?
-                .into(),
+            feature_evaluator_f32: self
+                .feature_evaluator_f32
+                .custom_transformed(transformer.clone())?,
+            feature_evaluator_f64: self.feature_evaluator_f64.custom_transformed(transformer)?,
         })
     }
 
@@ -191,18 +331,18 @@ impl PyFeatureEvaluator {
         transform: Option<Bound<PyAny>>,
         default_transformer: StockTransformer,
     ) -> Res<Self> {
-        let transform = parse_transform(transform, default_transformer)?;
-        match transform {
-            Some(transform) => Self::with_transform((fe_f32, fe_f64), transform.into()),
-            None => Ok(Self {
-                feature_evaluator_f32: fe_f32,
-                feature_evaluator_f64: fe_f64,
-            }),
+        let fe = Self::from_lcf(fe_f32, fe_f64);
+        match parse_transform_option(transform)? {
+            Some(ParsedTransform::Default) => {
+                fe.with_meta_transform(ParsedTransform::Stock(default_transformer))
+            }
+            Some(transform) => fe.with_meta_transform(transform),
+            None => Ok(fe),
         }
     }
 
     fn ts_from_numpy<'a, T>(
-        feature_evaluator: &lcf::Feature<T>,
+        feature_evaluator: &Evaluator<T>,
         t: &'a Arr<'a, T>,
         m: &'a Arr<'a, T>,
         sigma: &'a Option<Arr<'a, T>>,
@@ -288,7 +428,7 @@ impl PyFeatureEvaluator {
 
     #[allow(clippy::too_many_arguments)]
     fn call_impl<'py, T>(
-        feature_evaluator: &lcf::Feature<T>,
+        feature_evaluator: &Evaluator<T>,
         py: Python<'py>,
         t: Arr<'py, T>,
         m: Arr<'py, T>,
@@ -312,9 +452,9 @@ impl PyFeatureEvaluator {
         )?;
 
         let result = match fill_value {
-            Some(x) => feature_evaluator.eval_or_fill(&mut ts, x),
+            Some(x) => feature_evaluator.eval_or_fill(&mut ts, sigma.is_some(), x),
             None => feature_evaluator
-                .eval(&mut ts)
+                .eval(&mut ts, sigma.is_some())
                 .map_err(|e| Exception::ValueError(e.to_string()))?,
         };
         let array = PyArray1::from_vec(py, result);
@@ -324,7 +464,7 @@ impl PyFeatureEvaluator {
     #[allow(clippy::too_many_arguments)]
     fn py_many<'py, T>(
         &self,
-        feature_evaluator: &lcf::Feature<T>,
+        feature_evaluator: &Evaluator<T>,
         py: Python<'py>,
         lcs: PyLcs<'py>,
         sorted: Option<bool>,
@@ -335,8 +475,26 @@ impl PyFeatureEvaluator {
     where
         T: lcf::Float + numpy::Element,
     {
-        let wrapped_lcs = lcs
-            .into_iter()
+        Ok(Self::many_impl(
+            feature_evaluator,
+            Self::downcast_lcs(lcs)?,
+            sorted,
+            check,
+            self.is_t_required(sorted),
+            fill_value,
+            n_jobs,
+        )?
+        .into_pyarray(py)
+        .as_untyped()
+        .clone())
+    }
+
+    /// Downcast light curves to arrays of the same dtype
+    fn downcast_lcs<'py, T>(lcs: PyLcs<'py>) -> Res<Vec<PyLightCurve<'py, T>>>
+    where
+        T: numpy::Element,
+    {
+        lcs.into_iter()
             .enumerate()
             .map(|(i, (t, m, sigma))| {
                 let t = t.downcast::<PyArray1<T>>().map(|a| a.readonly());
@@ -355,23 +513,11 @@ impl PyFeatureEvaluator {
                     ))),
                 }
             })
-            .collect::<Res<Vec<_>>>()?;
-        Ok(Self::many_impl(
-            feature_evaluator,
-            wrapped_lcs,
-            sorted,
-            check,
-            self.is_t_required(sorted),
-            fill_value,
-            n_jobs,
-        )?
-        .into_pyarray(py)
-        .as_untyped()
-        .clone())
+            .collect()
     }
 
     fn many_impl<T>(
-        feature_evaluator: &lcf::Feature<T>,
+        feature_evaluator: &Evaluator<T>,
         lcs: Vec<PyLightCurve<T>>,
         sorted: Option<bool>,
         check: bool,
@@ -389,9 +535,18 @@ impl PyFeatureEvaluator {
         let mut tss = lcs
             .iter()
             .map(|(t, m, sigma)| {
-                Self::ts_from_numpy(feature_evaluator, t, m, sigma, sorted, check, is_t_required)
+                let ts = Self::ts_from_numpy(
+                    feature_evaluator,
+                    t,
+                    m,
+                    sigma,
+                    sorted,
+                    check,
+                    is_t_required,
+                )?;
+                Ok((ts, sigma.is_some()))
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Res<Vec<_>>>()?;
 
         rayon::ThreadPoolBuilder::new()
             .num_threads(n_jobs)
@@ -401,11 +556,11 @@ impl PyFeatureEvaluator {
                 ndarray::Zip::from(result.outer_iter_mut())
                     .and((&mut tss).into_producer())
                     .into_par_iter()
-                    .try_for_each::<_, Res<_>>(|(mut map, ts)| {
+                    .try_for_each::<_, Res<_>>(|(mut map, (ts, has_errors))| {
                         let features: ndarray::Array1<_> = match fill_value {
-                            Some(x) => feature_evaluator.eval_or_fill(ts, x),
+                            Some(x) => feature_evaluator.eval_or_fill(ts, *has_errors, x),
                             None => feature_evaluator
-                                .eval(ts)
+                                .eval(ts, *has_errors)
                                 .map_err(|e| Exception::ValueError(e.to_string()))?,
                         }
                         .into();
@@ -585,6 +740,69 @@ impl PyFeatureEvaluator {
         self.feature_evaluator_f64.get_descriptions()
     }
 
+    /// Transformers applied to feature values
+    #[getter]
+    fn transformers<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (name, transformer) in self
+            .feature_evaluator_f64
+            .get_names()
+            .into_iter()
+            .zip(self.feature_evaluator_f64.output_transformers())
+        {
+            dict.set_item(name, transformer.map(PyTransformer))?;
+        }
+        Ok(dict)
+    }
+
+    /// Restore untransformed feature values
+    ///
+    /// float32 values are assumed to be evaluated in single precision, which affects clipping
+    /// thresholds of the transformers
+    #[pyo3(signature = (values, *, fill_value=None))]
+    fn inverse_transform<'py>(
+        &self,
+        py: Python<'py>,
+        values: Bound<'py, PyAny>,
+        fill_value: Option<f64>,
+    ) -> Res<Bound<'py, PyArrayDyn<f64>>> {
+        let is_f32 = values.downcast::<PyArrayDyn<f32>>().is_ok();
+        let values: PyArrayLikeDyn<f64, AllowTypeChange> = values.extract()?;
+        let transformers = self.feature_evaluator_f64.output_transformers();
+        let values = values.as_array();
+        let ndim = values.ndim();
+        if !matches!(ndim, 1 | 2) || values.shape()[ndim - 1] != transformers.len() {
+            return Err(Exception::ValueError(format!(
+                "values must be 1-D or 2-D array with the last dimension of size {}, but array of shape {:?} is given",
+                transformers.len(),
+                values.shape()
+            )));
+        }
+        let names = self.feature_evaluator_f64.get_names();
+        let mut output = values.to_owned();
+        for mut lane in output.lanes_mut(Axis(ndim - 1)) {
+            for ((x, transformer), name) in lane.iter_mut().zip(&transformers).zip(&names) {
+                let inverse = transformer.as_ref().and_then(|transformer| {
+                    if is_f32 {
+                        transformer.inverse_value::<f32>(*x)
+                    } else {
+                        transformer.inverse_value::<f64>(*x)
+                    }
+                });
+                *x = match (inverse, fill_value) {
+                    (Some(inverse), _) => inverse,
+                    (None, Some(fill_value)) => fill_value,
+                    (None, None) => {
+                        return Err(Exception::ValueError(format!(
+                            "value {x} of {name} cannot be inverse transformed, use fill_value to replace such values"
+                        )));
+                    }
+                };
+            }
+        }
+        Ok(output.into_pyarray(py))
+    }
+
     /// Used by copy.copy
     fn __copy__(&self) -> Self {
         self.clone()
@@ -641,30 +859,22 @@ impl Extractor {
         features: Bound<PyTuple>,
         transform: Option<Bound<PyAny>>,
     ) -> PyResult<(Self, PyFeatureEvaluator)> {
-        if transform.is_some() {
-            return Err(Exception::NotImplementedError(
-                "transform is not implemented for Extractor, transform individual features instead"
-                    .to_string(),
-            )
-            .into());
-        }
-        let evals_iter = features.iter_borrowed().map(|arg| {
-            arg.extract::<PyFeatureEvaluator>().map(|fe| {
-                (
-                    fe.feature_evaluator_f32.clone(),
-                    fe.feature_evaluator_f64.clone(),
-                )
-            })
-        });
-        let (evals_f32, evals_f64) =
-            itertools::process_results(evals_iter, |iter| iter.unzip::<_, _, Vec<_>, Vec<_>>())?;
-        Ok((
-            Self {},
-            PyFeatureEvaluator {
-                feature_evaluator_f32: lcf::FeatureExtractor::new(evals_f32).into(),
-                feature_evaluator_f64: lcf::FeatureExtractor::new(evals_f64).into(),
-            },
-        ))
+        let transform = parse_transform_option(transform)?;
+        let default_transform = matches!(transform, Some(ParsedTransform::Default));
+        let (evals_f32, evals_f64): (Vec<_>, Vec<_>) =
+            PyFeatureEvaluator::extract_features(features.as_any(), default_transform)?
+                .into_iter()
+                .map(|fe| (fe.feature_evaluator_f32, fe.feature_evaluator_f64))
+                .unzip();
+        let fe = PyFeatureEvaluator {
+            feature_evaluator_f32: Evaluator::compose(evals_f32),
+            feature_evaluator_f64: Evaluator::compose(evals_f64),
+        };
+        let fe = match transform {
+            Some(transform) => fe.with_meta_transform(transform)?,
+            None => fe,
+        };
+        Ok((Self {}, fe))
     }
 
     #[classattr]
@@ -676,11 +886,11 @@ Parameters
 ----------
 *features : iterable
     Feature objects
-transform : None, optional
-    Not implemented for Extractor, transform individual features instead
+{}
 {}
 "#,
             lcf::FeatureExtractor::<f64, lcf::Feature<f64>>::doc().trim_start(),
+            META_TRANSFORM_DOC,
             COMMON_FEATURE_DOC,
         )
     }
@@ -817,7 +1027,7 @@ pub(crate) enum FitLnPrior {
 }
 
 macro_rules! fit_evaluator {
-    ($name: ident, $eval: ty, $ib: ty, $transform: expr, $nparam: literal, $ln_prior_by_str: tt, $ln_prior_doc: literal $(,)?) => {
+    ($name: ident, $eval: ty, $ib: ty, $transformer: ident, $derived: expr, $nparam: literal, $ln_prior_by_str: tt, $ln_prior_doc: literal $(,)?) => {
         #[derive(Serialize, Deserialize)]
         #[pyclass(extends = PyFeatureEvaluator, module="light_curve.light_curve_ext")]
         pub struct $name {}
@@ -843,6 +1053,28 @@ macro_rules! fit_evaluator {
                 static DESC: OnceCell<Vec<&str>> = OnceCell::new();
                 DESC.get_or_init(|| Self::lazy_default().get_descriptions())
             }
+
+            fn derived_doc() -> String {
+                let derived_model: Option<FitModel> = $derived;
+                match derived_model {
+                    Some(model) => {
+                        let names_descriptions: String = model
+                            .derived_names()
+                            .iter()
+                            .zip(model.derived_descriptions())
+                            .map(|(name, description)| format!("     - {}: {}\n", name, description))
+                            .collect();
+                        format!(
+                            r#"derived : bool, optional
+    If `True` the output is extended with the following quantities derived
+    from the best-fit model, they are never transformed:
+{names_descriptions}    Default is `False`.
+"#,
+                        )
+                    }
+                    None => String::new(),
+                }
+            }
         }
 
         impl $name {
@@ -892,6 +1124,8 @@ macro_rules! fit_evaluator {
                 bounds = None,
                 ln_prior = None,
                 transform = None,
+                mag_zp = None,
+                derived = false,
             ))]
             fn __new__(
                 algorithm: &str,
@@ -907,6 +1141,8 @@ macro_rules! fit_evaluator {
                 bounds: Option<Vec<(Option<f64>, Option<f64>)>>,
                 ln_prior: Option<FitLnPrior>,
                 transform: Option<Bound<PyAny>>,
+                mag_zp: Option<f64>,
+                derived: bool,
             ) -> PyResult<(Self, PyFeatureEvaluator)> {
                 let mcmc_niter = mcmc_niter.unwrap_or_else(lcf::McmcCurveFit::default_niterations);
 
@@ -1012,22 +1248,48 @@ macro_rules! fit_evaluator {
                         )
                         .into(),);
 
-                let make_transformation = match transform {
-                    None => false,
-                    Some(py_transform) => match py_transform.downcast::<PyBool>() {
-                        Ok(py_bool) => py_bool.is_true(),
-                        Err(_) => return Err(PyValueError::new_err(
-                            "transform must be a bool or None, other types are not implemented yet",
+                let transformers: Option<(ParamsTransformer<f32>, ParamsTransformer<f64>)> =
+                    match (parse_transform_option(transform)?, mag_zp) {
+                        (None, None) => None,
+                        (Some(ParsedTransform::Default), None) => Some((
+                            ParamsTransformer::Lcf($transformer::default().into()),
+                            ParamsTransformer::Lcf($transformer::default().into()),
                         )),
+                        (Some(ParsedTransform::Default), Some(mag_zp)) => Some((
+                            ParamsTransformer::Lcf($transformer::new(mag_zp as f32).into()),
+                            ParamsTransformer::Lcf($transformer::new(mag_zp).into()),
+                        )),
+                        (Some(ParsedTransform::Stock(stock_transformer)), None) => Some((
+                            ParamsTransformer::Stock(stock_transformer),
+                            ParamsTransformer::Stock(stock_transformer),
+                        )),
+                        (Some(ParsedTransform::Custom(transformer)), None) => Some((
+                            ParamsTransformer::Custom(transformer.clone()),
+                            ParamsTransformer::Custom(transformer),
+                        )),
+                        (_, Some(_)) => return Err(PyValueError::new_err(
+                            "mag_zp can be used with transform=True or transform='default' only",
+                        )),
+                    };
+                let derived_model: Option<FitModel> = $derived;
+                let fe = match (derived, derived_model) {
+                    (false, _) => match transformers {
+                        Some((tr_f32, tr_f64)) => PyFeatureEvaluator {
+                            feature_evaluator_f32: tr_f32.transformed(fe_f32)?,
+                            feature_evaluator_f64: tr_f64.transformed(fe_f64)?,
+                        },
+                        None => PyFeatureEvaluator::from_lcf(fe_f32, fe_f64),
+                    },
+                    (true, Some(model)) => {
+                        let (tr_f32, tr_f64) = transformers.unzip();
+                        PyFeatureEvaluator {
+                            feature_evaluator_f32: Evaluator::FitDerived(FitDerived::new(fe_f32, tr_f32, model)?),
+                            feature_evaluator_f64: Evaluator::FitDerived(FitDerived::new(fe_f64, tr_f64, model)?),
+                        }
                     }
-                };
-                let fe = if make_transformation {
-                    PyFeatureEvaluator::with_transform((fe_f32, fe_f64), ($transform.into(), $transform.into()))?
-                } else {
-                    PyFeatureEvaluator {
-                        feature_evaluator_f32: fe_f32,
-                        feature_evaluator_f64: fe_f64,
-                    }
+                    (true, None) => return Err(PyValueError::new_err(
+                        concat!("derived quantities are not supported by ", stringify!($name)),
+                    )),
                 };
 
                 Ok((Self{}, fe))
@@ -1058,6 +1320,24 @@ macro_rules! fit_evaluator {
                 return SUPPORTED_ALGORITHMS_CURVE_FIT;
             }
 
+            /// Supported transform names
+            #[classattr]
+            fn supported_transforms() -> Vec<&'static str> {
+                std::iter::once("default").chain(StockTransformer::all_names()).collect()
+            }
+
+            /// Default transform name
+            #[classattr]
+            fn default_transform() -> &'static str {
+                "default"
+            }
+
+            /// Default magnitude zero point used by transform='default'
+            #[classattr]
+            fn default_mag_zp() -> f64 {
+                $transformer::<f64>::default_mag_zp()
+            }
+
             #[classattr]
             fn __doc__() -> String {
                 #[cfg(any(feature = "ceres-source", feature = "ceres-system"))]
@@ -1113,19 +1393,29 @@ ln_prior : str or list of ln_prior.LnPrior1D or None, optional
     or a list of {nparam} `ln_prior.LnPrior1D` objects, see `ln_prior`
     submodule for corresponding functions. Available string literals are:
     {ln_prior}
-transform : bool or None, optional
-    If `False` or `None` (default) output is not transformed. If `True` output
-    is transformed as following:
-     - Half-amplitude A is transformed as `zp - 2.5 lg(2*A)`, zp = 8.9,
-       so that the amplitude is assumed to be the object peak flux in Jy.
+transform : str or bool or None, optional
+    If `False` or `None` (default) output is not transformed. If `True` or
+    'default' output is transformed as following:
+     - Half-amplitude A is transformed as `zp - 2.5 lg(2*A)`, zp = {mag_zp}
+       by default, so that the amplitude is assumed to be the object peak
+       flux in Jy, see `mag_zp` to use other units.
      - baseline flux is normalised by A: baseline -> baseline / A
      - reference time is removed
      - goodness of fit is transformed as `ln(reduced chi^2 + 1)` to reduce
        its spread
      - other parameters are not transformed
+    If str, it also can be one of the stock transformers, which is applied to
+    all the output values:
+     - {stock_transforms}
+    `transform.Transformer` object is applied to all the model parameters and
+    the goodness of fit.
     See `names` and `descriptions` attributes an object for the list and order
     of features.
-
+mag_zp : float or None, optional
+    Magnitude zero point for `transform='default'`, e.g. 23.9 for fluxes in
+    µJy or 31.4 for fluxes in nJy. Default is None which means {mag_zp}, it
+    cannot be used with other transforms.
+{derived}
 {attr}
 supported_algorithms : list of str
     Available argument values for the constructor
@@ -1159,6 +1449,9 @@ Examples
                     feature = stringify!($name),
                     nparam = $nparam,
                     ln_prior = $ln_prior_doc,
+                    derived = Self::derived_doc(),
+                    mag_zp = Self::default_mag_zp(),
+                    stock_transforms = StockTransformer::all_names().map(|name| format!("'{name}'")).join("\n     - "),
                 )
             }
         }
@@ -1225,7 +1518,8 @@ fit_evaluator!(
     BazinFit,
     lcf::BazinFit,
     lcf::BazinInitsBounds,
-    lcf::transformers::bazin_fit::BazinFitTransformer::default(),
+    BazinFitTransformer,
+    Some(FitModel::Bazin),
     5,
     {
         "no" => lcf::BazinLnPrior::fixed(lcf::LnPrior::none()),
@@ -1252,19 +1546,16 @@ impl Bins {
         offset: f64,
         transform: Option<Bound<PyAny>>,
     ) -> PyResult<(Self, PyFeatureEvaluator)> {
-        if transform.is_some() {
-            return Err(Exception::NotImplementedError(
-                "transform is not supported by Bins, apply transformations to individual features"
-                    .to_string(),
-            )
-            .into());
-        }
+        let transform = parse_transform_option(transform)?;
+        let default_transform = matches!(transform, Some(ParsedTransform::Default));
         let mut eval_f32 = lcf::Bins::default();
         let mut eval_f64 = lcf::Bins::default();
-        for x in features.try_iter()? {
-            let py_feature = x?.downcast::<PyFeatureEvaluator>()?.borrow();
-            eval_f32.add_feature(py_feature.feature_evaluator_f32.clone());
-            eval_f64.add_feature(py_feature.feature_evaluator_f64.clone());
+        let mut output_transformers = vec![];
+        for py_feature in PyFeatureEvaluator::extract_features(&features, default_transform)? {
+            let (fe_f32, fe_f64) = py_feature.to_lcf()?;
+            eval_f32.add_feature(fe_f32);
+            eval_f64.add_feature(fe_f64);
+            output_transformers.extend(py_feature.feature_evaluator_f64.output_transformers());
         }
 
         eval_f32.set_window(window as f32);
@@ -1273,13 +1564,13 @@ impl Bins {
         eval_f32.set_offset(offset as f32);
         eval_f64.set_offset(offset);
 
-        Ok((
-            Self {},
-            PyFeatureEvaluator {
-                feature_evaluator_f32: eval_f32.into(),
-                feature_evaluator_f64: eval_f64.into(),
-            },
-        ))
+        let fe = PyFeatureEvaluator::from_lcf(eval_f32.into(), eval_f64.into())
+            .annotated(output_transformers);
+        let fe = match transform {
+            Some(transform) => fe.with_meta_transform(transform)?,
+            None => fe,
+        };
+        Ok((Self {}, fe))
     }
 
     /// Use __getnewargs_ex__ instead
@@ -1311,16 +1602,18 @@ impl Bins {
 Parameters
 ----------
 features : iterable
-    Features to extract from binned time-series
+    Features to extract from binned time-series. They must not have
+    `transform.Transformer` transformers, use `transform` argument of `Bins`
+    instead
 window : positive float
     Width of binning interval in units of time
 offset : float
     Zero time moment
-transform : None
-    Not supported, apply transformations to individual features
+{transform}
 {footer}
 "#,
             header = lcf::Bins::<f64, lcf::Feature<f64>>::doc().trim_start(),
+            transform = META_TRANSFORM_DOC,
             footer = COMMON_FEATURE_DOC,
         )
     }
@@ -1396,7 +1689,8 @@ fit_evaluator!(
     LinexpFit,
     lcf::LinexpFit,
     lcf::LinexpInitsBounds,
-    lcf::transformers::linexp_fit::LinexpFitTransformer::default(),
+    LinexpFitTransformer,
+    None,
     4,
     {
         "no" => lcf::LinexpLnPrior::fixed(lcf::LnPrior::none()),
@@ -1608,8 +1902,6 @@ quantile : positive float
     }
 }
 
-type LcfPeriodogram<T> = lcf::Periodogram<T, lcf::Feature<T>>;
-
 #[derive(FromPyObject)]
 enum NyquistArgumentOfPeriodogram {
     String(String),
@@ -1619,22 +1911,234 @@ enum NyquistArgumentOfPeriodogram {
 #[derive(Serialize, Deserialize)]
 #[pyclass(extends = PyFeatureEvaluator, module="light_curve.light_curve_ext")]
 pub struct Periodogram {
-    eval_f32: LcfPeriodogram<f32>,
-    eval_f64: LcfPeriodogram<f64>,
+    eval_f32: PeriodogramEvaluator<f32>,
+    eval_f64: PeriodogramEvaluator<f64>,
+    /// Frequency grid is the same for all light curves
+    fixed_freqs: bool,
 }
 
 impl_pickle_serialisation!(Periodogram);
 
 impl Periodogram {
+    /// Default transformation of periodogram peaks: lg for periods, identity for signal-to-noise
+    /// ratios and false alarm probabilities, the underlying features are transformed separately
+    fn default_peak_transformer(peaks: usize, peak_size: usize, size: usize) -> CustomTransformer {
+        let peak = [
+            (CustomTransformer::Stock(StockTransformer::Lg), 1),
+            (
+                CustomTransformer::Stock(StockTransformer::Identity),
+                peak_size - 1,
+            ),
+        ];
+        CustomTransformer::Chunks(
+            std::iter::repeat_n(peak, peaks)
+                .flatten()
+                .chain(std::iter::once((
+                    CustomTransformer::Stock(StockTransformer::Identity),
+                    size - peak_size * peaks,
+                )))
+                .collect(),
+        )
+    }
+
+    fn parse_algorithm(
+        algorithm: &str,
+        nterms: usize,
+        phase_bins: Option<usize>,
+        magnitude_bins: Option<usize>,
+        max_duration: Option<f64>,
+    ) -> PyResult<Algorithm> {
+        let unsupported = |name: &str| {
+            Err(PyValueError::new_err(format!(
+                "{name} is not supported for algorithm='{algorithm}'"
+            )))
+        };
+        if nterms == 0 {
+            return Err(PyValueError::new_err("nterms must be positive"));
+        }
+        if nterms > 1 && algorithm != "lomb-scargle" {
+            return unsupported("nterms > 1");
+        }
+        let phase_bins_at_least = |default: usize, min: usize| {
+            let phase_bins = phase_bins.unwrap_or(default);
+            if phase_bins < min {
+                return Err(PyValueError::new_err(format!(
+                    "phase_bins must be at least {min}"
+                )));
+            }
+            Ok(phase_bins)
+        };
+        match algorithm {
+            "lomb-scargle" => {
+                if phase_bins.is_some() {
+                    return unsupported("phase_bins");
+                }
+                if magnitude_bins.is_some() {
+                    return unsupported("magnitude_bins");
+                }
+                if max_duration.is_some() {
+                    return unsupported("max_duration");
+                }
+                Ok(Algorithm::LombScargle { nterms })
+            }
+            "pdm" => {
+                if magnitude_bins.is_some() {
+                    return unsupported("magnitude_bins");
+                }
+                if max_duration.is_some() {
+                    return unsupported("max_duration");
+                }
+                Ok(Algorithm::PhaseDispersion {
+                    phase_bins: phase_bins_at_least(Algorithm::DEFAULT_PDM_PHASE_BINS, 2)?,
+                })
+            }
+            "conditional-entropy" => {
+                if max_duration.is_some() {
+                    return unsupported("max_duration");
+                }
+                let magnitude_bins = magnitude_bins.unwrap_or(Algorithm::DEFAULT_CE_MAGNITUDE_BINS);
+                if magnitude_bins < 2 {
+                    return Err(PyValueError::new_err("magnitude_bins must be at least 2"));
+                }
+                Ok(Algorithm::ConditionalEntropy {
+                    phase_bins: phase_bins_at_least(Algorithm::DEFAULT_CE_PHASE_BINS, 1)?,
+                    magnitude_bins,
+                })
+            }
+            "bls" => {
+                if magnitude_bins.is_some() {
+                    return unsupported("magnitude_bins");
+                }
+                let max_duration = max_duration.unwrap_or(Algorithm::DEFAULT_BLS_MAX_DURATION);
+                if !(max_duration > 0.0 && max_duration < 1.0) {
+                    return Err(PyValueError::new_err(
+                        "max_duration must be between 0 and 1",
+                    ));
+                }
+                Ok(Algorithm::BoxLeastSquares {
+                    phase_bins: phase_bins_at_least(Algorithm::DEFAULT_BLS_PHASE_BINS, 2)?,
+                    max_duration,
+                })
+            }
+            _ => Err(PyValueError::new_err(
+                "algorithm must be one of: 'lomb-scargle', 'pdm', 'conditional-entropy' or 'bls'",
+            )),
+        }
+    }
+
+    fn parse_fap(
+        fap: Option<&str>,
+        n_bootstrap: usize,
+        random_seed: Option<u64>,
+        algorithm: Algorithm,
+    ) -> PyResult<Option<FapMethod>> {
+        match fap {
+            None => Ok(None),
+            Some("baluev") if !algorithm.supports_baluev() => Err(PyValueError::new_err(
+                "fap='baluev' is supported for single-term Lomb-Scargle periodogram only, use fap='bootstrap'",
+            )),
+            Some("baluev") => Ok(Some(FapMethod::Baluev)),
+            Some("bootstrap") => {
+                if n_bootstrap == 0 {
+                    return Err(PyValueError::new_err("n_bootstrap must be positive"));
+                }
+                Ok(Some(FapMethod::Bootstrap {
+                    n: n_bootstrap,
+                    random_seed,
+                }))
+            }
+            Some(_) => Err(PyValueError::new_err(
+                "fap must be one of: None, 'baluev' or 'bootstrap'",
+            )),
+        }
+    }
+
+    fn parse_aliases(aliases: Option<&str>, alias_tolerance: f64) -> PyResult<Option<AliasPeaks>> {
+        let Some(aliases) = aliases else {
+            return Ok(None);
+        };
+        if !(alias_tolerance >= 0.0 && alias_tolerance.is_finite()) {
+            return Err(PyValueError::new_err(
+                "alias_tolerance must be a non-negative finite number",
+            ));
+        }
+        match aliases {
+            "flag" => Ok(Some(AliasPeaks::Flag {
+                tolerance: alias_tolerance,
+            })),
+            "suppress" => Ok(Some(AliasPeaks::Suppress {
+                tolerance: alias_tolerance,
+            })),
+            _ => Err(PyValueError::new_err(
+                "aliases must be one of: None, 'flag' or 'suppress'",
+            )),
+        }
+    }
+
+    fn parse_grid(
+        grid: Option<&str>,
+        min_period: Option<f64>,
+        max_period: Option<f64>,
+        n: Option<usize>,
+    ) -> PyResult<Option<PeriodRangeGrid>> {
+        let Some(grid) = grid else {
+            if min_period.is_some() || max_period.is_some() || n.is_some() {
+                return Err(PyValueError::new_err(
+                    "min_period, max_period and n are supported for grid='log-freq' or grid='linear-period' only",
+                ));
+            }
+            return Ok(None);
+        };
+        let (Some(min_period), Some(max_period), Some(size)) = (min_period, max_period, n) else {
+            return Err(PyValueError::new_err(
+                "min_period, max_period and n must be given if grid is specified",
+            ));
+        };
+        if !(min_period > 0.0 && min_period < max_period && max_period.is_finite()) {
+            return Err(PyValueError::new_err(
+                "min_period and max_period must be positive finite numbers, min_period < max_period",
+            ));
+        }
+        if size < 2 {
+            return Err(PyValueError::new_err("n must be at least 2"));
+        }
+        match grid {
+            "log-freq" => Ok(Some(PeriodRangeGrid::LogFreq {
+                min_period,
+                max_period,
+                size,
+            })),
+            "linear-period" => Ok(Some(PeriodRangeGrid::LinearPeriod {
+                min_period,
+                max_period,
+                size,
+            })),
+            _ => Err(PyValueError::new_err(
+                "grid must be one of: None, 'log-freq' or 'linear-period'",
+            )),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn create_evals(
         peaks: Option<usize>,
         resolution: Option<f32>,
         max_freq_factor: Option<f32>,
         nyquist: Option<NyquistArgumentOfPeriodogram>,
         freqs: Option<Bound<PyAny>>,
+        grid: Option<PeriodRangeGrid>,
         fast: Option<bool>,
+        algorithm: Algorithm,
         features: Option<Bound<PyAny>>,
-    ) -> PyResult<(LcfPeriodogram<f32>, LcfPeriodogram<f64>)> {
+        default_transform_features: bool,
+        fap: Option<FapMethod>,
+        aliases: Option<AliasPeaks>,
+        peak_widths: bool,
+    ) -> PyResult<(
+        PeriodogramEvaluator<f32>,
+        PeriodogramEvaluator<f64>,
+        Vec<OutputTransformer>,
+    )> {
         let mut eval_f32 = match peaks {
             Some(peaks) => lcf::Periodogram::new(peaks),
             None => lcf::Periodogram::default(),
@@ -1738,7 +2242,9 @@ impl Periodogram {
                     FreqGrid::zero_based_pow2(step_candidate as f32, (size - 1).ilog2())
                 }
                 _ => {
-                    panic!("This FreqGrid is not implemented yet")
+                    return Err(PyValueError::new_err(
+                        "freqs grid type is not supported for float32 inputs",
+                    ));
                 }
             };
 
@@ -1746,142 +2252,677 @@ impl Periodogram {
             eval_f64.set_freq_grid(freq_grid_f64);
         }
 
+        if let Some(grid) = grid {
+            // Such grids are evaluated directly by PeriodogramEvaluator, light-curve-feature
+            // periodogram gets them for the case it is used as it is, e.g. inside Bins
+            eval_f32.set_periodogram_algorithm(lcf::PeriodogramPowerDirect {}.into());
+            eval_f64.set_periodogram_algorithm(lcf::PeriodogramPowerDirect {}.into());
+            let freqs_f64 = grid.freqs();
+            let freqs_f32: Vec<_> = freqs_f64.iter().map(|&freq| freq as f32).collect();
+            eval_f32.set_freq_grid(FreqGrid::from_array(ArrayView1::from(&freqs_f32)));
+            eval_f64.set_freq_grid(FreqGrid::from_array(ArrayView1::from(&freqs_f64)));
+        }
+
+        let peaks = peaks.unwrap_or_else(LcfPeriodogram::<f64>::default_peaks);
+        let mut features_output_transformers = vec![];
+        let mut features_f32 = vec![];
+        let mut features_f64 = vec![];
         if let Some(features) = features {
-            for x in features.try_iter()? {
-                let py_feature = x?.downcast::<PyFeatureEvaluator>()?.borrow();
-                eval_f32.add_feature(py_feature.feature_evaluator_f32.clone());
-                eval_f64.add_feature(py_feature.feature_evaluator_f64.clone());
+            for py_feature in
+                PyFeatureEvaluator::extract_features(&features, default_transform_features)?
+            {
+                let (fe_f32, fe_f64) = py_feature.to_lcf()?;
+                eval_f32.add_feature(fe_f32.clone());
+                eval_f64.add_feature(fe_f64.clone());
+                features_f32.push(fe_f32);
+                features_f64.push(fe_f64);
+                features_output_transformers
+                    .extend(py_feature.feature_evaluator_f64.output_transformers());
             }
         }
 
-        Ok((eval_f32, eval_f64))
+        let eval_f32 = PeriodogramEvaluator::new(
+            eval_f32,
+            fast,
+            algorithm,
+            peaks,
+            features_f32,
+            fap,
+            grid,
+            aliases,
+            peak_widths,
+        );
+        let eval_f64 = PeriodogramEvaluator::new(
+            eval_f64,
+            fast,
+            algorithm,
+            peaks,
+            features_f64,
+            fap,
+            grid,
+            aliases,
+            peak_widths,
+        );
+        // Peak outputs are not transformed
+        let output_transformers =
+            std::iter::repeat_n(Some(CustomTransformer::identity()), eval_f64.peaks_size())
+                .chain(features_output_transformers)
+                .collect();
+        Ok((eval_f32, eval_f64, output_transformers))
     }
 
     fn power_impl<'py, T>(
-        eval: &lcf::Periodogram<T, lcf::Feature<T>>,
+        eval: &PeriodogramEvaluator<T>,
         py: Python<'py>,
         t: Arr<T>,
         m: Arr<T>,
+        sigma: Option<Arr<T>>,
+        band: Option<&[usize]>,
     ) -> Res<Bound<'py, PyUntypedArray>>
     where
         T: Float + numpy::Element,
     {
-        let t: DataSample<_> = t.as_array().into();
-        let m: DataSample<_> = m.as_array().into();
-        let mut ts = TimeSeries::new_without_weight(t, m);
-        let power = eval.power(&mut ts).map_err(lcf::EvaluatorError::from)?;
+        if let Some(band) = band {
+            Self::check_multiband(eval, &t, band)?;
+        }
+        let mut ts = Self::time_series(&t, &m, &sigma)?;
+        let power = match band {
+            Some(band) => eval.freq_power_multiband(&mut ts, band)?.1,
+            None => eval.power(&mut ts, sigma.is_some())?,
+        };
         let power = PyArray1::from_vec(py, power);
         Ok(power.as_untyped().clone())
     }
 
     fn freq_power_impl<'py, T>(
-        eval: &lcf::Periodogram<T, lcf::Feature<T>>,
+        eval: &PeriodogramEvaluator<T>,
         py: Python<'py>,
         t: Arr<T>,
         m: Arr<T>,
+        sigma: Option<Arr<T>>,
+        band: Option<&[usize]>,
     ) -> Res<(Bound<'py, PyUntypedArray>, Bound<'py, PyUntypedArray>)>
     where
         T: Float + numpy::Element,
     {
-        let t: DataSample<_> = t.as_array().into();
-        let m: DataSample<_> = m.as_array().into();
-        let mut ts = TimeSeries::new_without_weight(t, m);
-        let (freq, power) = eval
-            .freq_power(&mut ts)
-            .map_err(lcf::EvaluatorError::from)?;
+        if let Some(band) = band {
+            Self::check_multiband(eval, &t, band)?;
+        }
+        let mut ts = Self::time_series(&t, &m, &sigma)?;
+        let (freq, power) = match band {
+            Some(band) => eval.freq_power_multiband(&mut ts, band)?,
+            None => eval.freq_power(&mut ts, sigma.is_some())?,
+        };
         let freq = PyArray1::from_vec(py, freq);
         let power = PyArray1::from_vec(py, power);
         Ok((freq.as_untyped().clone(), power.as_untyped().clone()))
     }
-}
 
-#[pymethods]
-impl Periodogram {
-    #[allow(clippy::too_many_arguments)]
-    #[new]
-    #[pyo3(signature = (
-        *,
-        peaks = LcfPeriodogram::<f64>::default_peaks(),
-        resolution = LcfPeriodogram::<f64>::default_resolution(),
-        max_freq_factor = LcfPeriodogram::<f64>::default_max_freq_factor(),
-        nyquist = NyquistArgumentOfPeriodogram::String(String::from("average")),
-        freqs = None,
-        fast = true,
-        features = None,
-        transform = None,
-    ))]
-    fn __new__(
-        peaks: Option<usize>,
-        resolution: Option<f32>,
-        max_freq_factor: Option<f32>,
-        nyquist: Option<NyquistArgumentOfPeriodogram>,
-        freqs: Option<Bound<PyAny>>,
-        fast: Option<bool>,
-        features: Option<Bound<PyAny>>,
-        transform: Option<Bound<PyAny>>,
-    ) -> PyResult<(Self, PyFeatureEvaluator)> {
-        if transform.is_some() {
-            return Err(PyNotImplementedError::new_err(
-                "transform is not supported by Periodogram, peak-related features are not transformed, but you still may apply transformation for the underlying features",
-            ));
-        }
-        let (eval_f32, eval_f64) = Self::create_evals(
-            peaks,
-            resolution,
-            max_freq_factor,
-            nyquist,
-            freqs,
-            fast,
-            features,
-        )?;
-        Ok((
-            Self {
-                eval_f32: eval_f32.clone(),
-                eval_f64: eval_f64.clone(),
-            },
-            PyFeatureEvaluator {
-                feature_evaluator_f32: eval_f32.into(),
-                feature_evaluator_f64: eval_f64.into(),
+    fn window_power_impl<'py, T>(
+        eval: &PeriodogramEvaluator<T>,
+        py: Python<'py>,
+        t: Arr<T>,
+    ) -> Res<Bound<'py, PyUntypedArray>>
+    where
+        T: Float + numpy::Element,
+    {
+        // Magnitudes are not used, we need time series to get the frequency grid only
+        let mut ts = Self::time_series(&t, &t, &None)?;
+        let (_freq, window) = eval.window_power(&mut ts)?;
+        let window = PyArray1::from_vec(py, window);
+        Ok(window.as_untyped().clone())
+    }
+
+    fn false_alarm_probability_impl<'py, T>(
+        eval: &PeriodogramEvaluator<T>,
+        py: Python<'py>,
+        t: Arr<T>,
+        m: Arr<T>,
+        sigma: Option<Arr<T>>,
+        power: &PyArrayLikeDyn<'py, f64, AllowTypeChange>,
+    ) -> Res<Bound<'py, PyUntypedArray>>
+    where
+        T: Float + numpy::Element,
+    {
+        if !eval.algorithm().supports_baluev() && matches!(eval.fap_method(), FapMethod::Baluev) {
+            return Err(Exception::ValueError(
+                "Baluev approximation is supported for single-term Lomb-Scargle periodogram only, use fap='bootstrap'"
+                    .to_owned(),
+            ));
+        }
+        let mut ts = Self::time_series(&t, &m, &sigma)?;
+        let power = power.as_array();
+        let power_flat: Vec<_> = power.iter().copied().collect();
+        let fap = eval.false_alarm_probability(&mut ts, sigma.is_some(), &power_flat)?;
+        let fap = ndarray::ArrayD::from_shape_vec(power.raw_dim(), fap)
+            .expect("output has the same size as input");
+        Ok(PyArrayDyn::from_owned_array(py, fap).as_untyped().clone())
+    }
+
+    /// Frequency grids and power values of light curves computed in parallel
+    fn par_freq_power<T>(
+        eval: &PeriodogramEvaluator<T>,
+        lcs: &[PyLightCurve<T>],
+        n_jobs: i64,
+    ) -> Res<Vec<(Vec<T>, Vec<T>)>>
+    where
+        T: Float + numpy::Element,
+    {
+        let n_jobs = if n_jobs < 0 { 0 } else { n_jobs as usize };
+
+        let mut tss: Vec<_> = lcs
+            .iter()
+            .map(|(t, m, sigma)| Ok((Self::time_series(t, m, sigma)?, sigma.is_some())))
+            .collect::<Res<_>>()?;
+
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n_jobs)
+            .build()
+            .unwrap()
+            .install(|| {
+                tss.par_iter_mut()
+                    .map(|(ts, has_errors)| Ok(eval.freq_power(ts, *has_errors)?))
+                    .collect()
+            })
+    }
+
+    /// Power values stacked into 2-D array for the fixed frequency grid
+    fn stack_power<T>(results: Vec<(Vec<T>, Vec<T>)>) -> ndarray::Array2<T>
+    where
+        T: Float,
+    {
+        let n_freq = results.first().map_or(0, |(freq, _power)| freq.len());
+        ndarray::Array2::from_shape_vec(
+            (results.len(), n_freq),
+            results
+                .into_iter()
+                .flat_map(|(_freq, power)| power)
+                .collect(),
+        )
+        .expect("fixed frequency grid has the same size for all light curves")
+    }
+
+    fn power_many_impl<'py, T>(
+        &self,
+        eval: &PeriodogramEvaluator<T>,
+        py: Python<'py>,
+        lcs: PyLcs<'py>,
+        n_jobs: i64,
+    ) -> Res<Bound<'py, PyAny>>
+    where
+        T: Float + numpy::Element,
+    {
+        let lcs = PyFeatureEvaluator::downcast_lcs(lcs)?;
+        let results = Self::par_freq_power(eval, &lcs, n_jobs)?;
+        if self.fixed_freqs {
+            Ok(Self::stack_power(results).into_pyarray(py).into_any())
+        } else {
+            let powers = results
+                .into_iter()
+                .map(|(_freq, power)| PyArray1::from_vec(py, power));
+            Ok(PyList::new(py, powers)?.into_any())
+        }
+    }
+
+    fn freq_power_many_impl<'py, T>(
+        &self,
+        eval: &PeriodogramEvaluator<T>,
+        py: Python<'py>,
+        lcs: PyLcs<'py>,
+        n_jobs: i64,
+    ) -> Res<Bound<'py, PyAny>>
+    where
+        T: Float + numpy::Element,
+    {
+        let lcs = PyFeatureEvaluator::downcast_lcs(lcs)?;
+        let results = Self::par_freq_power(eval, &lcs, n_jobs)?;
+        if self.fixed_freqs {
+            let freq = PyArray1::from_slice(py, results.first().map_or(&[][..], |(freq, _)| freq));
+            let power = Self::stack_power(results).into_pyarray(py);
+            Ok(PyTuple::new(py, [freq.into_any(), power.into_any()])?.into_any())
+        } else {
+            let freq_powers = results
+                .into_iter()
+                .map(|(freq, power)| (PyArray1::from_vec(py, freq), PyArray1::from_vec(py, power)));
+            Ok(PyList::new(py, freq_powers)?.into_any())
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn multiband_features_impl<'py, T>(
+        eval: &PeriodogramEvaluator<T>,
+        feature_evaluator: &Evaluator<T>,
+        py: Python<'py>,
+        t: Arr<T>,
+        m: Arr<T>,
+        sigma: Option<Arr<T>>,
+        band: &[usize],
+        fill_value: Option<T>,
+    ) -> Res<Bound<'py, PyUntypedArray>>
+    where
+        T: Float + numpy::Element,
+    {
+        Self::check_multiband(eval, &t, band)?;
+        let mut ts = Self::time_series(&t, &m, &sigma)?;
+        let result = feature_evaluator
+            .eval_multiband(&mut ts, band)
+            .expect("Periodogram evaluator must support multi-band time series");
+        let values = match (result, fill_value) {
+            (Ok(values), _) => values,
+            (Err(_), Some(fill_value)) => vec![fill_value; feature_evaluator.size_hint()],
+            (Err(err), None) => return Err(err.into()),
+        };
+        Ok(PyArray1::from_vec(py, values).as_untyped().clone())
+    }
+
+    /// Dense band indices of the observations in order of the first appearance of the labels
+    fn band_indices(band: &Bound<PyAny>) -> Res<Vec<usize>> {
+        let labels = PyDict::new(band.py());
+        let mut indices = vec![];
+        for label in band.try_iter()? {
+            let label = label?;
+            let index = match labels.get_item(&label)? {
+                Some(index) => index.extract()?,
+                None => {
+                    let index = labels.len();
+                    labels.set_item(&label, index)?;
+                    index
+                }
+            };
+            indices.push(index);
+        }
+        Ok(indices)
+    }
+
+    fn check_multiband<T>(eval: &PeriodogramEvaluator<T>, t: &Arr<T>, band: &[usize]) -> Res<()>
+    where
+        T: Float + numpy::Element,
+    {
+        if !eval.is_multiband_supported() {
+            return Err(Exception::ValueError(
+                "multi-band periodogram is supported for algorithm='lomb-scargle' without fap only"
+                    .to_owned(),
+            ));
+        }
+        if band.len() != t.as_array().len() {
+            return Err(Exception::ValueError(format!(
+                "band must have the same length as t, {} != {}",
+                band.len(),
+                t.as_array().len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Time series weighted by inverse squared errors if they are given
+    ///
+    /// Arrays are checked the same way as by `many`: they must have the same size, `t` must be
+    /// sorted, `t` and `m` must be finite and `sigma` must have no NaNs
+    fn time_series<'a, T>(
+        t: &'a Arr<'a, T>,
+        m: &'a Arr<'a, T>,
+        sigma: &'a Option<Arr<'a, T>>,
+    ) -> Res<TimeSeries<'a, T>>
+    where
+        T: Float + numpy::Element,
+    {
+        if t.len() != m.len() {
+            return Err(Exception::ValueError(
+                "t and m must have the same size".to_string(),
+            ));
+        }
+        if let Some(sigma) = sigma {
+            if t.len() != sigma.len() {
+                return Err(Exception::ValueError(
+                    "t and sigma must have the same size".to_string(),
+                ));
+            }
+        }
+        let (t, m) = (t.as_array(), m.as_array());
+        check_finite(t)?;
+        check_finite(m)?;
+        let mut t: DataSample<_> = t.into();
+        check_sorted(t.as_slice(), None)?;
+        let m: DataSample<_> = m.into();
+        let ts = match sigma {
+            Some(sigma) => {
+                let sigma = sigma.as_array();
+                check_no_nans(sigma)?;
+                TimeSeries::new(t, m, sigma.mapv(|x| x.powi(-2)))
+            }
+            None => TimeSeries::new_without_weight(t, m),
+        };
+        Ok(ts)
+    }
+}
+
+#[pymethods]
+impl Periodogram {
+    #[allow(clippy::too_many_arguments)]
+    #[new]
+    #[pyo3(signature = (
+        *,
+        peaks = LcfPeriodogram::<f64>::default_peaks(),
+        resolution = LcfPeriodogram::<f64>::default_resolution(),
+        max_freq_factor = LcfPeriodogram::<f64>::default_max_freq_factor(),
+        nyquist = NyquistArgumentOfPeriodogram::String(String::from("average")),
+        freqs = None,
+        grid = None,
+        min_period = None,
+        max_period = None,
+        n = None,
+        fast = true,
+        algorithm = "lomb-scargle",
+        nterms = 1,
+        phase_bins = None,
+        magnitude_bins = None,
+        max_duration = None,
+        features = None,
+        transform = None,
+        fap = None,
+        n_bootstrap = FapMethod::DEFAULT_N_BOOTSTRAP,
+        random_seed = None,
+        aliases = None,
+        alias_tolerance = AliasPeaks::DEFAULT_TOLERANCE,
+        peak_widths = false,
+    ))]
+    fn __new__(
+        peaks: Option<usize>,
+        resolution: Option<f32>,
+        max_freq_factor: Option<f32>,
+        nyquist: Option<NyquistArgumentOfPeriodogram>,
+        freqs: Option<Bound<PyAny>>,
+        grid: Option<&str>,
+        min_period: Option<f64>,
+        max_period: Option<f64>,
+        n: Option<usize>,
+        fast: Option<bool>,
+        algorithm: &str,
+        nterms: usize,
+        phase_bins: Option<usize>,
+        magnitude_bins: Option<usize>,
+        max_duration: Option<f64>,
+        features: Option<Bound<PyAny>>,
+        transform: Option<Bound<PyAny>>,
+        fap: Option<&str>,
+        n_bootstrap: usize,
+        random_seed: Option<u64>,
+        aliases: Option<&str>,
+        alias_tolerance: f64,
+        peak_widths: bool,
+    ) -> PyResult<(Self, PyFeatureEvaluator)> {
+        let algorithm =
+            Self::parse_algorithm(algorithm, nterms, phase_bins, magnitude_bins, max_duration)?;
+        let fap = Self::parse_fap(fap, n_bootstrap, random_seed, algorithm)?;
+        let aliases = Self::parse_aliases(aliases, alias_tolerance)?;
+        let transform = parse_transform_option(transform)?;
+        let default_transform = matches!(transform, Some(ParsedTransform::Default));
+        let grid = Self::parse_grid(grid, min_period, max_period, n)?;
+        if grid.is_some() && freqs.is_some() {
+            return Err(PyValueError::new_err(
+                "freqs and grid cannot be given together",
+            ));
+        }
+        let fixed_freqs = freqs.is_some() || grid.is_some();
+        let (eval_f32, eval_f64, output_transformers) = Self::create_evals(
+            peaks,
+            resolution,
+            max_freq_factor,
+            nyquist,
+            freqs,
+            grid,
+            fast,
+            algorithm,
+            features,
+            default_transform,
+            fap,
+            aliases,
+            peak_widths,
+        )?;
+        let fe = PyFeatureEvaluator {
+            feature_evaluator_f32: Evaluator::Periodogram(eval_f32.clone()),
+            feature_evaluator_f64: Evaluator::Periodogram(eval_f64.clone()),
+        }
+        .annotated(output_transformers);
+        let fe = match transform {
+            Some(ParsedTransform::Default) => {
+                let peaks = peaks.unwrap_or_else(LcfPeriodogram::<f64>::default_peaks);
+                fe.with_custom_transform(Self::default_peak_transformer(
+                    peaks,
+                    eval_f64.peak_size(),
+                    eval_f64.size_hint(),
+                ))?
+            }
+            Some(transform) => fe.with_meta_transform(transform)?,
+            None => fe,
+        };
+        Ok((
+            Self {
+                eval_f32,
+                eval_f64,
+                fixed_freqs,
             },
+            fe,
         ))
     }
 
     /// Periodogram values
-    #[pyo3(signature = (t, m, *, cast=false))]
+    #[pyo3(signature = (t, m, sigma=None, *, band=None, cast=false))]
     fn power<'py>(
         &self,
         py: Python<'py>,
         t: Bound<PyAny>,
         m: Bound<PyAny>,
+        sigma: Option<Bound<PyAny>>,
+        band: Option<Bound<PyAny>>,
         cast: bool,
     ) -> Res<Bound<'py, PyUntypedArray>> {
-        dtype_dispatch!(
-            |t, m| Self::power_impl(&self.eval_f32, py, t, m),
-            |t, m| Self::power_impl(&self.eval_f64, py, t, m),
-            t,
-            =m;
-            cast=cast
-        )
+        let band = band.map(|band| Self::band_indices(&band)).transpose()?;
+        if let Some(sigma) = sigma {
+            dtype_dispatch!(
+                |t, m, sigma| Self::power_impl(&self.eval_f32, py, t, m, Some(sigma), band.as_deref()),
+                |t, m, sigma| Self::power_impl(&self.eval_f64, py, t, m, Some(sigma), band.as_deref()),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| Self::power_impl(&self.eval_f32, py, t, m, None, band.as_deref()),
+                |t, m| Self::power_impl(&self.eval_f64, py, t, m, None, band.as_deref()),
+                t,
+                =m;
+                cast=cast
+            )
+        }
     }
 
     /// Angular frequencies and periodogram values
-    #[pyo3(signature = (t, m, *, cast=false))]
+    #[pyo3(signature = (t, m, sigma=None, *, band=None, cast=false))]
     fn freq_power<'py>(
         &self,
         py: Python<'py>,
         t: Bound<PyAny>,
         m: Bound<PyAny>,
+        sigma: Option<Bound<PyAny>>,
+        band: Option<Bound<PyAny>>,
         cast: bool,
     ) -> Res<(Bound<'py, PyUntypedArray>, Bound<'py, PyUntypedArray>)> {
+        let band = band.map(|band| Self::band_indices(&band)).transpose()?;
+        if let Some(sigma) = sigma {
+            dtype_dispatch!(
+                |t, m, sigma| Self::freq_power_impl(&self.eval_f32, py, t, m, Some(sigma), band.as_deref()),
+                |t, m, sigma| Self::freq_power_impl(&self.eval_f64, py, t, m, Some(sigma), band.as_deref()),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| Self::freq_power_impl(&self.eval_f32, py, t, m, None, band.as_deref()),
+                |t, m| Self::freq_power_impl(&self.eval_f64, py, t, m, None, band.as_deref()),
+                t,
+                =m;
+                cast=cast
+            )
+        }
+    }
+
+    /// Periodogram values of multiple light curves computed in parallel
+    #[pyo3(signature = (lcs, *, n_jobs=-1))]
+    fn power_many<'py>(
+        &self,
+        py: Python<'py>,
+        lcs: PyLcs<'py>,
+        n_jobs: i64,
+    ) -> Res<Bound<'py, PyAny>> {
+        if lcs.is_empty() {
+            Err(Exception::ValueError("lcs is empty".to_string()))
+        } else {
+            dtype_dispatch!(
+                |_first_t| self.power_many_impl(&self.eval_f32, py, lcs, n_jobs),
+                |_first_t| self.power_many_impl(&self.eval_f64, py, lcs, n_jobs),
+                lcs[0].0
+            )
+        }
+    }
+
+    /// Frequency grids and periodogram values of multiple light curves computed in parallel
+    #[pyo3(signature = (lcs, *, n_jobs=-1))]
+    fn freq_power_many<'py>(
+        &self,
+        py: Python<'py>,
+        lcs: PyLcs<'py>,
+        n_jobs: i64,
+    ) -> Res<Bound<'py, PyAny>> {
+        if lcs.is_empty() {
+            Err(Exception::ValueError("lcs is empty".to_string()))
+        } else {
+            dtype_dispatch!(
+                |_first_t| self.freq_power_many_impl(&self.eval_f32, py, lcs, n_jobs),
+                |_first_t| self.freq_power_many_impl(&self.eval_f64, py, lcs, n_jobs),
+                lcs[0].0
+            )
+        }
+    }
+
+    /// Spectral window power of the time sampling on the periodogram frequency grid
+    fn window_power<'py>(
+        &self,
+        py: Python<'py>,
+        t: Bound<PyAny>,
+    ) -> Res<Bound<'py, PyUntypedArray>> {
         dtype_dispatch!(
-            |t, m| Self::freq_power_impl(&self.eval_f32, py, t, m),
-            |t, m| Self::freq_power_impl(&self.eval_f64, py, t, m),
-            t,
-            =m;
-            cast=cast
+            |t| Self::window_power_impl(&self.eval_f32, py, t),
+            |t| Self::window_power_impl(&self.eval_f64, py, t),
+            t
         )
     }
 
+    /// False alarm probabilities of periodogram power values
+    #[pyo3(signature = (t, m, power, sigma=None, *, cast=false))]
+    fn false_alarm_probability<'py>(
+        &self,
+        py: Python<'py>,
+        t: Bound<PyAny>,
+        m: Bound<PyAny>,
+        power: PyArrayLikeDyn<'py, f64, AllowTypeChange>,
+        sigma: Option<Bound<PyAny>>,
+        cast: bool,
+    ) -> Res<Bound<'py, PyUntypedArray>> {
+        if let Some(sigma) = sigma {
+            dtype_dispatch!(
+                |t, m, sigma| Self::false_alarm_probability_impl(&self.eval_f32, py, t, m, Some(sigma), &power),
+                |t, m, sigma| Self::false_alarm_probability_impl(&self.eval_f64, py, t, m, Some(sigma), &power),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| Self::false_alarm_probability_impl(&self.eval_f32, py, t, m, None, &power),
+                |t, m| Self::false_alarm_probability_impl(&self.eval_f64, py, t, m, None, &power),
+                t,
+                =m;
+                cast=cast
+            )
+        }
+    }
+
+    /// Feature values of multi-band light curve
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (t, m, band, sigma=None, *, fill_value=None, cast=false))]
+    fn multiband_features<'py>(
+        slf: PyRef<'py, Self>,
+        py: Python<'py>,
+        t: Bound<PyAny>,
+        m: Bound<PyAny>,
+        band: Bound<PyAny>,
+        sigma: Option<Bound<PyAny>>,
+        fill_value: Option<f64>,
+        cast: bool,
+    ) -> Res<Bound<'py, PyUntypedArray>> {
+        let band = Self::band_indices(&band)?;
+        let supr = slf.as_super();
+        if let Some(sigma) = sigma {
+            dtype_dispatch!(
+                |t, m, sigma| Self::multiband_features_impl(
+                    &slf.eval_f32,
+                    &supr.feature_evaluator_f32,
+                    py,
+                    t,
+                    m,
+                    Some(sigma),
+                    &band,
+                    fill_value.map(|v| v as f32),
+                ),
+                |t, m, sigma| Self::multiband_features_impl(
+                    &slf.eval_f64,
+                    &supr.feature_evaluator_f64,
+                    py,
+                    t,
+                    m,
+                    Some(sigma),
+                    &band,
+                    fill_value,
+                ),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| Self::multiband_features_impl(
+                    &slf.eval_f32,
+                    &supr.feature_evaluator_f32,
+                    py,
+                    t,
+                    m,
+                    None,
+                    &band,
+                    fill_value.map(|v| v as f32),
+                ),
+                |t, m| Self::multiband_features_impl(
+                    &slf.eval_f64,
+                    &supr.feature_evaluator_f64,
+                    py,
+                    t,
+                    m,
+                    None,
+                    &band,
+                    fill_value,
+                ),
+                t,
+                =m;
+                cast=cast
+            )
+        }
+    }
+
     #[classattr]
     fn __doc__() -> String {
         format!(
@@ -1911,20 +2952,126 @@ freqs : array-like or None, optional
     For `fast=False` any grid is accepted, but linear grids, like
     np.linspace(min_freq, max_freq, n), apply some computational
     optimisations.
+grid : str or None, optional
+    Fixed frequency grid defined by the range of periods, `min_period`,
+    `max_period` and `n` must be given. Could be one of:
+     - 'log-freq': `n` frequencies evenly spaced in logarithm
+     - 'linear-period': `n` evenly spaced periods
+    If given, `resolution`, `max_freq_factor` and `nyquist` are being
+    ignored and `freqs` must be None. The periodogram is evaluated
+    directly on these grids whatever `fast` is. Default is None
+min_period : float or None, optional
+    Minimum period of the `grid`
+max_period : float or None, optional
+    Maximum period of the `grid`
+n : int or None, optional
+    Number of the `grid` frequencies
 fast : bool or None, optional
     Use "Fast" (approximate and FFT-based) or direct periodogram algorithm,
     default is {default_fast}
+algorithm : str, optional
+    Period search algorithm, all of them use the same frequency grid and
+    the same peak outputs. Could be one of:
+     - 'lomb-scargle': Lomb-Scargle periodogram, see below
+     - 'pdm': phase dispersion minimization (Stellingwerf 1978), power is
+        one minus the ratio of pooled in-bin variance to total variance
+     - 'conditional-entropy': conditional entropy of magnitude given phase
+        (Graham et al. 2013), power is one minus the entropy normalized by
+        its maximum value
+     - 'bls': box least squares for transits and eclipses (Kovács et al.
+        2002), power is the relative chi-squared reduction of the box
+        model. Additional `period_depth_i` and `period_duration_i` outputs
+        are added for each peak: the difference of in-box and out-of-box
+        mean magnitudes, positive for eclipses, and the duration of the
+        box
+    Observations are weighted by inverse squared errors for all algorithms.
+    Algorithms other than single-term Lomb-Scargle periodogram cannot be
+    used inside `Bins` and other `Periodogram` objects. Default is
+    'lomb-scargle'
+nterms : int, optional
+    Number of Fourier terms of the Lomb-Scargle periodogram model, values
+    larger than one give multi-term generalized periodogram, which fits
+    periodic non-sinusoidal signals better, e.g. RR Lyrae and eclipsing
+    binaries. For `fast=True` it is supported for the default frequency
+    grid and zero-based linear `freqs` only, direct algorithm is used
+    otherwise. Default is 1
+phase_bins : int or None, optional
+    Number of phase bins for phase-binned algorithms, default is
+    {default_pdm_phase_bins} for 'pdm', {default_ce_phase_bins} for 'conditional-entropy' and
+    {default_bls_phase_bins} for 'bls'
+magnitude_bins : int or None, optional
+    Number of magnitude bins for 'conditional-entropy', default is
+    {default_ce_magnitude_bins}
+max_duration : float or None, optional
+    Maximum box duration for 'bls' as a fraction of the period, default is
+    {default_bls_max_duration}
 features : iterable or None, optional
     Features to extract from periodogram considering it as a time-series,
-    default is None which means no additional features
-    Features to extract from periodogram considering it as a time-series
-transform : None, optional
-    Not supported for Periodogram, peaks are not transformed, but you still
-    may apply transformation for the underlying features with thier
-    constructors
+    default is None which means no additional features. They must not have
+    `transform.Transformer` transformers, use `transform` argument instead
+{transform}
+    For 'default' periods are transformed with 'lg' and other peak outputs,
+    e.g. signal-to-noise ratios and false alarm probabilities, are not
+    transformed.
+fap : str or None, optional
+    Method to estimate false alarm probabilities of the peaks, if given, an
+    additional `period_fap_i` output is added after the period and
+    signal-to-noise ratio of each peak. Could be one of:
+     - 'baluev': analytic upper-bound approximation of Baluev (2008)
+     - 'bootstrap': fraction of light curves with magnitudes and errors
+        resampled with replacement, which maximum power exceeds the peak
+        value
+    Missing peaks have false alarm probability of unity, 'baluev' is
+    supported for single-term Lomb-Scargle periodogram only. Default is
+    None
+n_bootstrap : int, optional
+    Number of resampled light curves for `fap='bootstrap'`, default is
+    {default_n_bootstrap}
+random_seed : int or None, optional
+    Random seed for `fap='bootstrap'`, default is None which means random
+    seed. Resampled light curves are processed in parallel, but results
+    are reproducible for fixed seed
+aliases : str or None, optional
+    Treatment of the peaks which coincide with peaks of the spectral window,
+    i.e. could be caused by the time sampling only, see `window_power`.
+    Window peaks are zero frequency and local maxima with window power of
+    at least {alias_window_threshold}. Could be one of:
+     - 'flag': an additional `period_alias_i` output is added for each
+        peak, it is unity for alias peaks and zero otherwise
+     - 'suppress': alias peaks are skipped
+    Default is None which means no check
+alias_tolerance : float, optional
+    Maximum distance between periodogram and window peak frequencies for
+    `aliases`, in units of the frequency resolution 2 pi / T, where T is
+    the time series duration. Default is {default_alias_tolerance}
+peak_widths : bool, optional
+    Add `period_width_i` and `period_error_i` outputs for each peak: the
+    half width at half maximum of the peak in angular frequency and the
+    period uncertainty 2 pi width / freq^2. They are computed from the
+    `freq_power` spectrum, the peak position and height are refined by the
+    parabolic interpolation and the half-maximum crossings are linearly
+    interpolated between grid points. Default is False
+
+If observation errors are given or `nterms` is larger than one, the
+generalized Lomb-Scargle periodogram (Zechmeister & Kürster 2009) is used:
+the floating-mean Fourier series model is fitted with inverse squared
+errors as weights. Its power is (N - 1) / 2 times the relative chi-squared
+reduction. Otherwise, the classic Lomb-Scargle periodogram is used, which
+fits the sine model to the values with subtracted sample mean, and has the
+same normalization. Note that equal errors still give the generalized
+periodogram, which generally differs from the classic one, but doesn't
+depend on the error value. Periodograms used inside `Bins` and other
+`Periodogram` objects ignore observation errors.
+
+Multi-band periodogram (VanderPlas & Ivezić 2015) is computed if `band`
+is given to `power`, `freq_power` or `multiband_features`: every band has
+its own floating mean and Fourier series amplitudes, but the frequency is
+shared. Its power is (N - 1) / 2 times the relative chi-squared reduction
+of all observations, with constant per-band means as the reference model.
+It is supported for algorithm='lomb-scargle' without `fap` only.
 
 {common}
-freq_power(t, m, *, cast=False)
+freq_power(t, m, sigma=None, *, band=None, cast=False)
     Get periodogram as a pair of frequencies and power values
 
     Parameters
@@ -1933,6 +3080,12 @@ freq_power(t, m, *, cast=False)
         Time array
     m : np.ndarray of np.float32 or np.float64
         Magnitude (flux) array
+    sigma : np.ndarray of np.float32 or np.float64 or None, optional
+        Observation errors, if given error-weighted generalized
+        Lomb-Scargle periodogram is computed
+    band : array-like or None, optional
+        Passband labels of the observations, any hashable objects like
+        strings or integers. If given, multi-band periodogram is computed
     cast : bool, optional
         Cast inputs to np.ndarray objects of the same dtype
 
@@ -1943,7 +3096,7 @@ freq_power(t, m, *, cast=False)
     power : np.ndarray of np.float32 or np.float64
         Periodogram power
 
-power(t, m, *, cast=False)
+power(t, m, sigma=None, *, band=None, cast=False)
     Get periodogram power
 
     Parameters
@@ -1952,6 +3105,10 @@ power(t, m, *, cast=False)
         Time array
     m : np.ndarray of np.float32 or np.float64
         Magnitude (flux) array
+    sigma : np.ndarray of np.float32 or np.float64 or None, optional
+        Observation errors, see `freq_power`
+    band : array-like or None, optional
+        Passband labels of the observations, see `freq_power`
     cast : bool, optional
         Cast inputs to np.ndarray objects of the same dtype
 
@@ -1960,6 +3117,110 @@ power(t, m, *, cast=False)
     power : np.ndarray of np.float32 or np.float64
         Periodogram power
 
+power_many(lcs, *, n_jobs=-1)
+    Get periodogram power of multiple light curves in parallel
+
+    Parameters
+    ----------
+    lcs : list of (t, m, sigma)
+        A collection of light curves packed into three-tuples, all light
+        curves must be represented by numpy.ndarray of the same dtype,
+        sigma could be None. See `freq_power` for details
+    n_jobs : int
+        Number of tasks to run in paralell. Default is -1 which means run as
+        many jobs as CPU count. See rayon rust crate documentation for
+        details
+
+    Returns
+    -------
+    power : np.ndarray or list of np.ndarray
+        2-D array of shape (len(lcs), len(freqs)) if `freqs` is given, list
+        of 1-D periodogram power arrays otherwise
+
+freq_power_many(lcs, *, n_jobs=-1)
+    Get periodograms of multiple light curves in parallel as pairs of
+    frequencies and power values
+
+    Parameters
+    ----------
+    lcs : list of (t, m, sigma)
+        A collection of light curves, see `power_many`
+    n_jobs : int
+        Number of tasks to run in paralell, see `power_many`
+
+    Returns
+    -------
+    tuple of np.ndarray or list of tuples of np.ndarray
+        If `freqs` is given, a pair of the frequency grid and 2-D power
+        array of shape (len(lcs), len(freqs)), list of (freq, power) pairs
+        otherwise
+
+multiband_features(t, m, band, sigma=None, *, fill_value=None, cast=False)
+    Extract features from multi-band periodogram, the output layout is the
+    same as of the object call
+
+    Parameters
+    ----------
+    t : np.ndarray of np.float32 or np.float64
+        Time array
+    m : np.ndarray of np.float32 or np.float64
+        Magnitude (flux) array
+    band : array-like
+        Passband labels of the observations, see `freq_power`
+    sigma : np.ndarray of np.float32 or np.float64 or None, optional
+        Observation errors, see `freq_power`
+    fill_value : float or None, optional
+        Value to fill invalid feature values, for example if count of
+        observations is not enough to find a proper value.
+        None causes exception for invalid features
+    cast : bool, optional
+        Cast inputs to np.ndarray objects of the same dtype
+
+    Returns
+    -------
+    ndarray of np.float32 or np.float64
+        Extracted feature array
+
+false_alarm_probability(t, m, power, sigma=None, *, cast=False)
+    Get false alarm probabilities of periodogram power values, i.e.
+    probabilities that the maximum of periodogram of pure noise light curve
+    exceeds given values. Method is defined by `fap`, 'baluev' is used if it
+    is None
+
+    Parameters
+    ----------
+    t : np.ndarray of np.float32 or np.float64
+        Time array
+    m : np.ndarray of np.float32 or np.float64
+        Magnitude (flux) array
+    power : float or array-like
+        Periodogram power values
+    sigma : np.ndarray of np.float32 or np.float64 or None, optional
+        Observation errors, see `freq_power`
+    cast : bool, optional
+        Cast inputs to np.ndarray objects of the same dtype
+
+    Returns
+    -------
+    fap : np.ndarray of np.float64
+        False alarm probabilities of the same shape as `power`
+
+window_power(t)
+    Get spectral window of the time sampling, |sum_i exp(i freq t_i)|^2 / N^2,
+    on the same frequency grid as `freq_power`. It is unity at zero
+    frequency, its peaks at non-zero frequencies produce alias peaks of
+    periodogram, e.g. daily aliases of ground-based observations
+
+    Parameters
+    ----------
+    t : np.ndarray of np.float32 or np.float64
+        Time array
+
+    Returns
+    -------
+    window : np.ndarray of np.float32 or np.float64
+        Spectral window power
+
 Examples
 --------
 >>> import numpy as np
@@ -1977,6 +3238,268 @@ Examples
             default_max_freq_factor = LcfPeriodogram::<f64>::default_max_freq_factor(),
             default_nyquist = "average",
             default_fast = "True",
+            default_n_bootstrap = FapMethod::DEFAULT_N_BOOTSTRAP,
+            alias_window_threshold = AliasPeaks::WINDOW_POWER_THRESHOLD,
+            default_alias_tolerance = AliasPeaks::DEFAULT_TOLERANCE,
+            default_pdm_phase_bins = Algorithm::DEFAULT_PDM_PHASE_BINS,
+            default_ce_phase_bins = Algorithm::DEFAULT_CE_PHASE_BINS,
+            default_ce_magnitude_bins = Algorithm::DEFAULT_CE_MAGNITUDE_BINS,
+            default_bls_phase_bins = Algorithm::DEFAULT_BLS_PHASE_BINS,
+            default_bls_max_duration = Algorithm::DEFAULT_BLS_MAX_DURATION,
+            transform = META_TRANSFORM_DOC,
+            common = ATTRIBUTES_DOC,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[pyclass(extends = PyFeatureEvaluator, module="light_curve.light_curve_ext")]
+pub struct FourierDecomposition {
+    eval_f32: fourier::FourierDecomposition<f32>,
+    eval_f64: fourier::FourierDecomposition<f64>,
+}
+
+impl_pickle_serialisation!(FourierDecomposition);
+
+impl FourierDecomposition {
+    const DEFAULT_ORDER: usize = 3;
+
+    /// Default transformation: lg for the period, identity for the Fourier series parameters
+    fn default_transformer(size: usize) -> CustomTransformer {
+        CustomTransformer::Chunks(vec![
+            (CustomTransformer::Stock(StockTransformer::Lg), 1),
+            (
+                CustomTransformer::Stock(StockTransformer::Identity),
+                size - 1,
+            ),
+        ])
+    }
+
+    fn fold_impl<'py, T>(
+        eval: &fourier::FourierDecomposition<T>,
+        py: Python<'py>,
+        t: Arr<T>,
+        m: Arr<T>,
+        sigma: Option<Arr<T>>,
+        period: Option<f64>,
+    ) -> Res<Bound<'py, PyTuple>>
+    where
+        T: Float + numpy::Element,
+    {
+        if t.len() != m.len() {
+            return Err(Exception::ValueError(
+                "t and m must have the same size".to_string(),
+            ));
+        }
+        if let Some(sigma) = &sigma {
+            if t.len() != sigma.len() {
+                return Err(Exception::ValueError(
+                    "t and sigma must have the same size".to_string(),
+                ));
+            }
+        }
+        let period = match period {
+            Some(period) => period,
+            None => {
+                let mut ts = Periodogram::time_series(&t, &m, &sigma)?;
+                eval.period(&mut ts, sigma.is_some())?
+            }
+        };
+        let t = t.as_array();
+        let m = m.as_array();
+        let phase: Vec<_> = t
+            .iter()
+            .map(|&t| (t.to_f64().unwrap() / period).rem_euclid(1.0))
+            .collect();
+        let mut indices: Vec<_> = (0..phase.len()).collect();
+        indices.sort_unstable_by(|&a, &b| phase[a].total_cmp(&phase[b]));
+        let folded_phase = PyArray1::from_iter(
+            py,
+            indices
+                .iter()
+                .map(|&i| num_traits::cast::<f64, T>(phase[i]).unwrap()),
+        );
+        let folded_m = PyArray1::from_iter(py, indices.iter().map(|&i| m[i]));
+        let mut folded = vec![
+            folded_phase.as_untyped().clone(),
+            folded_m.as_untyped().clone(),
+        ];
+        if let Some(sigma) = sigma {
+            let sigma = sigma.as_array();
+            let folded_sigma = PyArray1::from_iter(py, indices.iter().map(|&i| sigma[i]));
+            folded.push(folded_sigma.as_untyped().clone());
+        }
+        Ok(PyTuple::new(py, folded)?)
+    }
+}
+
+#[pymethods]
+impl FourierDecomposition {
+    #[new]
+    #[pyo3(signature = (period, *, order = FourierDecomposition::DEFAULT_ORDER, transform = None))]
+    fn __new__(
+        period: Bound<PyAny>,
+        order: usize,
+        transform: Option<Bound<PyAny>>,
+    ) -> Res<(Self, PyFeatureEvaluator)> {
+        if order == 0 {
+            return Err(Exception::ValueError("order must be positive".to_owned()));
+        }
+        let (source_f32, source_f64) = match period.downcast::<Periodogram>() {
+            Ok(periodogram) => {
+                let periodogram = periodogram.borrow();
+                (
+                    fourier::PeriodSource::Periodogram(periodogram.eval_f32.clone()),
+                    fourier::PeriodSource::Periodogram(periodogram.eval_f64.clone()),
+                )
+            }
+            Err(_) => {
+                let period: f64 = period.extract().map_err(|_| {
+                    Exception::TypeError(
+                        "period must be a float or a Periodogram object".to_owned(),
+                    )
+                })?;
+                if !(period.is_finite() && period > 0.0) {
+                    return Err(Exception::ValueError(
+                        "period must be a positive finite number".to_owned(),
+                    ));
+                }
+                (
+                    fourier::PeriodSource::Fixed(period),
+                    fourier::PeriodSource::Fixed(period),
+                )
+            }
+        };
+        let eval_f32 = fourier::FourierDecomposition::new(source_f32, order);
+        let eval_f64 = fourier::FourierDecomposition::new(source_f64, order);
+        let fe = PyFeatureEvaluator {
+            feature_evaluator_f32: Evaluator::FourierDecomposition(eval_f32.clone()),
+            feature_evaluator_f64: Evaluator::FourierDecomposition(eval_f64.clone()),
+        };
+        let fe = match parse_transform_option(transform)? {
+            Some(ParsedTransform::Default) => {
+                fe.with_custom_transform(Self::default_transformer(eval_f64.size_hint()))?
+            }
+            Some(transform) => fe.with_meta_transform(transform)?,
+            None => fe,
+        };
+        Ok((Self { eval_f32, eval_f64 }, fe))
+    }
+
+    /// Required by pickle.load / pickle.loads
+    #[staticmethod]
+    fn __getnewargs__() -> (f64,) {
+        (1.0,)
+    }
+
+    /// Phase-folded light curve, folded errors are returned if `sigma` is given
+    #[pyo3(signature = (t, m, period=None, *, sigma=None, cast=false))]
+    fn fold<'py>(
+        &self,
+        py: Python<'py>,
+        t: Bound<PyAny>,
+        m: Bound<PyAny>,
+        period: Option<f64>,
+        sigma: Option<Bound<PyAny>>,
+        cast: bool,
+    ) -> Res<Bound<'py, PyTuple>> {
+        if period.is_some_and(|period| !(period.is_finite() && period > 0.0)) {
+            return Err(Exception::ValueError(
+                "period must be a positive finite number".to_owned(),
+            ));
+        }
+        if let Some(sigma) = sigma {
+            dtype_dispatch!(
+                |t, m, sigma| Self::fold_impl(&self.eval_f32, py, t, m, Some(sigma), period),
+                |t, m, sigma| Self::fold_impl(&self.eval_f64, py, t, m, Some(sigma), period),
+                t,
+                =m,
+                =sigma;
+                cast=cast
+            )
+        } else {
+            dtype_dispatch!(
+                |t, m| Self::fold_impl(&self.eval_f32, py, t, m, None, period),
+                |t, m| Self::fold_impl(&self.eval_f64, py, t, m, None, period),
+                t,
+                =m;
+                cast=cast
+            )
+        }
+    }
+
+    #[classattr]
+    fn __doc__() -> String {
+        format!(
+            r#"Fourier series fit of the phase-folded light curve
+
+The light curve is fitted by the Fourier series
+m(t) = A_0 + sum_k A_k sin(2 pi k t / P + phi_k), k = 1..order,
+with weighted least squares, the phases are counted from zero time. The
+outputs are the period P, amplitudes A_k and phases phi_k of all harmonics,
+amplitude ratios R_k1 = A_k / A_1 and relative phases
+phi_k1 = phi_k - k phi_1 modulo 2 pi of the higher harmonics, e.g. R21,
+phi21, R31 and phi31 commonly used to classify pulsating variables
+(Simon & Lee 1981).
+
+- Depends on: **time**, **magnitude**
+- Minimum number of observations: **2 * order + 1**
+- Number of features: **4 * order - 1**
+
+Parameters
+----------
+period : float or Periodogram
+    Fixed period, or `Periodogram` object which highest peak gives the
+    period, observation errors are used by the periodogram if given
+order : int, optional
+    Number of harmonics, default is {default_order}
+{transform}
+    For 'default' the period is transformed with 'lg' and other values are
+    not transformed.
+
+{common}
+fold(t, m, period=None, *, sigma=None, cast=False)
+    Fold light curve with the period
+
+    Parameters
+    ----------
+    t : np.ndarray of np.float32 or np.float64
+        Time array, it must be sorted if the period is found with
+        periodogram
+    m : np.ndarray of np.float32 or np.float64
+        Magnitude (flux) array
+    period : float or None, optional
+        Period to fold with, default is the period of the object, found
+        with periodogram if needed
+    sigma : np.ndarray of np.float32 or np.float64 or None, optional
+        Observation errors, they are used by the periodogram to find the
+        period and are folded together with magnitudes
+    cast : bool, optional
+        Cast inputs to np.ndarray objects of the same dtype
+
+    Returns
+    -------
+    phase : np.ndarray of np.float32 or np.float64
+        Phases in [0, 1) interval sorted in ascending order, counted from
+        zero time
+    m : np.ndarray of np.float32 or np.float64
+        Magnitudes (fluxes) in the order of the phases
+    sigma : np.ndarray of np.float32 or np.float64
+        Errors in the order of the phases, returned only if `sigma` is
+        given
+
+Examples
+--------
+>>> import numpy as np
+>>> from light_curve import FourierDecomposition, Periodogram
+>>> t = np.linspace(0, 10, 101)
+>>> m = np.sin(2*np.pi * t / 0.7) + 0.3 * np.sin(4*np.pi * t / 0.7 + 1.0)
+>>> fourier = FourierDecomposition(Periodogram(peaks=1, fast=False), order=2)
+>>> period, a1, phi1, a2, phi2, r21, phi21 = fourier(t, m)
+>>> phase, folded_m = fourier.fold(t, m)
+"#,
+            default_order = Self::DEFAULT_ORDER,
+            transform = META_TRANSFORM_DOC,
             common = ATTRIBUTES_DOC,
         )
     }
@@ -2000,7 +3523,8 @@ fit_evaluator!(
     VillarFit,
     lcf::VillarFit,
     lcf::VillarInitsBounds,
-    lcf::transformers::villar_fit::VillarFitTransformer::default(),
+    VillarFitTransformer,
+    Some(FitModel::Villar),
     7,
     {
         "no" => lcf::VillarLnPrior::fixed(lcf::LnPrior::none()),
@@ -2054,10 +3578,10 @@ impl OtsuSplit {
         }
         Ok((
             Self {},
-            PyFeatureEvaluator {
-                feature_evaluator_f32: lcf::OtsuSplit::new().into(),
-                feature_evaluator_f64: lcf::OtsuSplit::new().into(),
-            },
+            PyFeatureEvaluator::from_lcf(
+                lcf::OtsuSplit::new().into(),
+                lcf::OtsuSplit::new().into(),
+            ),
         ))
     }
 
@@ -2111,10 +3635,10 @@ impl JsonDeserializedFeature {
     #[new]
     #[pyo3(text_signature = "(json_string)")]
     fn __new__(s: String) -> Res<(Self, PyFeatureEvaluator)> {
-        let feature_evaluator_f32: lcf::Feature<f32> = serde_json::from_str(&s).map_err(|err| {
+        let feature_evaluator_f32: Evaluator<f32> = serde_json::from_str(&s).map_err(|err| {
             Exception::ValueError(format!("Cannot deserialize feature from JSON: {err}"))
         })?;
-        let feature_evaluator_f64: lcf::Feature<f64> = serde_json::from_str(&s).map_err(|err| {
+        let feature_evaluator_f64: Evaluator<f64> = serde_json::from_str(&s).map_err(|err| {
             Exception::ValueError(format!("Cannot deserialize feature from JSON: {err}"))
         })?;
 
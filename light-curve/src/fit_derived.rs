@@ -0,0 +1,376 @@
+use crate::errors::{Exception, Res};
+use crate::evaluator::{Evaluator, OutputTransformer};
+use crate::transform::{CustomTransformer, StockTransformer};
+
+use light_curve_feature::transformers::{
+    bazin_fit::BazinFitTransformer, villar_fit::VillarFitTransformer,
+};
+use light_curve_feature::{self as lcf, prelude::*};
+use serde::{Deserialize, Serialize};
+
+const PEAK_SEARCH_GRID_SIZE: usize = 1025;
+const PEAK_SEARCH_WINDOW_SCALES: f64 = 10.0;
+const REFINE_ITERATIONS: usize = 64;
+const DECLINE_TIME_INTERVAL: f64 = 15.0;
+
+const DERIVED_DESCRIPTIONS: [&str; 4] = [
+    "epoch of the model maximum",
+    "model flux at the maximum, including baseline",
+    "time interval from the half-maximum point on the rising part of the model to the maximum, \
+    half-maximum is counted from the baseline",
+    "magnitude decline of the model from the maximum to 15 time units (days) after it",
+];
+
+fn float<T: lcf::Float>(x: f64) -> T {
+    <T as num_traits::NumCast>::from(x).unwrap()
+}
+
+/// Parametric models which physical properties can be derived from the fit parameters
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub(crate) enum FitModel {
+    Bazin,
+    Villar,
+}
+
+impl FitModel {
+    pub(crate) fn derived_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Bazin => &[
+                "bazin_fit_peak_time",
+                "bazin_fit_peak_flux",
+                "bazin_fit_rise_half_max_time",
+                "bazin_fit_decline_15d",
+            ],
+            Self::Villar => &[
+                "villar_fit_peak_time",
+                "villar_fit_peak_flux",
+                "villar_fit_rise_half_max_time",
+                "villar_fit_decline_15d",
+            ],
+        }
+    }
+
+    pub(crate) fn derived_descriptions(self) -> &'static [&'static str] {
+        &DERIVED_DESCRIPTIONS
+    }
+
+    fn default_transformer<T: lcf::Float>(self) -> lcf::Transformer<T> {
+        match self {
+            Self::Bazin => BazinFitTransformer::default().into(),
+            Self::Villar => VillarFitTransformer::default().into(),
+        }
+    }
+
+    fn f<T: lcf::Float>(self, t: T, params: &[T]) -> T {
+        match self {
+            Self::Bazin => lcf::BazinFit::f(t, params),
+            Self::Villar => lcf::VillarFit::f(t, params),
+        }
+    }
+
+    /// Peak time of the model, NaN if it has no maximum
+    ///
+    /// Bazin peak is `t0 + tau_rise ln(tau_fall / tau_rise - 1)`, it exists for
+    /// `tau_fall > tau_rise` only. Villar peak is found numerically in the given time window.
+    fn peak_time<T: lcf::Float>(self, params: &[T], start: T, end: T) -> T {
+        match self {
+            Self::Bazin => {
+                let (rise, fall) = (params[3].abs(), params[4].abs());
+                if fall > rise {
+                    params[2] + rise * (fall / rise - T::one()).ln()
+                } else {
+                    T::nan()
+                }
+            }
+            Self::Villar => find_peak(|t| self.f(t, params), start, end),
+        }
+    }
+
+    /// Characteristic time scale of the model
+    fn time_scale<T: lcf::Float>(self, params: &[T]) -> T {
+        let rise_fall = params[3].abs() + params[4].abs();
+        match self {
+            Self::Bazin => rise_fall,
+            // Plateau duration
+            Self::Villar => rise_fall + params[6].abs(),
+        }
+    }
+
+    /// Peak time, peak flux, rise time from half-maximum to peak and magnitude decline after peak
+    ///
+    /// Both Bazin and Villar parameters start with amplitude, baseline, reference time, rise time
+    /// and fall time. NaN is returned for the values which cannot be derived.
+    fn derived<T: lcf::Float>(self, params: &[T]) -> [T; 4] {
+        let baseline = params[1];
+        let reference_time = params[2];
+        let scale = self.time_scale(params);
+        if !(scale.is_finite() && scale > T::zero() && reference_time.is_finite()) {
+            return [T::nan(); 4];
+        }
+        let f = |t| self.f(t, params);
+
+        let window = scale * float(PEAK_SEARCH_WINDOW_SCALES);
+        let peak_time = self.peak_time(params, reference_time - window, reference_time + window);
+        let peak_flux = f(peak_time);
+        let rise_half_max_time = if peak_flux > baseline {
+            rise_half_max_time(f, baseline, peak_time, peak_flux, reference_time - window)
+        } else {
+            T::nan()
+        };
+        let decline = {
+            let ratio = f(peak_time + float(DECLINE_TIME_INTERVAL)) / peak_flux;
+            if ratio > T::zero() {
+                float::<T>(-2.5) * ratio.log10()
+            } else {
+                T::nan()
+            }
+        };
+        [peak_time, peak_flux, rise_half_max_time, decline]
+    }
+}
+
+/// Find maximum position on a grid and refine it with golden-section search
+fn find_peak<T, F>(f: F, start: T, end: T) -> T
+where
+    T: lcf::Float,
+    F: Fn(T) -> T,
+{
+    let step = (end - start) / float((PEAK_SEARCH_GRID_SIZE - 1) as f64);
+    let grid = |i: usize| start + step * float(i as f64);
+    let (i_max, _) = (0..PEAK_SEARCH_GRID_SIZE)
+        .map(|i| f(grid(i)))
+        .enumerate()
+        .fold((0, T::neg_infinity()), |(i_max, f_max), (i, x)| {
+            if x > f_max { (i, x) } else { (i_max, f_max) }
+        });
+
+    let inv_phi = float::<T>(0.5 * (f64::sqrt(5.0) - 1.0));
+    let mut a = grid(i_max.saturating_sub(1));
+    let mut b = grid(usize::min(i_max + 1, PEAK_SEARCH_GRID_SIZE - 1));
+    let mut x1 = b - (b - a) * inv_phi;
+    let mut x2 = a + (b - a) * inv_phi;
+    let mut f1 = f(x1);
+    let mut f2 = f(x2);
+    for _ in 0..REFINE_ITERATIONS {
+        if f1 < f2 {
+            a = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = a + (b - a) * inv_phi;
+            f2 = f(x2);
+        } else {
+            b = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = b - (b - a) * inv_phi;
+            f1 = f(x1);
+        }
+    }
+    float::<T>(0.5) * (a + b)
+}
+
+/// Walk from the peak back in time to find half-maximum crossing and refine it with bisection
+fn rise_half_max_time<T, F>(f: F, baseline: T, peak_time: T, peak_flux: T, start: T) -> T
+where
+    T: lcf::Float,
+    F: Fn(T) -> T,
+{
+    let half_max = baseline + float::<T>(0.5) * (peak_flux - baseline);
+    let step = (peak_time - start) / float((PEAK_SEARCH_GRID_SIZE - 1) as f64);
+    let mut right = peak_time;
+    for i in 1..PEAK_SEARCH_GRID_SIZE {
+        let left = peak_time - step * float(i as f64);
+        if f(left) <= half_max {
+            let (mut a, mut b) = (left, right);
+            for _ in 0..REFINE_ITERATIONS {
+                let middle = float::<T>(0.5) * (a + b);
+                if f(middle) <= half_max {
+                    a = middle;
+                } else {
+                    b = middle;
+                }
+            }
+            return peak_time - float::<T>(0.5) * (a + b);
+        }
+        right = left;
+    }
+    T::nan()
+}
+
+/// Transformer of the fit parameters
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum ParamsTransformer<T>
+where
+    T: lcf::Float,
+{
+    /// Model-specific `light-curve-feature` transformer, it cannot be inverted
+    Lcf(lcf::Transformer<T>),
+    Stock(StockTransformer),
+    Custom(CustomTransformer),
+}
+
+impl<T> ParamsTransformer<T>
+where
+    T: lcf::Float,
+{
+    /// Evaluator of transformed fit parameters
+    pub(crate) fn transformed(self, fit: lcf::Feature<T>) -> Res<Evaluator<T>> {
+        let evaluator = Evaluator::Lcf(fit);
+        match self {
+            Self::Lcf(transformer) => evaluator.lcf_transformed(transformer, None),
+            Self::Stock(stock_transformer) => evaluator.stock_transformed(stock_transformer),
+            Self::Custom(transformer) => evaluator.custom_transformed(transformer),
+        }
+    }
+
+    fn transform(&self, values: Vec<T>) -> Vec<T> {
+        match self {
+            Self::Lcf(transformer) => transformer.transform(values),
+            Self::Stock(stock_transformer) => {
+                stock_transformer.lcf_transformer::<T>().transform(values)
+            }
+            Self::Custom(transformer) => transformer.transform(values),
+        }
+    }
+}
+
+/// Parametric fit evaluator extended with physical quantities derived from the model
+///
+/// The derived quantities are always computed from the raw fit parameters, while the transformer
+/// is applied to the fit parameters only.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct FitDerived<T>
+where
+    T: lcf::Float,
+{
+    fit: lcf::Feature<T>,
+    transformer: Option<ParamsTransformer<T>>,
+    model: FitModel,
+    names: Vec<String>,
+    descriptions: Vec<String>,
+}
+
+impl<T> FitDerived<T>
+where
+    T: lcf::Float,
+{
+    pub(crate) fn new(
+        fit: lcf::Feature<T>,
+        transformer: Option<ParamsTransformer<T>>,
+        model: FitModel,
+    ) -> Res<Self> {
+        let to_strings = |v: Vec<&str>| v.into_iter().map(String::from).collect::<Vec<_>>();
+        let lcf_transformer = match &transformer {
+            Some(ParamsTransformer::Lcf(transformer)) => Some(transformer.clone()),
+            Some(ParamsTransformer::Stock(stock_transformer)) => {
+                Some(stock_transformer.lcf_transformer())
+            }
+            Some(ParamsTransformer::Custom(_)) | None => None,
+        };
+        let (params_names, params_descriptions) = match (&transformer, lcf_transformer) {
+            (_, Some(lcf_transformer)) => {
+                // We use the transformed feature to get output names and descriptions only
+                let output: lcf::Feature<T> = lcf::Transformed::new(fit.clone(), lcf_transformer)
+                    .map_err(|err| {
+                        Exception::ValueError(format!(
+                            "feature and transformation are incompatible: {err:?}"
+                        ))
+                    })?
+                    .into();
+                (
+                    to_strings(output.get_names()),
+                    to_strings(output.get_descriptions()),
+                )
+            }
+            (Some(ParamsTransformer::Custom(transformer)), None) => {
+                transformer.check_size(fit.size_hint())?;
+                (
+                    transformer.names(to_strings(fit.get_names())),
+                    transformer.descriptions(to_strings(fit.get_descriptions())),
+                )
+            }
+            (_, None) => (
+                to_strings(fit.get_names()),
+                to_strings(fit.get_descriptions()),
+            ),
+        };
+        let names = params_names
+            .into_iter()
+            .chain(model.derived_names().iter().map(|&name| name.into()))
+            .collect();
+        let descriptions = params_descriptions
+            .into_iter()
+            .chain(
+                model
+                    .derived_descriptions()
+                    .iter()
+                    .map(|&description| description.into()),
+            )
+            .collect();
+        Ok(Self {
+            fit,
+            transformer,
+            model,
+            names,
+            descriptions,
+        })
+    }
+
+    pub(crate) fn fit(&self) -> &lcf::Feature<T> {
+        &self.fit
+    }
+
+    /// Transform fit parameters with the default transformer of the model
+    pub(crate) fn default_transformed(self) -> Res<Self> {
+        let transformer = ParamsTransformer::Lcf(self.model.default_transformer());
+        Self::new(self.fit, Some(transformer), self.model)
+    }
+
+    pub(crate) fn eval(&self, ts: &mut lcf::TimeSeries<T>) -> Result<Vec<T>, lcf::EvaluatorError> {
+        let params = self.fit.eval(ts)?;
+        let derived = self.model.derived(&params);
+        let mut values = match &self.transformer {
+            Some(transformer) => transformer.transform(params),
+            None => params,
+        };
+        values.extend(derived);
+        Ok(values)
+    }
+
+    pub(crate) fn get_names(&self) -> Vec<&str> {
+        self.names.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn get_descriptions(&self) -> Vec<&str> {
+        self.descriptions.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn size_hint(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Derived quantities are never transformed
+    pub(crate) fn output_transformers(&self) -> Vec<OutputTransformer> {
+        let n_derived = self.model.derived_names().len();
+        let n_params = self.size_hint() - n_derived;
+        let params_transformers = match &self.transformer {
+            Some(ParamsTransformer::Lcf(_)) => vec![None; n_params],
+            Some(ParamsTransformer::Stock(stock_transformer)) => {
+                vec![Some(CustomTransformer::Stock(*stock_transformer)); n_params]
+            }
+            Some(ParamsTransformer::Custom(transformer)) => transformer
+                .per_value(n_params)
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => vec![Some(CustomTransformer::identity()); n_params],
+        };
+        params_transformers
+            .into_iter()
+            .chain(std::iter::repeat_n(
+                Some(CustomTransformer::identity()),
+                n_derived,
+            ))
+            .collect()
+    }
+}
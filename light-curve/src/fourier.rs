@@ -0,0 +1,275 @@
+//! Fourier decomposition of periodic light curves
+use crate::evaluator::OutputTransformer;
+use crate::periodogram::PeriodogramEvaluator;
+use crate::transform::CustomTransformer;
+
+use light_curve_feature::{self as lcf, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Relative value of the elimination pivot below which the Fourier series fit is degenerate
+const DEGENERACY_TOLERANCE: f64 = 1e-12;
+
+fn float<T: lcf::Float>(x: f64) -> T {
+    num_traits::cast(x).unwrap()
+}
+
+/// Source of the period used to fold time series
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum PeriodSource<T>
+where
+    T: lcf::Float,
+{
+    Fixed(f64),
+    /// Period of the highest peak of the periodogram
+    Periodogram(PeriodogramEvaluator<T>),
+}
+
+impl<T> PeriodSource<T>
+where
+    T: lcf::Float,
+{
+    pub(crate) fn period(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<f64, lcf::EvaluatorError> {
+        match self {
+            Self::Fixed(period) => Ok(*period),
+            Self::Periodogram(periodogram) => periodogram.top_period(ts, has_errors),
+        }
+    }
+}
+
+/// Fourier series fitted to the time series folded with the period
+///
+/// The model is `A_0 + sum_k A_k sin(2 pi k t / P + phi_k)` for `k = 1..=order`, phases are
+/// counted from zero time. The outputs are the period, amplitudes and phases of all harmonics,
+/// amplitude ratios `R_k1 = A_k / A_1` and relative phases `phi_k1 = phi_k - k phi_1` of the higher
+/// harmonics (Simon & Lee 1981).
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct FourierDecomposition<T>
+where
+    T: lcf::Float,
+{
+    period: PeriodSource<T>,
+    order: usize,
+    names: Vec<String>,
+    descriptions: Vec<String>,
+}
+
+impl<T> FourierDecomposition<T>
+where
+    T: lcf::Float,
+{
+    /// `order` must be positive
+    pub(crate) fn new(period: PeriodSource<T>, order: usize) -> Self {
+        let period_info = std::iter::once((
+            "fourier_period".to_owned(),
+            match &period {
+                PeriodSource::Fixed(_) => "fixed period of the Fourier series".to_owned(),
+                PeriodSource::Periodogram(_) => {
+                    "period of the highest periodogram peak used for the Fourier series".to_owned()
+                }
+            },
+        ));
+        let harmonic_info = (1..=order).flat_map(|k| {
+            [
+                (
+                    format!("fourier_amplitude_{k}"),
+                    format!("amplitude of the {k}-th harmonic of the Fourier series"),
+                ),
+                (
+                    format!("fourier_phase_{k}"),
+                    format!(
+                        "phase of the {k}-th harmonic of the Fourier series, sine model, counted from zero time"
+                    ),
+                ),
+            ]
+        });
+        let relative_info = (2..=order).flat_map(|k| {
+            [
+                (
+                    format!("fourier_r{k}1"),
+                    format!("ratio of amplitudes of the {k}-th and the first harmonics"),
+                ),
+                (
+                    format!("fourier_phi{k}1"),
+                    format!(
+                        "relative phase of the {k}-th harmonic, phi_{k} - {k} phi_1 modulo 2 pi"
+                    ),
+                ),
+            ]
+        });
+        let (names, descriptions) = period_info
+            .chain(harmonic_info)
+            .chain(relative_info)
+            .unzip();
+        Self {
+            period,
+            order,
+            names,
+            descriptions,
+        }
+    }
+
+    /// Period to fold the time series with
+    pub(crate) fn period(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<f64, lcf::EvaluatorError> {
+        self.period.period(ts, has_errors)
+    }
+
+    pub(crate) fn eval(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        let minimum = 2 * self.order + 1;
+        if ts.lenu() < minimum {
+            return Err(lcf::EvaluatorError::ShortTimeSeries {
+                actual: ts.lenu(),
+                minimum,
+            });
+        }
+        let period = self.period(ts, has_errors)?;
+        let coeffs = self.fit(ts, 2.0 * PI / period)?;
+
+        let (amplitudes, phases): (Vec<_>, Vec<_>) = coeffs[1..]
+            .chunks(2)
+            .map(|cos_sin| {
+                let (a, b) = (cos_sin[0], cos_sin[1]);
+                (a.hypot(b), a.atan2(b).rem_euclid(2.0 * PI))
+            })
+            .unzip();
+        let mut values = Vec::with_capacity(self.size_hint());
+        values.push(period);
+        for (&amplitude, &phase) in amplitudes.iter().zip(&phases) {
+            values.extend([amplitude, phase]);
+        }
+        for k in 2..=self.order {
+            values.push(amplitudes[k - 1] / amplitudes[0]);
+            values.push((phases[k - 1] - k as f64 * phases[0]).rem_euclid(2.0 * PI));
+        }
+        Ok(values.into_iter().map(float).collect())
+    }
+
+    /// Weighted least squares coefficients of `1, cos(freq t), sin(freq t), cos(2 freq t), ...`
+    fn fit(&self, ts: &lcf::TimeSeries<T>, freq: f64) -> Result<Vec<f64>, lcf::EvaluatorError> {
+        let size = 2 * self.order + 1;
+        let mut matrix = vec![vec![0.0; size]; size];
+        let mut vector = vec![0.0; size];
+        let mut row = vec![0.0; size];
+        for ((&t, &m), &w) in
+            ts.t.sample
+                .iter()
+                .zip(ts.m.sample.iter())
+                .zip(ts.w.sample.iter())
+        {
+            let (t, m, w) = (
+                t.to_f64().unwrap(),
+                m.to_f64().unwrap(),
+                w.to_f64().unwrap(),
+            );
+            let (sin1, cos1) = (freq * t).sin_cos();
+            let (mut sin, mut cos) = (sin1, cos1);
+            row[0] = 1.0;
+            for k in 0..self.order {
+                row[2 * k + 1] = cos;
+                row[2 * k + 2] = sin;
+                (sin, cos) = (sin * cos1 + cos * sin1, cos * cos1 - sin * sin1);
+            }
+            for ((matrix_row, value), &x) in matrix.iter_mut().zip(vector.iter_mut()).zip(&row) {
+                *value += w * m * x;
+                for (element, &y) in matrix_row.iter_mut().zip(&row) {
+                    *element += w * x * y;
+                }
+            }
+        }
+        solve(matrix, vector).ok_or(lcf::EvaluatorError::ZeroDivision(
+            "Fourier series fit is degenerate",
+        ))
+    }
+
+    pub(crate) fn eval_or_fill(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+        fill_value: T,
+    ) -> Vec<T> {
+        match self.eval(ts, has_errors) {
+            Ok(values) => values,
+            Err(_) => vec![fill_value; self.size_hint()],
+        }
+    }
+
+    pub(crate) fn output_transformers(&self) -> Vec<OutputTransformer> {
+        vec![Some(CustomTransformer::identity()); self.size_hint()]
+    }
+
+    pub(crate) fn get_names(&self) -> Vec<&str> {
+        self.names.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn get_descriptions(&self) -> Vec<&str> {
+        self.descriptions.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn size_hint(&self) -> usize {
+        self.names.len()
+    }
+
+    pub(crate) fn is_t_required(&self) -> bool {
+        true
+    }
+
+    pub(crate) fn is_m_required(&self) -> bool {
+        true
+    }
+
+    pub(crate) fn is_w_required(&self) -> bool {
+        false
+    }
+
+    pub(crate) fn is_sorting_required(&self) -> bool {
+        match &self.period {
+            PeriodSource::Fixed(_) => false,
+            PeriodSource::Periodogram(periodogram) => periodogram.is_sorting_required(),
+        }
+    }
+}
+
+/// Solve linear system with Gaussian elimination with partial pivoting, `None` if it is
+/// degenerate
+fn solve(mut matrix: Vec<Vec<f64>>, mut vector: Vec<f64>) -> Option<Vec<f64>> {
+    let size = vector.len();
+    let scale = (0..size).map(|i| matrix[i][i]).fold(0.0, f64::max);
+    for col in 0..size {
+        let pivot_row =
+            (col..size).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        let pivot_abs = matrix[pivot_row][col].abs();
+        if !pivot_abs.is_finite() || pivot_abs <= DEGENERACY_TOLERANCE * scale {
+            return None;
+        }
+        matrix.swap(col, pivot_row);
+        vector.swap(col, pivot_row);
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let (upper_vector, lower_vector) = vector.split_at_mut(col + 1);
+        let pivot = &upper[col];
+        for (matrix_row, value) in lower.iter_mut().zip(lower_vector.iter_mut()) {
+            let factor = matrix_row[col] / pivot[col];
+            for (element, &pivot_element) in matrix_row[col..].iter_mut().zip(&pivot[col..]) {
+                *element -= factor * pivot_element;
+            }
+            *value -= factor * upper_vector[col];
+        }
+    }
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let sum: f64 = (row + 1..size).map(|j| matrix[row][j] * solution[j]).sum();
+        solution[row] = (vector[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
@@ -4,6 +4,7 @@ use ln_prior::*;
 #[cfg(feature = "mimalloc")]
 use mimalloc::MiMalloc;
 use pyo3::prelude::*;
+use transform::register_transform_submodule;
 
 #[macro_use]
 mod np_array;
@@ -11,8 +12,12 @@ mod check;
 mod cont_array;
 mod dmdt;
 mod errors;
+mod evaluator;
 mod features;
+mod fit_derived;
+mod fourier;
 mod ln_prior;
+mod periodogram;
 mod transform;
 
 #[cfg(feature = "mimalloc")]
@@ -74,6 +79,7 @@ fn light_curve(py: Python, m: Bound<PyModule>) -> PyResult<()> {
     m.add_class::<f::Eta>()?;
     m.add_class::<f::EtaE>()?;
     m.add_class::<f::ExcessVariance>()?;
+    m.add_class::<f::FourierDecomposition>()?;
     m.add_class::<f::JsonDeserializedFeature>()?;
     m.add_class::<f::InterPercentileRange>()?;
     m.add_class::<f::Kurtosis>()?;
@@ -104,6 +110,7 @@ fn light_curve(py: Python, m: Bound<PyModule>) -> PyResult<()> {
     m.add_class::<f::VillarFit>()?;
     m.add_class::<f::WeightedMean>()?;
 
+    register_transform_submodule(py, m.clone())?;
     register_ln_prior_submodule(py, m)?;
 
     Ok(())
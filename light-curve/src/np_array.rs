@@ -285,6 +285,10 @@ macro_rules! dtype_dispatch {
         let [x1, x2, x3] = $arrays;
         $func(x1, x2, x3)
     }};
+    (@call $func:ident, $arrays:ident, $_arg1:expr, $_arg2:expr, $_arg3:expr, $_arg4:expr,) => {{
+        let [x1, x2, x3, x4] = $arrays;
+        $func(x1, x2, x3, x4)
+    }};
     ($func:tt ($first_arg:expr $(,$eq:tt $arg:expr)* $(,)?)) => {
         dtype_dispatch!($func, $func, $first_arg $(,$eq $arg)*)
     };
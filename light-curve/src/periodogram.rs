@@ -0,0 +1,1354 @@
+//! Periodogram evaluator extending `light-curve-feature` one with error-weighted periodogram
+use crate::evaluator::{Evaluator, OutputTransformer};
+use crate::transform::CustomTransformer;
+
+use light_curve_feature::{self as lcf, prelude::*};
+use ndarray::{Array1, ArrayView1};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+pub(crate) type LcfPeriodogram<T> = lcf::Periodogram<T, lcf::Feature<T>>;
+
+/// Number of grid points used to extirpolate a single observation
+const EXTIRPOLATION_POINTS: usize = 6;
+/// Minimum number of FFT grid points per period of the highest frequency
+const FFT_OVERSAMPLING: usize = 8;
+/// Value of the elimination pivot below which model terms are considered degenerate
+const DEGENERACY_TOLERANCE: f64 = 1e-12;
+
+fn float<T: lcf::Float>(x: f64) -> T {
+    num_traits::cast(x).unwrap()
+}
+
+/// Weighted sums of harmonics of a single frequency, weights are normalized
+struct HarmonicSums {
+    /// Sums of w cos(k freq t) for k = 0..=2 nterms
+    cos: Vec<f64>,
+    /// Sums of w sin(k freq t) for k = 0..=2 nterms
+    sin: Vec<f64>,
+    /// Sums of w y cos(k freq t) for k = 0..=nterms
+    y_cos: Vec<f64>,
+    /// Sums of w y sin(k freq t) for k = 0..=nterms
+    y_sin: Vec<f64>,
+}
+
+impl HarmonicSums {
+    fn zeros(nterms: usize) -> Self {
+        Self {
+            cos: vec![0.0; 2 * nterms + 1],
+            sin: vec![0.0; 2 * nterms + 1],
+            y_cos: vec![0.0; nterms + 1],
+            y_sin: vec![0.0; nterms + 1],
+        }
+    }
+
+    /// Sum of w cos(k freq t) for any integer k
+    fn cos(&self, k: isize) -> f64 {
+        self.cos[k.unsigned_abs()]
+    }
+
+    /// Sum of w sin(k freq t) for any integer k
+    fn sin(&self, k: isize) -> f64 {
+        k.signum() as f64 * self.sin[k.unsigned_abs()]
+    }
+}
+
+/// Weighted observations prepared for the generalized Lomb-Scargle periodogram
+struct WeightedSeries {
+    t: Vec<f64>,
+    y: Vec<f64>,
+    /// Normalized weights
+    w: Vec<f64>,
+    /// Sum of original weights
+    w_sum: f64,
+    y_mean: f64,
+    y_var: f64,
+    /// Number of Fourier terms of the model
+    nterms: usize,
+    /// Normalization factor of the power, see [WeightedSeries::power]
+    scale: f64,
+}
+
+impl WeightedSeries {
+    fn new<T: lcf::Float>(ts: &lcf::TimeSeries<T>, nterms: usize) -> Self {
+        Self::from_observations(
+            ts.t.sample
+                .iter()
+                .zip(ts.m.sample.iter())
+                .zip(ts.w.sample.iter())
+                .map(|((&t, &m), &w)| (t, m, w)),
+            nterms,
+        )
+    }
+
+    /// Series from non-empty sequence of time, value and weight triples
+    fn from_observations<T: lcf::Float>(
+        observations: impl Iterator<Item = (T, T, T)>,
+        nterms: usize,
+    ) -> Self {
+        let mut t = vec![];
+        let mut y = vec![];
+        let mut w = vec![];
+        for (t_i, y_i, w_i) in observations {
+            t.push(t_i.to_f64().unwrap());
+            y.push(y_i.to_f64().unwrap());
+            w.push(w_i.to_f64().unwrap());
+        }
+        let t0 = t[0];
+        t.iter_mut().for_each(|t| *t -= t0);
+        let w_sum: f64 = w.iter().sum();
+        w.iter_mut().for_each(|w| *w /= w_sum);
+        let y_mean = y.iter().zip(&w).map(|(y, w)| w * y).sum::<f64>();
+        let y_var = y
+            .iter()
+            .zip(&w)
+            .map(|(y, w)| w * (y - y_mean).powi(2))
+            .sum::<f64>();
+        let scale = 0.5 * (t.len() as f64 - 1.0);
+        Self {
+            t,
+            y,
+            w,
+            w_sum,
+            y_mean,
+            y_var,
+            nterms,
+            scale,
+        }
+    }
+
+    /// Generalized Lomb-Scargle power (Zechmeister & Kürster 2009) from harmonic sums
+    ///
+    /// The power is the relative reduction of the weighted chi-squared of the floating-mean
+    /// Fourier series model with `nterms` harmonics, multiplied by (N - 1) / 2. If `floating_mean`
+    /// is false, the model is fitted to the values with subtracted weighted mean instead, which
+    /// gives the classic Lomb-Scargle periodogram for equal weights and a single term, normalized
+    /// as the `light-curve-feature` one.
+    fn power(&self, sums: &HarmonicSums, floating_mean: bool) -> f64 {
+        if self.y_var <= 0.0 {
+            return 0.0;
+        }
+        // Model columns are cos(k freq t) and sin(k freq t) for k = 1..=nterms, centered by their
+        // weighted means to account for the floating mean
+        let size = 2 * self.nterms;
+        let column = |i: usize| (i % 2 == 0, (i / 2 + 1) as isize);
+        let mean = |(is_cos, k): (bool, isize)| if is_cos { sums.cos(k) } else { sums.sin(k) };
+        let mut matrix = vec![0.0; size * size];
+        let mut vector = vec![0.0; size];
+        for i in 0..size {
+            let (i_is_cos, j) = column(i);
+            let y_sum = if i_is_cos {
+                sums.y_cos[j as usize]
+            } else {
+                sums.y_sin[j as usize]
+            };
+            vector[i] = y_sum - self.y_mean * mean(column(i));
+            for l in 0..size {
+                let (l_is_cos, k) = column(l);
+                // Product-to-sum identities
+                let product = match (i_is_cos, l_is_cos) {
+                    (true, true) => 0.5 * (sums.cos(j - k) + sums.cos(j + k)),
+                    (false, false) => 0.5 * (sums.cos(j - k) - sums.cos(j + k)),
+                    (true, false) => 0.5 * (sums.sin(j + k) - sums.sin(j - k)),
+                    (false, true) => 0.5 * (sums.sin(j + k) + sums.sin(j - k)),
+                };
+                matrix[i * size + l] = if floating_mean {
+                    product - mean(column(i)) * mean(column(l))
+                } else {
+                    product
+                };
+            }
+        }
+        self.scale * degenerate_quadratic_form(matrix, vector) / self.y_var
+    }
+
+    fn direct_sums(&self, freq: f64) -> HarmonicSums {
+        let mut sums = HarmonicSums::zeros(self.nterms);
+        for ((&t, &y), &w) in self.t.iter().zip(&self.y).zip(&self.w) {
+            let (sin1, cos1) = (freq * t).sin_cos();
+            let (mut sin, mut cos) = (0.0, 1.0);
+            for k in 0..=2 * self.nterms {
+                sums.cos[k] += w * cos;
+                sums.sin[k] += w * sin;
+                if k <= self.nterms {
+                    sums.y_cos[k] += w * y * cos;
+                    sums.y_sin[k] += w * y * sin;
+                }
+                (sin, cos) = (sin * cos1 + cos * sin1, cos * cos1 - sin * sin1);
+            }
+        }
+        sums
+    }
+
+    /// Function of harmonic sums, e.g. [WeightedSeries::power], on arbitrary frequency grid
+    fn direct_map(&self, freqs: &[f64], f: impl Fn(&HarmonicSums) -> f64) -> Vec<f64> {
+        freqs
+            .iter()
+            .map(|&freq| f(&self.direct_sums(freq)))
+            .collect()
+    }
+
+    /// Function of harmonic sums, e.g. [WeightedSeries::power], on the grid of `size` frequencies
+    /// `k * step`, `k = 0..size`
+    ///
+    /// Harmonic sums are computed by FFT of observations extirpolated to a regular grid, see
+    /// Press & Rybicki (1989)
+    fn fft_map(&self, step: f64, size: usize, f: impl Fn(&HarmonicSums) -> f64) -> Vec<f64> {
+        // We need sums for harmonics up to 2 nterms
+        let n_fft = (2 * self.nterms * size * FFT_OVERSAMPLING).next_power_of_two();
+        let grid_scale = step * n_fft as f64 / (2.0 * PI);
+        let mut w_grid = vec![0.0; n_fft];
+        let mut wy_grid = vec![0.0; n_fft];
+        for ((&t, &y), &w) in self.t.iter().zip(&self.y).zip(&self.w) {
+            let x = (t * grid_scale).rem_euclid(n_fft as f64);
+            extirpolate(&mut w_grid, x, w);
+            extirpolate(&mut wy_grid, x, w * y);
+        }
+        let w_fft = fft(w_grid);
+        let wy_fft = fft(wy_grid);
+        // Forward FFT gives sums of exp(-i phase), so sine sums have opposite sign
+        (0..size)
+            .map(|k| {
+                let mut sums = HarmonicSums::zeros(self.nterms);
+                for harmonic in 0..=2 * self.nterms {
+                    sums.cos[harmonic] = w_fft[harmonic * k].0;
+                    sums.sin[harmonic] = -w_fft[harmonic * k].1;
+                    if harmonic <= self.nterms {
+                        sums.y_cos[harmonic] = wy_fft[harmonic * k].0;
+                        sums.y_sin[harmonic] = -wy_fft[harmonic * k].1;
+                    }
+                }
+                f(&sums)
+            })
+            .collect()
+    }
+
+    /// Spectral window power `|sum_i w_i exp(i freq t_i)|^2` from harmonic sums, it is unity at
+    /// zero frequency
+    fn window(sums: &HarmonicSums) -> f64 {
+        sums.cos[1].powi(2) + sums.sin[1].powi(2)
+    }
+}
+
+/// Quadratic form of the pseudo-inverse of symmetric positive semi-definite matrix
+///
+/// Computes `b^T A^+ b` with Gaussian elimination skipping degenerate pivots, e.g. sine terms for
+/// the zero frequency. The matrix must be of order of unity, which holds for normalized weights,
+/// and `b` must belong to its column space.
+fn degenerate_quadratic_form(mut matrix: Vec<f64>, mut vector: Vec<f64>) -> f64 {
+    let size = vector.len();
+    let mut result = 0.0;
+    for i in 0..size {
+        let pivot = matrix[i * size + i];
+        if pivot <= DEGENERACY_TOLERANCE {
+            continue;
+        }
+        result += vector[i].powi(2) / pivot;
+        for j in i + 1..size {
+            let factor = matrix[j * size + i] / pivot;
+            vector[j] -= factor * vector[i];
+            for k in i + 1..size {
+                matrix[j * size + k] -= factor * matrix[i * size + k];
+            }
+        }
+    }
+    result
+}
+
+/// Add value at non-integer position of periodic grid using Lagrange interpolation weights
+fn extirpolate(grid: &mut [f64], x: f64, value: f64) {
+    let n = grid.len();
+    let nearest = x.round();
+    if (x - nearest).abs() < f64::EPSILON * n as f64 {
+        grid[nearest as usize % n] += value;
+        return;
+    }
+    let first = (x - 0.5 * EXTIRPOLATION_POINTS as f64).floor() + 1.0;
+    for j in 0..EXTIRPOLATION_POINTS {
+        let node = first + j as f64;
+        let weight = (0..EXTIRPOLATION_POINTS)
+            .filter(|&k| k != j)
+            .map(|k| {
+                let other = first + k as f64;
+                (x - other) / (node - other)
+            })
+            .product::<f64>();
+        grid[node.rem_euclid(n as f64) as usize % n] += weight * value;
+    }
+}
+
+/// Forward FFT of real values, the size must be a power of two
+fn fft(values: Vec<f64>) -> Vec<(f64, f64)> {
+    let n = values.len();
+    let bits = n.trailing_zeros();
+    let mut data = vec![(0.0, 0.0); n];
+    for (i, x) in values.into_iter().enumerate() {
+        let j = if bits == 0 {
+            0
+        } else {
+            i.reverse_bits() >> (usize::BITS - bits)
+        };
+        data[j] = (x, 0.0);
+    }
+    let mut len = 2;
+    while len <= n {
+        let (w_sin, w_cos) = (-2.0 * PI / len as f64).sin_cos();
+        for start in (0..n).step_by(len) {
+            let mut w = (1.0, 0.0);
+            for i in 0..len / 2 {
+                let (a, b) = (data[start + i], data[start + i + len / 2]);
+                let b = (b.0 * w.0 - b.1 * w.1, b.0 * w.1 + b.1 * w.0);
+                data[start + i] = (a.0 + b.0, a.1 + b.1);
+                data[start + i + len / 2] = (a.0 - b.0, a.1 - b.1);
+                w = (w.0 * w_cos - w.1 * w_sin, w.0 * w_sin + w.1 * w_cos);
+            }
+        }
+        len *= 2;
+    }
+    data
+}
+
+/// Natural logarithm of the gamma function for positive arguments, Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |acc, (i, c)| {
+            acc + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Method to estimate false alarm probability of periodogram peaks
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) enum FapMethod {
+    /// Analytic upper-bound approximation of Baluev (2008)
+    Baluev,
+    /// Distribution of the maximum power of light curves resampled with replacement
+    Bootstrap { n: usize, random_seed: Option<u64> },
+}
+
+impl FapMethod {
+    pub(crate) const DEFAULT_N_BOOTSTRAP: usize = 1000;
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Baluev => "Baluev (2008) approximation",
+            Self::Bootstrap { .. } => "bootstrap",
+        }
+    }
+}
+
+/// False alarm probability of the maximum periodogram power, Baluev (2008)
+///
+/// `max_freq` is the maximum angular frequency of the grid. The power is normalized as the
+/// classic periodogram, i.e. it is (N - 1) / 2 times the relative chi-squared reduction.
+fn baluev_fap(series: &WeightedSeries, max_freq: f64, power: f64) -> f64 {
+    let n = series.t.len() as f64;
+    if n < 4.0 {
+        return 1.0;
+    }
+    let z = (2.0 * power / (n - 1.0)).clamp(0.0, 1.0);
+    let fap_single = (1.0 - z).powf(0.5 * (n - 3.0));
+    let t_mean = series
+        .t
+        .iter()
+        .zip(&series.w)
+        .map(|(t, w)| w * t)
+        .sum::<f64>();
+    let t_var = series
+        .t
+        .iter()
+        .zip(&series.w)
+        .map(|(t, w)| w * (t - t_mean).powi(2))
+        .sum::<f64>();
+    // Frequency range in units of the effective baseline resolution
+    let width = max_freq / (2.0 * PI) * (4.0 * PI * t_var).sqrt();
+    let gamma =
+        (2.0 / (n - 1.0)).sqrt() * (ln_gamma(0.5 * (n - 1.0)) - ln_gamma(0.5 * (n - 2.0))).exp();
+    let tau = gamma * width * (1.0 - z).powf(0.5 * (n - 4.0)) * (0.5 * (n - 1.0) * z).sqrt();
+    (1.0 - (1.0 - fap_single) * (-tau).exp()).clamp(0.0, 1.0)
+}
+
+/// Period search algorithm, all of them use the same frequency grid
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Algorithm {
+    /// Generalized Lomb-Scargle periodogram with given number of Fourier terms
+    LombScargle { nterms: usize },
+    /// Phase dispersion minimization (Stellingwerf 1978)
+    PhaseDispersion { phase_bins: usize },
+    /// Conditional entropy of magnitudes given phase (Graham et al. 2013)
+    ConditionalEntropy {
+        phase_bins: usize,
+        magnitude_bins: usize,
+    },
+    /// Box least squares (Kovács et al. 2002), `max_duration` is a fraction of the period
+    BoxLeastSquares {
+        phase_bins: usize,
+        max_duration: f64,
+    },
+}
+
+impl Algorithm {
+    pub(crate) const DEFAULT_PDM_PHASE_BINS: usize = 10;
+    pub(crate) const DEFAULT_CE_PHASE_BINS: usize = 10;
+    pub(crate) const DEFAULT_CE_MAGNITUDE_BINS: usize = 5;
+    pub(crate) const DEFAULT_BLS_PHASE_BINS: usize = 100;
+    pub(crate) const DEFAULT_BLS_MAX_DURATION: f64 = 0.1;
+
+    /// Baluev (2008) false alarm probability approximation is valid for single-term Lomb-Scargle
+    /// periodogram only
+    pub(crate) fn supports_baluev(&self) -> bool {
+        matches!(self, Self::LombScargle { nterms: 1 })
+    }
+
+    fn nterms(&self) -> usize {
+        match self {
+            Self::LombScargle { nterms } => *nterms,
+            _ => 1,
+        }
+    }
+
+    /// Names and descriptions of additional outputs for every peak
+    fn peak_info(&self, i: usize) -> Vec<(String, String)> {
+        match self {
+            Self::BoxLeastSquares { .. } => vec![
+                (
+                    format!("period_depth_{i}"),
+                    format!(
+                        "difference of in-box and out-of-box mean magnitudes of the best box model of the {i}-th highest peak of BLS periodogram"
+                    ),
+                ),
+                (
+                    format!("period_duration_{i}"),
+                    format!(
+                        "duration of the box of the best box model of the {i}-th highest peak of BLS periodogram"
+                    ),
+                ),
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// Power of phase-binned algorithms, the higher the better
+    fn phase_binned_power(&self, series: &WeightedSeries, freq: f64) -> f64 {
+        match *self {
+            Self::LombScargle { .. } => {
+                unreachable!("Lomb-Scargle periodogram is not phase-binned")
+            }
+            Self::PhaseDispersion { phase_bins } => series.phase_dispersion_power(freq, phase_bins),
+            Self::ConditionalEntropy {
+                phase_bins,
+                magnitude_bins,
+            } => series.conditional_entropy_power(freq, phase_bins, magnitude_bins),
+            Self::BoxLeastSquares {
+                phase_bins,
+                max_duration,
+            } => series.best_box(freq, phase_bins, max_duration).power,
+        }
+    }
+}
+
+/// Best box model of the box least squares periodogram
+#[derive(Clone, Copy, Default)]
+struct BoxFit {
+    /// Relative chi-squared reduction
+    power: f64,
+    /// Difference of in-box and out-of-box means
+    depth: f64,
+    /// Box duration in units of time
+    duration: f64,
+}
+
+impl WeightedSeries {
+    /// Phase bin indices for given angular frequency
+    fn phase_bins(&self, freq: f64, phase_bins: usize) -> impl Iterator<Item = usize> + '_ {
+        self.t.iter().map(move |&t| {
+            let phase = (freq * t / (2.0 * PI)).rem_euclid(1.0);
+            usize::min((phase * phase_bins as f64) as usize, phase_bins - 1)
+        })
+    }
+
+    /// Weights and weighted sums of centered values in phase bins
+    fn binned_sums(&self, freq: f64, phase_bins: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        let mut w_sum = vec![0.0; phase_bins];
+        let mut wy_sum = vec![0.0; phase_bins];
+        let mut wy2_sum = vec![0.0; phase_bins];
+        for ((bin, &y), &w) in self.phase_bins(freq, phase_bins).zip(&self.y).zip(&self.w) {
+            let y = y - self.y_mean;
+            w_sum[bin] += w;
+            wy_sum[bin] += w * y;
+            wy2_sum[bin] += w * y.powi(2);
+        }
+        (w_sum, wy_sum, wy2_sum)
+    }
+
+    /// One minus the phase dispersion minimization statistic, i.e. the ratio of the pooled
+    /// in-bin variance to the total variance
+    fn phase_dispersion_power(&self, freq: f64, phase_bins: usize) -> f64 {
+        if self.y_var <= 0.0 {
+            return 0.0;
+        }
+        let (w_sum, wy_sum, wy2_sum) = self.binned_sums(freq, phase_bins);
+        let mut non_empty_bins = 0;
+        let mut in_bin_var = 0.0;
+        for ((&w, &wy), &wy2) in w_sum.iter().zip(&wy_sum).zip(&wy2_sum) {
+            if w > 0.0 {
+                non_empty_bins += 1;
+                in_bin_var += wy2 - wy.powi(2) / w;
+            }
+        }
+        let n = self.t.len() as f64;
+        let dof = n - non_empty_bins as f64;
+        if dof <= 0.0 {
+            return 0.0;
+        }
+        1.0 - (in_bin_var / dof) / (self.y_var / (n - 1.0))
+    }
+
+    /// One minus the conditional entropy of magnitude given phase, normalized by its maximum
+    /// value. Weights are used as probability masses of the observations
+    fn conditional_entropy_power(
+        &self,
+        freq: f64,
+        phase_bins: usize,
+        magnitude_bins: usize,
+    ) -> f64 {
+        let (y_min, y_max) = self
+            .y
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &y| {
+                (min.min(y), max.max(y))
+            });
+        if y_max <= y_min || magnitude_bins < 2 {
+            return 0.0;
+        }
+        let mut prob = vec![0.0; phase_bins * magnitude_bins];
+        for ((phase_bin, &y), &w) in self.phase_bins(freq, phase_bins).zip(&self.y).zip(&self.w) {
+            let magnitude_bin = usize::min(
+                ((y - y_min) / (y_max - y_min) * magnitude_bins as f64) as usize,
+                magnitude_bins - 1,
+            );
+            prob[phase_bin * magnitude_bins + magnitude_bin] += w;
+        }
+        let entropy: f64 = prob
+            .chunks(magnitude_bins)
+            .map(|phase_prob| {
+                let phase_total: f64 = phase_prob.iter().sum();
+                phase_prob
+                    .iter()
+                    .filter(|&&p| p > 0.0)
+                    .map(|&p| p * (phase_total / p).ln())
+                    .sum::<f64>()
+            })
+            .sum();
+        1.0 - entropy / (magnitude_bins as f64).ln()
+    }
+
+    /// Box model with the largest chi-squared reduction
+    fn best_box(&self, freq: f64, phase_bins: usize, max_duration: f64) -> BoxFit {
+        if self.y_var <= 0.0 {
+            return BoxFit::default();
+        }
+        let (w_sum, wy_sum, _) = self.binned_sums(freq, phase_bins);
+        let max_width = usize::clamp(
+            (max_duration * phase_bins as f64).round() as usize,
+            1,
+            phase_bins - 1,
+        );
+        let mut best = BoxFit::default();
+        for start in 0..phase_bins {
+            let (mut r, mut s) = (0.0, 0.0);
+            for width in 1..=max_width {
+                let bin = (start + width - 1) % phase_bins;
+                r += w_sum[bin];
+                s += wy_sum[bin];
+                if r <= DEGENERACY_TOLERANCE || r >= 1.0 - DEGENERACY_TOLERANCE {
+                    continue;
+                }
+                // Values are centered, so the out-of-box weighted sum is -s
+                let power = s.powi(2) / (r * (1.0 - r)) / self.y_var;
+                if power > best.power {
+                    best = BoxFit {
+                        power,
+                        depth: s / (r * (1.0 - r)),
+                        duration: width as f64 / phase_bins as f64 * 2.0 * PI / freq,
+                    };
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Indices of local maxima sorted by descending value
+fn peak_indices_reverse_sorted<T: lcf::Float>(values: &[T]) -> Vec<usize> {
+    let mut indices: Vec<_> = (1..values.len().saturating_sub(1))
+        .filter(|&i| values[i] > values[i - 1] && values[i] >= values[i + 1])
+        .collect();
+    indices.sort_unstable_by(|&a, &b| values[b].partial_cmp(&values[a]).unwrap());
+    indices
+}
+
+/// Half width at half maximum of the peak at grid index `i`, and the refined peak frequency
+///
+/// The peak position and height are refined with the parabola through the maximum and its
+/// neighbours, and the half-maximum crossings are linearly interpolated between grid points. If
+/// the power does not fall below the half maximum on one side, the grid edge is used.
+fn peak_half_width(freq: &[f64], power: &[f64], i: usize) -> (f64, f64) {
+    let (mut peak_freq, mut peak_power) = (freq[i], power[i]);
+    if i > 0 && i + 1 < freq.len() {
+        let (x0, x1, x2) = (freq[i - 1], freq[i], freq[i + 1]);
+        let (y0, y1, y2) = (power[i - 1], power[i], power[i + 1]);
+        // Divided differences of the interpolating parabola
+        let d01 = (y1 - y0) / (x1 - x0);
+        let d12 = (y2 - y1) / (x2 - x1);
+        let curvature = (d12 - d01) / (x2 - x0);
+        if curvature < 0.0 {
+            let vertex = 0.5 * (x0 + x1) - 0.5 * d01 / curvature;
+            if vertex > x0 && vertex < x2 {
+                peak_freq = vertex;
+                peak_power = y1 + d01 * (vertex - x1) + curvature * (vertex - x0) * (vertex - x1);
+            }
+        }
+    }
+    let half = 0.5 * peak_power;
+    let crossing = |j: usize, k: usize| {
+        freq[j] + (half - power[j]) * (freq[k] - freq[j]) / (power[k] - power[j])
+    };
+    let left = (0..i)
+        .rev()
+        .find(|&j| power[j] < half)
+        .map_or(freq[0], |j| crossing(j, j + 1));
+    let right = (i + 1..freq.len())
+        .find(|&j| power[j] < half)
+        .map_or(freq[freq.len() - 1], |j| crossing(j, j - 1));
+    (peak_freq, 0.5 * (right - left))
+}
+
+/// Treatment of periodogram peaks coinciding with peaks of the spectral window
+///
+/// `tolerance` is the maximum frequency distance to a window peak in units of the frequency
+/// resolution `2 pi / T`, where `T` is the time series duration.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum AliasPeaks {
+    /// Add a per-peak output which is unity for alias peaks and zero otherwise
+    Flag { tolerance: f64 },
+    /// Skip alias peaks
+    Suppress { tolerance: f64 },
+}
+
+impl AliasPeaks {
+    pub(crate) const DEFAULT_TOLERANCE: f64 = 1.0;
+    /// Minimum spectral window power of a window peak, the window power is unity at zero frequency
+    pub(crate) const WINDOW_POWER_THRESHOLD: f64 = 0.25;
+
+    fn tolerance(&self) -> f64 {
+        match *self {
+            Self::Flag { tolerance } | Self::Suppress { tolerance } => tolerance,
+        }
+    }
+}
+
+/// Frequency grid defined by the range of periods
+///
+/// Periodograms on such grids are always evaluated directly by this crate. The grid is also passed
+/// to `light-curve-feature` periodogram as an arbitrary grid, which is used only if the evaluator
+/// is represented by `light-curve-feature` one, see [PeriodogramEvaluator::to_lcf_unweighted].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum PeriodRangeGrid {
+    /// Frequencies are evenly spaced in logarithm
+    LogFreq {
+        min_period: f64,
+        max_period: f64,
+        size: usize,
+    },
+    /// Periods are evenly spaced
+    LinearPeriod {
+        min_period: f64,
+        max_period: f64,
+        size: usize,
+    },
+}
+
+impl PeriodRangeGrid {
+    /// Angular frequencies in ascending order
+    pub(crate) fn freqs(&self) -> Vec<f64> {
+        match *self {
+            Self::LogFreq {
+                min_period,
+                max_period,
+                size,
+            } => {
+                let min_freq = 2.0 * PI / max_period;
+                let ratio = max_period / min_period;
+                (0..size)
+                    .map(|i| min_freq * ratio.powf(i as f64 / (size - 1) as f64))
+                    .collect()
+            }
+            Self::LinearPeriod {
+                min_period,
+                max_period,
+                size,
+            } => {
+                let step = (max_period - min_period) / (size - 1) as f64;
+                (0..size)
+                    .map(|i| 2.0 * PI / (max_period - i as f64 * step))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Periodogram feature evaluator
+///
+/// It uses the classic Lomb-Scargle periodogram for single-term periodogram of time series without
+/// observation errors, which is computed by `light-curve-feature` unless [PeriodRangeGrid] is
+/// given, and the generalized Lomb-Scargle periodogram with floating mean, error weights and
+/// optional multiple Fourier terms otherwise. Alternatively, phase-binned algorithms
+/// could be used, see [Algorithm]. The frequency grid is defined by the underlying
+/// `light-curve-feature` periodogram unless [PeriodRangeGrid] is given. Optionally, false alarm
+/// probabilities of the peaks are estimated and peaks coinciding with the spectral window peaks
+/// are flagged or suppressed.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct PeriodogramEvaluator<T>
+where
+    T: lcf::Float,
+{
+    periodogram: LcfPeriodogram<T>,
+    fast: bool,
+    algorithm: Algorithm,
+    peaks: usize,
+    features: Vec<lcf::Feature<T>>,
+    fap: Option<FapMethod>,
+    grid: Option<PeriodRangeGrid>,
+    aliases: Option<AliasPeaks>,
+    widths: bool,
+    names: Vec<String>,
+    descriptions: Vec<String>,
+}
+
+impl<T> PeriodogramEvaluator<T>
+where
+    T: lcf::Float,
+{
+    /// `periodogram` must be built with the same `peaks`, `features` and `grid`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        periodogram: LcfPeriodogram<T>,
+        fast: bool,
+        algorithm: Algorithm,
+        peaks: usize,
+        features: Vec<lcf::Feature<T>>,
+        fap: Option<FapMethod>,
+        grid: Option<PeriodRangeGrid>,
+        aliases: Option<AliasPeaks>,
+        widths: bool,
+    ) -> Self {
+        let lcf_info: Vec<_> = periodogram
+            .get_names()
+            .into_iter()
+            .zip(periodogram.get_descriptions())
+            .map(|(name, description)| (name.to_owned(), description.to_owned()))
+            .collect();
+        let (peak_info, features_info) = lcf_info.split_at(2 * peaks);
+        let (names, descriptions) = peak_info
+            .chunks(2)
+            .enumerate()
+            .flat_map(|(i, period_s_to_n)| {
+                let fap_info = fap.map(|method| {
+                    (
+                        format!("period_fap_{i}"),
+                        format!(
+                            "false alarm probability of the {i}-th highest peak of periodogram, estimated with {}",
+                            method.name()
+                        ),
+                    )
+                });
+                let alias_info = matches!(aliases, Some(AliasPeaks::Flag { .. })).then(|| {
+                    (
+                        format!("period_alias_{i}"),
+                        format!(
+                            "unity if the {i}-th highest peak of periodogram coincides with a peak of the spectral window, zero otherwise"
+                        ),
+                    )
+                });
+                let width_info = widths.then(|| {
+                    [
+                        (
+                            format!("period_width_{i}"),
+                            format!(
+                                "half width at half maximum of the {i}-th highest peak of periodogram in angular frequency"
+                            ),
+                        ),
+                        (
+                            format!("period_error_{i}"),
+                            format!(
+                                "uncertainty of the {i}-th highest peak period, derived from the peak half width"
+                            ),
+                        ),
+                    ]
+                });
+                period_s_to_n
+                    .iter()
+                    .cloned()
+                    .chain(fap_info)
+                    .chain(alias_info)
+                    .chain(width_info.into_iter().flatten())
+                    .chain(algorithm.peak_info(i))
+            })
+            .chain(features_info.iter().cloned())
+            .unzip();
+        Self {
+            periodogram,
+            fast,
+            algorithm,
+            peaks,
+            features,
+            fap,
+            grid,
+            aliases,
+            widths,
+            names,
+            descriptions,
+        }
+    }
+
+    /// Underlying `light-curve-feature` periodogram, which ignores observation errors
+    ///
+    /// Returns `None` if it cannot represent this evaluator, i.e. for algorithms other than
+    /// single-term Lomb-Scargle periodogram or false alarm probability, alias and width outputs.
+    pub(crate) fn to_lcf_unweighted(&self) -> Option<lcf::Feature<T>> {
+        (self.algorithm == Algorithm::LombScargle { nterms: 1 } && self.has_lcf_outputs())
+            .then(|| self.periodogram.clone().into())
+    }
+
+    /// Outputs are the same as of the underlying `light-curve-feature` periodogram
+    fn has_lcf_outputs(&self) -> bool {
+        self.fap.is_none() && self.aliases.is_none() && !self.widths
+    }
+
+    pub(crate) fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// False alarm probability estimation method, Baluev (2008) approximation is used by default
+    pub(crate) fn fap_method(&self) -> FapMethod {
+        self.fap.unwrap_or(FapMethod::Baluev)
+    }
+
+    /// Classic Lomb-Scargle periodogram is used for single-term periodogram of time series
+    /// without observation errors
+    ///
+    /// Time series with constant errors use the generalized periodogram with floating mean, so the
+    /// result does not depend on the error value. `has_errors` tells if observation errors are
+    /// given, weights of time series without them are unity.
+    fn is_classic(&self, has_errors: bool) -> bool {
+        self.algorithm == Algorithm::LombScargle { nterms: 1 } && !has_errors
+    }
+
+    /// Classic periodogram is computed by `light-curve-feature`, except for [PeriodRangeGrid]
+    /// which is evaluated directly, because `light-curve-feature` is slow for arbitrary grids
+    fn is_lcf(&self, has_errors: bool) -> bool {
+        self.is_classic(has_errors) && self.grid.is_none()
+    }
+
+    /// Frequency grid for the time series
+    fn freq_grid(&self, ts: &mut lcf::TimeSeries<T>) -> Result<Vec<T>, lcf::EvaluatorError> {
+        match self.grid {
+            Some(grid) => {
+                self.periodogram.check_ts_length(ts)?;
+                Ok(grid.freqs().into_iter().map(float).collect())
+            }
+            // light-curve-feature does not expose the frequency grid, so we take it from the
+            // classic periodogram output
+            None => Ok(self
+                .periodogram
+                .freq_power(ts)
+                .map_err(lcf::EvaluatorError::from)?
+                .0),
+        }
+    }
+
+    /// Frequency grid and periodogram power, see [PeriodogramEvaluator::is_classic] for
+    /// `has_errors`
+    pub(crate) fn freq_power(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<(Vec<T>, Vec<T>), lcf::EvaluatorError> {
+        if self.is_lcf(has_errors) {
+            return self
+                .periodogram
+                .freq_power(ts)
+                .map_err(lcf::EvaluatorError::from);
+        }
+        let freq = self.freq_grid(ts)?;
+        let series = WeightedSeries::new(ts, self.algorithm.nterms());
+        let freq_f64: Vec<_> = freq.iter().map(|&f| f.to_f64().unwrap()).collect();
+        let power = match self.algorithm {
+            Algorithm::LombScargle { .. } => {
+                self.lomb_scargle_power(&series, &freq_f64, !self.is_classic(has_errors))
+            }
+            algorithm => freq_f64
+                .iter()
+                .map(|&freq| algorithm.phase_binned_power(&series, freq))
+                .collect(),
+        };
+        Ok((freq, power.into_iter().map(float).collect()))
+    }
+
+    /// Generalized Lomb-Scargle power, see [PeriodogramEvaluator::harmonic_map] and
+    /// [WeightedSeries::power]
+    fn lomb_scargle_power(
+        &self,
+        series: &WeightedSeries,
+        freq: &[f64],
+        floating_mean: bool,
+    ) -> Vec<f64> {
+        self.harmonic_map(series, freq, |sums| series.power(sums, floating_mean))
+    }
+
+    /// Function of harmonic sums on the frequency grid, FFT is used for zero-based linear grids if
+    /// `fast` is set
+    fn harmonic_map(
+        &self,
+        series: &WeightedSeries,
+        freq: &[f64],
+        f: impl Fn(&HarmonicSums) -> f64,
+    ) -> Vec<f64> {
+        let is_zero_based_linear = freq.len() > 1
+            && freq[0] == 0.0
+            && freq.windows(2).enumerate().all(|(i, pair)| {
+                let expected = (i + 1) as f64 * freq[1];
+                (pair[1] - expected).abs() <= 1e-6 * expected
+            });
+        if self.fast && is_zero_based_linear {
+            series.fft_map(freq[1], freq.len(), f)
+        } else {
+            series.direct_map(freq, f)
+        }
+    }
+
+    /// Spectral window of the time series sampling on the frequency grid
+    fn window_power_f64(&self, ts: &lcf::TimeSeries<T>, freq: &[f64]) -> Vec<f64> {
+        let series = WeightedSeries::from_observations(
+            ts.t.sample.iter().map(|&t| (t, T::zero(), T::one())),
+            1,
+        );
+        self.harmonic_map(&series, freq, WeightedSeries::window)
+    }
+
+    /// Frequency grid and spectral window power of the time series sampling, values of the time
+    /// series are used to get the frequency grid only
+    pub(crate) fn window_power(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+    ) -> Result<(Vec<T>, Vec<T>), lcf::EvaluatorError> {
+        let freq = self.freq_grid(ts)?;
+        let freq_f64: Vec<_> = freq.iter().map(|&f| f.to_f64().unwrap()).collect();
+        let window = self
+            .window_power_f64(ts, &freq_f64)
+            .into_iter()
+            .map(float)
+            .collect();
+        Ok((freq, window))
+    }
+
+    /// Multi-band periodogram is implemented for Lomb-Scargle periodogram without false alarm
+    /// probability outputs
+    pub(crate) fn is_multiband_supported(&self) -> bool {
+        matches!(self.algorithm, Algorithm::LombScargle { .. }) && self.fap.is_none()
+    }
+
+    /// Multi-band generalized Lomb-Scargle periodogram (VanderPlas & Ivezić 2015)
+    ///
+    /// Every band has its own floating mean and Fourier series of the shared frequency, so the
+    /// chi-squared reduction is the sum of the single-band ones. The power is normalized as the
+    /// single-band one, the reference model consists of constant per-band means. `band` contains
+    /// band indices of the observations.
+    pub(crate) fn freq_power_multiband(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        band: &[usize],
+    ) -> Result<(Vec<T>, Vec<T>), lcf::EvaluatorError> {
+        let freq = self.freq_grid(ts)?;
+        let freq_f64: Vec<_> = freq.iter().map(|&f| f.to_f64().unwrap()).collect();
+        let n_bands = band.iter().max().map_or(0, |&b| b + 1);
+        let mut chi2_reduction = vec![0.0; freq.len()];
+        let mut chi2_reference = 0.0;
+        for i_band in 0..n_bands {
+            let mut observations =
+                ts.t.sample
+                    .iter()
+                    .zip(ts.m.sample.iter())
+                    .zip(ts.w.sample.iter())
+                    .zip(band)
+                    .filter(|&(_, &b)| b == i_band)
+                    .map(|(((&t, &m), &w), _)| (t, m, w))
+                    .peekable();
+            if observations.peek().is_none() {
+                continue;
+            }
+            let series = WeightedSeries::from_observations(observations, self.algorithm.nterms());
+            if series.y_var <= 0.0 {
+                continue;
+            }
+            let chi2 = series.w_sum * series.y_var;
+            chi2_reference += chi2;
+            for (reduction, power) in chi2_reduction
+                .iter_mut()
+                .zip(self.lomb_scargle_power(&series, &freq_f64, true))
+            {
+                *reduction += chi2 * power / series.scale;
+            }
+        }
+        let scale = 0.5 * (ts.lenu() as f64 - 1.0);
+        let power = chi2_reduction
+            .into_iter()
+            .map(|reduction| {
+                if chi2_reference > 0.0 {
+                    float(scale * reduction / chi2_reference)
+                } else {
+                    T::zero()
+                }
+            })
+            .collect();
+        Ok((freq, power))
+    }
+
+    pub(crate) fn power(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        if self.is_lcf(has_errors) {
+            return self
+                .periodogram
+                .power(ts)
+                .map_err(lcf::EvaluatorError::from);
+        }
+        Ok(self.freq_power(ts, has_errors)?.1)
+    }
+
+    /// Period of the highest peak of the periodogram
+    pub(crate) fn top_period(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<f64, lcf::EvaluatorError> {
+        self.periodogram.check_ts_length(ts)?;
+        let (freq, power) = self.freq_power(ts, has_errors)?;
+        let i = *peak_indices_reverse_sorted(&power)
+            .first()
+            .ok_or(lcf::EvaluatorError::FlatTimeSeries)?;
+        Ok(2.0 * PI / freq[i].to_f64().unwrap())
+    }
+
+    /// False alarm probabilities of given power values, see [PeriodogramEvaluator::fap_method]
+    pub(crate) fn false_alarm_probability(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+        powers: &[f64],
+    ) -> Result<Vec<f64>, lcf::EvaluatorError> {
+        self.periodogram.check_ts_length(ts)?;
+        let (freq, _power) = self.freq_power(ts, has_errors)?;
+        self.fap_impl(self.fap_method(), ts, has_errors, &freq, powers)
+    }
+
+    fn fap_impl(
+        &self,
+        method: FapMethod,
+        ts: &lcf::TimeSeries<T>,
+        has_errors: bool,
+        freq: &[T],
+        powers: &[f64],
+    ) -> Result<Vec<f64>, lcf::EvaluatorError> {
+        match method {
+            FapMethod::Baluev => {
+                let series = WeightedSeries::new(ts, self.algorithm.nterms());
+                let max_freq = freq
+                    .iter()
+                    .fold(0.0, |acc, &f| f64::max(acc, f.to_f64().unwrap()));
+                Ok(powers
+                    .iter()
+                    .map(|&power| baluev_fap(&series, max_freq, power))
+                    .collect())
+            }
+            FapMethod::Bootstrap { n, random_seed } => {
+                let max_powers = self.bootstrap_max_powers(ts, has_errors, n, random_seed)?;
+                Ok(powers
+                    .iter()
+                    .map(|&power| {
+                        let count = max_powers.iter().filter(|&&max| max >= power).count();
+                        count as f64 / n as f64
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Maximum periodogram power for light curves with magnitudes and weights resampled with
+    /// replacement, time moments are kept
+    fn bootstrap_max_powers(
+        &self,
+        ts: &lcf::TimeSeries<T>,
+        has_errors: bool,
+        n: usize,
+        random_seed: Option<u64>,
+    ) -> Result<Vec<f64>, lcf::EvaluatorError> {
+        let mut rng = match random_seed {
+            Some(seed) => Xoshiro256PlusPlus::seed_from_u64(seed),
+            None => Xoshiro256PlusPlus::from_rng(&mut rand::rng()),
+        };
+        // Each resampling has its own generator, so results do not depend on thread scheduling
+        let seeds: Vec<u64> = (0..n).map(|_| rng.random()).collect();
+        let length = ts.lenu();
+        let t = ts.t.sample.view();
+        let m = ts.m.sample.view();
+        let w = ts.w.sample.view();
+        seeds
+            .into_par_iter()
+            .map(|seed| {
+                let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+                let idx: Vec<_> = (0..length).map(|_| rng.random_range(0..length)).collect();
+                let m: Array1<_> = idx.iter().map(|&i| m[i]).collect();
+                let mut resampled = if has_errors {
+                    let w: Array1<_> = idx.iter().map(|&i| w[i]).collect();
+                    lcf::TimeSeries::new(t, m, w)
+                } else {
+                    lcf::TimeSeries::new_without_weight(t, m)
+                };
+                let power = self.power(&mut resampled, has_errors)?;
+                Ok(power.into_iter().fold(T::zero(), T::max).to_f64().unwrap())
+            })
+            .collect()
+    }
+
+    pub(crate) fn eval(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        if self.has_lcf_outputs() && self.is_lcf(has_errors) {
+            return self.periodogram.eval(ts);
+        }
+        self.periodogram.check_ts_length(ts)?;
+        let (freq, power) = self.freq_power(ts, has_errors)?;
+        self.eval_spectrum(ts, has_errors, &freq, &power)
+    }
+
+    /// Feature values of multi-band time series, see [PeriodogramEvaluator::freq_power_multiband]
+    pub(crate) fn eval_multiband(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        band: &[usize],
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        self.periodogram.check_ts_length(ts)?;
+        let (freq, power) = self.freq_power_multiband(ts, band)?;
+        // Multi-band periodogram is always error-weighted
+        self.eval_spectrum(ts, true, &freq, &power)
+    }
+
+    /// Predicate on frequency grid indices which is true for the frequencies close to the spectral
+    /// window peaks, see [AliasPeaks]. It is always false if aliases are not checked
+    fn alias_detector(&self, ts: &lcf::TimeSeries<T>, freq: &[f64]) -> impl Fn(usize) -> bool {
+        let (window_freqs, max_distance) = match self.aliases {
+            Some(aliases) => {
+                let window = self.window_power_f64(ts, freq);
+                // Zero frequency is the main window peak, which could be out of the grid
+                let window_freqs: Vec<_> = std::iter::once(0.0)
+                    .chain(
+                        peak_indices_reverse_sorted(&window)
+                            .into_iter()
+                            .filter(|&i| window[i] >= AliasPeaks::WINDOW_POWER_THRESHOLD)
+                            .map(|i| freq[i]),
+                    )
+                    .collect();
+                let (t_min, t_max) = ts.t.sample.iter().fold(
+                    (f64::INFINITY, f64::NEG_INFINITY),
+                    |(min, max), &t| {
+                        let t = t.to_f64().unwrap();
+                        (min.min(t), max.max(t))
+                    },
+                );
+                let resolution = 2.0 * PI / (t_max - t_min);
+                (window_freqs, aliases.tolerance() * resolution)
+            }
+            None => (vec![], 0.0),
+        };
+        move |i| {
+            window_freqs
+                .iter()
+                .any(|&window_freq| (freq[i] - window_freq).abs() <= max_distance)
+        }
+    }
+
+    /// Peak and underlying feature values from the periodogram
+    fn eval_spectrum(
+        &self,
+        ts: &lcf::TimeSeries<T>,
+        has_errors: bool,
+        freq: &[T],
+        power: &[T],
+    ) -> Result<Vec<T>, lcf::EvaluatorError> {
+        let mut values = Vec::with_capacity(self.size_hint());
+
+        // Signal-to-noise ratio is computed by the data sample exactly as `light-curve-feature` does
+        let mut power_ds: lcf::DataSample<_> = ArrayView1::from(power).into();
+        let freq_f64: Vec<_> = freq.iter().map(|&f| f.to_f64().unwrap()).collect();
+        let power_f64: Vec<_> = power.iter().map(|&p| p.to_f64().unwrap()).collect();
+        let is_alias = self.alias_detector(ts, &freq_f64);
+        let peak_indices: Vec<_> = peak_indices_reverse_sorted(power)
+            .into_iter()
+            .filter(|&i| !matches!(self.aliases, Some(AliasPeaks::Suppress { .. })) || !is_alias(i))
+            .take(self.peaks)
+            .collect();
+        let faps = match self.fap {
+            Some(method) => {
+                let peak_powers: Vec<_> = peak_indices
+                    .iter()
+                    .map(|&i| power[i].to_f64().unwrap())
+                    .collect();
+                Some(self.fap_impl(method, ts, has_errors, freq, &peak_powers)?)
+            }
+            None => None,
+        };
+        let box_fits = match self.algorithm {
+            Algorithm::BoxLeastSquares {
+                phase_bins,
+                max_duration,
+            } => {
+                let series = WeightedSeries::new(ts, 1);
+                let box_fits: Vec<_> = peak_indices
+                    .iter()
+                    .map(|&i| series.best_box(freq[i].to_f64().unwrap(), phase_bins, max_duration))
+                    .collect();
+                Some(box_fits)
+            }
+            _ => None,
+        };
+        for i_peak in 0..self.peaks {
+            // Missing peaks have zero period, signal-to-noise ratio and algorithm-specific
+            // outputs, and unity false alarm probability
+            match peak_indices.get(i_peak) {
+                Some(&i) => {
+                    let period = float::<T>(2.0 * PI) / freq[i];
+                    values.extend([period, power_ds.signal_to_noise(power[i])]);
+                }
+                None => values.extend([T::zero(), T::zero()]),
+            }
+            if let Some(faps) = &faps {
+                values.push(faps.get(i_peak).map_or(T::one(), |&fap| float(fap)));
+            }
+            if let Some(AliasPeaks::Flag { .. }) = self.aliases {
+                let alias = peak_indices.get(i_peak).is_some_and(|&i| is_alias(i));
+                values.push(if alias { T::one() } else { T::zero() });
+            }
+            if self.widths {
+                match peak_indices.get(i_peak) {
+                    Some(&i) => {
+                        let (peak_freq, half_width) = peak_half_width(&freq_f64, &power_f64, i);
+                        let period_error = 2.0 * PI * half_width / peak_freq.powi(2);
+                        values.extend([float::<T>(half_width), float(period_error)]);
+                    }
+                    None => values.extend([T::zero(), T::zero()]),
+                }
+            }
+            if let Some(box_fits) = &box_fits {
+                let box_fit = box_fits.get(i_peak).copied().unwrap_or_default();
+                values.extend([float::<T>(box_fit.depth), float(box_fit.duration)]);
+            }
+        }
+
+        let freq: lcf::DataSample<_> = ArrayView1::from(freq).into();
+        let mut power_ts = lcf::TimeSeries::new_without_weight(freq, power_ds);
+        for feature in &self.features {
+            values.extend(feature.eval(&mut power_ts)?);
+        }
+        Ok(values)
+    }
+
+    pub(crate) fn eval_or_fill(
+        &self,
+        ts: &mut lcf::TimeSeries<T>,
+        has_errors: bool,
+        fill_value: T,
+    ) -> Vec<T> {
+        if self.has_lcf_outputs() && self.is_lcf(has_errors) {
+            return self.periodogram.eval_or_fill(ts, fill_value);
+        }
+        match self.eval(ts, has_errors) {
+            Ok(values) => values,
+            Err(_) => vec![fill_value; self.size_hint()],
+        }
+    }
+
+    pub(crate) fn output_transformers(&self) -> Vec<OutputTransformer> {
+        // Peak outputs are not transformed
+        std::iter::repeat_n(Some(CustomTransformer::identity()), self.peaks_size())
+            .chain(
+                self.features
+                    .iter()
+                    .flat_map(|feature| Evaluator::Lcf(feature.clone()).output_transformers()),
+            )
+            .collect()
+    }
+
+    /// Number of outputs per peak: period, signal-to-noise ratio, optional false alarm
+    /// probability, alias flag and width outputs, and algorithm-specific outputs
+    pub(crate) fn peak_size(&self) -> usize {
+        2 + usize::from(self.fap.is_some())
+            + usize::from(matches!(self.aliases, Some(AliasPeaks::Flag { .. })))
+            + 2 * usize::from(self.widths)
+            + self.algorithm.peak_info(0).len()
+    }
+
+    /// Number of outputs describing the peaks, the rest are outputs of the underlying features
+    pub(crate) fn peaks_size(&self) -> usize {
+        self.peak_size() * self.peaks
+    }
+
+    pub(crate) fn get_names(&self) -> Vec<&str> {
+        self.names.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn get_descriptions(&self) -> Vec<&str> {
+        self.descriptions.iter().map(String::as_str).collect()
+    }
+
+    pub(crate) fn size_hint(&self) -> usize {
+        self.names.len()
+    }
+
+    pub(crate) fn is_t_required(&self) -> bool {
+        self.periodogram.is_t_required()
+    }
+
+    pub(crate) fn is_m_required(&self) -> bool {
+        self.periodogram.is_m_required()
+    }
+
+    /// Observation errors are optional, but we need them if given
+    pub(crate) fn is_w_required(&self) -> bool {
+        true
+    }
+
+    pub(crate) fn is_sorting_required(&self) -> bool {
+        self.periodogram.is_sorting_required()
+    }
+}
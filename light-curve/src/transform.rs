@@ -1,3 +1,4 @@
+//! Transformers of feature values
 use crate::errors::{Exception, Res};
 
 use enum_iterator::Sequence;
@@ -5,10 +6,14 @@ use light_curve_feature::transformers::{
     Transformer, arcsinh::ArcsinhTransformer, clipped_lg::ClippedLgTransformer,
     identity::IdentityTransformer, lg::LgTransformer, ln1p::Ln1pTransformer, sqrt::SqrtTransformer,
 };
+use light_curve_feature::{self as lcf, prelude::*};
+use numpy::prelude::*;
+use numpy::{AllowTypeChange, PyArray1, PyArrayLike1, PyArrayLike2};
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyString};
+use pyo3::types::{PyBool, PyBytes, PyString};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Sequence)]
+#[derive(Clone, Copy, Debug, PartialEq, Sequence, Serialize, Deserialize)]
 pub(crate) enum StockTransformer {
     Arcsinh,
     ClippedLg,
@@ -37,6 +42,17 @@ impl StockTransformer {
             Self::Sqrt => SqrtTransformer::doc(),
         }
     }
+
+    pub(crate) fn lcf_transformer<T: lcf::Float>(self) -> Transformer<T> {
+        match self {
+            Self::Arcsinh => ArcsinhTransformer::default().into(),
+            Self::ClippedLg => ClippedLgTransformer::default().into(),
+            Self::Identity => IdentityTransformer::default().into(),
+            Self::Lg => LgTransformer::default().into(),
+            Self::Ln1p => Ln1pTransformer::default().into(),
+            Self::Sqrt => SqrtTransformer::default().into(),
+        }
+    }
 }
 
 impl TryFrom<&str> for StockTransformer {
@@ -74,45 +90,679 @@ impl From<StockTransformer> for &'static str {
 
 impl From<StockTransformer> for (Transformer<f32>, Transformer<f64>) {
     fn from(val: StockTransformer) -> Self {
-        match val {
-            StockTransformer::Arcsinh => (
-                ArcsinhTransformer::default().into(),
-                ArcsinhTransformer::default().into(),
-            ),
-            StockTransformer::ClippedLg => (
-                ClippedLgTransformer::default().into(),
-                ClippedLgTransformer::default().into(),
-            ),
-            StockTransformer::Identity => (
-                IdentityTransformer::default().into(),
-                IdentityTransformer::default().into(),
-            ),
-            StockTransformer::Lg => (
-                LgTransformer::default().into(),
-                LgTransformer::default().into(),
-            ),
-            StockTransformer::Ln1p => (
-                Ln1pTransformer::default().into(),
-                Ln1pTransformer::default().into(),
-            ),
-            StockTransformer::Sqrt => (
-                SqrtTransformer::default().into(),
-                SqrtTransformer::default().into(),
-            ),
-        }
-    }
-}
-
-pub(crate) fn parse_transform(
-    option: Option<Bound<PyAny>>,
-    default: StockTransformer,
-) -> Res<Option<StockTransformer>> {
+        (val.lcf_transformer(), val.lcf_transformer())
+    }
+}
+
+fn float<T: lcf::Float>(x: f64) -> T {
+    num_traits::cast(x).unwrap()
+}
+
+/// Per-value mean and standard deviation of the standardization transformer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct StandardScale {
+    mean: Vec<f64>,
+    std: Vec<f64>,
+}
+
+impl StandardScale {
+    fn new(mean: Vec<f64>, std: Vec<f64>) -> Res<Self> {
+        if mean.len() != std.len() {
+            return Err(Exception::ValueError(
+                "mean and std must have the same size".to_string(),
+            ));
+        }
+        if std.iter().any(|&x| !(x.is_finite() && x > 0.0)) {
+            return Err(Exception::ValueError(
+                "std must be positive and finite".to_string(),
+            ));
+        }
+        if mean.iter().any(|x| !x.is_finite()) {
+            return Err(Exception::ValueError("mean must be finite".to_string()));
+        }
+        Ok(Self { mean, std })
+    }
+
+    /// Fit mean and standard deviation of every column ignoring non-finite values
+    ///
+    /// Zero standard deviation is replaced with unity, so constant values are only shifted
+    fn fit(rows: &[Vec<f64>]) -> Res<Self> {
+        let size = rows.first().map_or(0, Vec::len);
+        let (mean, std) = (0..size)
+            .map(|i| {
+                let column: Vec<_> = rows
+                    .iter()
+                    .map(|row| row[i])
+                    .filter(|x| x.is_finite())
+                    .collect();
+                if column.is_empty() {
+                    return Err(Exception::ValueError(format!(
+                        "cannot fit standardization: column {i} has no finite values"
+                    )));
+                }
+                let n = column.len() as f64;
+                let mean = column.iter().sum::<f64>() / n;
+                let std = f64::sqrt(column.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n);
+                Ok((mean, if std > 0.0 { std } else { 1.0 }))
+            })
+            .collect::<Res<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        Ok(Self { mean, std })
+    }
+}
+
+/// Transformer of feature values which is not limited by `light-curve-feature` transformers
+///
+/// Parameters are stored as f64 and converted to the feature float type on evaluation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum CustomTransformer {
+    Stock(StockTransformer),
+    Affine {
+        a: f64,
+        b: f64,
+    },
+    ClippedLog {
+        floor: f64,
+        base: f64,
+    },
+    Power {
+        exponent: f64,
+    },
+    /// `None` means that the transformer is not fitted yet
+    Standardize(Option<StandardScale>),
+    Chain(Vec<CustomTransformer>),
+    /// Transformers applied to consecutive chunks of values of given sizes
+    Chunks(Vec<(CustomTransformer, usize)>),
+}
+
+impl CustomTransformer {
+    /// Chain transformers flattening nested chains
+    pub(crate) fn chain(transformers: impl IntoIterator<Item = Self>) -> Self {
+        Self::Chain(
+            transformers
+                .into_iter()
+                .flat_map(|transformer| match transformer {
+                    Self::Chain(chain) => chain,
+                    transformer => vec![transformer],
+                })
+                .collect(),
+        )
+    }
+
+    /// Short name used as a prefix of output names
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Stock(stock) => (*stock).into(),
+            Self::Affine { .. } => "affine",
+            Self::ClippedLog { .. } => "clipped_log",
+            Self::Power { .. } => "power",
+            Self::Standardize(_) => "standardized",
+            Self::Chain(_) => "chain",
+            Self::Chunks(_) => "chunks",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Self::Stock(stock) => {
+                let name: &str = (*stock).into();
+                format!("{name} transformer")
+            }
+            Self::Affine { a, b } => format!("affine transformation {a} * x + {b}"),
+            Self::ClippedLog { floor, base } => {
+                format!("logarithm with base {base} of the value clipped from below at {floor}")
+            }
+            Self::Power { exponent } => format!("power {exponent}"),
+            Self::Standardize(_) => "standardization".to_string(),
+            Self::Chain(chain) => chain
+                .iter()
+                .map(Self::description)
+                .collect::<Vec<_>>()
+                .join(", then "),
+            Self::Chunks(chunks) => chunks
+                .iter()
+                .map(|(transformer, size)| {
+                    format!("{} for {size} values", transformer.description())
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// Check if the transformer can be applied to a given number of values
+    pub(crate) fn check_size(&self, size: usize) -> Res<()> {
+        match self {
+            Self::Standardize(None) => Err(Exception::ValueError(
+                "standardization transformer must be fitted before use, see Transformer.fit()"
+                    .to_string(),
+            )),
+            Self::Standardize(Some(scale)) if scale.mean.len() != size => {
+                Err(Exception::ValueError(format!(
+                    "standardization transformer is fitted for {} values, but {size} values are given",
+                    scale.mean.len()
+                )))
+            }
+            Self::Chain(chain) => chain
+                .iter()
+                .try_for_each(|transformer| transformer.check_size(size)),
+            Self::Chunks(chunks) => {
+                let total: usize = chunks.iter().map(|(_, chunk_size)| chunk_size).sum();
+                if total != size {
+                    return Err(Exception::ValueError(format!(
+                        "transformer is defined for {total} values, but {size} values are given"
+                    )));
+                }
+                chunks
+                    .iter()
+                    .try_for_each(|(transformer, chunk_size)| transformer.check_size(*chunk_size))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply function to consecutive chunks of values
+    fn map_chunks<V, F>(chunks: &[(Self, usize)], values: Vec<V>, f: F) -> Vec<V>
+    where
+        F: Fn(&Self, Vec<V>) -> Vec<V>,
+    {
+        let mut values = values.into_iter();
+        chunks
+            .iter()
+            .flat_map(|(transformer, size)| f(transformer, values.by_ref().take(*size).collect()))
+            .collect()
+    }
+
+    /// Identity transformer keeps names and descriptions unchanged
+    pub(crate) fn names(&self, names: Vec<String>) -> Vec<String> {
+        match self {
+            Self::Stock(StockTransformer::Identity) => names,
+            Self::Chain(chain) => chain
+                .iter()
+                .fold(names, |names, transformer| transformer.names(names)),
+            Self::Chunks(chunks) => Self::map_chunks(chunks, names, Self::names),
+            _ => names
+                .into_iter()
+                .map(|name| format!("{}_{name}", self.name()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn descriptions(&self, descriptions: Vec<String>) -> Vec<String> {
+        match self {
+            Self::Stock(StockTransformer::Identity) => descriptions,
+            Self::Chunks(chunks) => Self::map_chunks(chunks, descriptions, Self::descriptions),
+            _ => {
+                let transformation = self.description();
+                descriptions
+                    .into_iter()
+                    .map(|description| format!("{description}, transformed by {transformation}"))
+                    .collect()
+            }
+        }
+    }
+
+    /// Apply transformation, [CustomTransformer::check_size] must be called before
+    pub(crate) fn transform<T: lcf::Float>(&self, values: Vec<T>) -> Vec<T> {
+        match self {
+            Self::Stock(stock) => stock.lcf_transformer::<T>().transform(values),
+            Self::Affine { a, b } => {
+                let (a, b) = (float::<T>(*a), float::<T>(*b));
+                values.into_iter().map(|x| a * x + b).collect()
+            }
+            Self::ClippedLog { floor, base } => {
+                let floor = float::<T>(*floor);
+                let ln_base = float::<T>(base.ln());
+                values
+                    .into_iter()
+                    // max() would replace NaN with floor, so we keep NaN explicitly
+                    .map(|x| {
+                        if x.is_nan() {
+                            x
+                        } else {
+                            x.max(floor).ln() / ln_base
+                        }
+                    })
+                    .collect()
+            }
+            Self::Power { exponent } => {
+                let exponent = float::<T>(*exponent);
+                values.into_iter().map(|x| x.powf(exponent)).collect()
+            }
+            Self::Standardize(Some(scale)) => values
+                .into_iter()
+                .zip(scale.mean.iter().zip(scale.std.iter()))
+                .map(|(x, (&mean, &std))| (x - float(mean)) / float(std))
+                .collect(),
+            // Unfitted transformer cannot be applied, check_size() gives a proper error
+            Self::Standardize(None) => vec![T::nan(); values.len()],
+            Self::Chain(chain) => chain
+                .iter()
+                .fold(values, |values, transformer| transformer.transform(values)),
+            Self::Chunks(chunks) => Self::map_chunks(chunks, values, |transformer, values| {
+                transformer.transform(values)
+            }),
+        }
+    }
+
+    /// Fit standardization stages transforming the rows in place
+    fn fit_transform(&self, rows: &mut [Vec<f64>]) -> Res<Self> {
+        let fitted = match self {
+            Self::Chain(chain) => {
+                // Every stage transforms the rows for the following ones
+                return Ok(Self::Chain(
+                    chain
+                        .iter()
+                        .map(|transformer| transformer.fit_transform(rows))
+                        .collect::<Res<_>>()?,
+                ));
+            }
+            Self::Chunks(chunks) => {
+                let total: usize = chunks.iter().map(|(_, size)| size).sum();
+                if rows.iter().any(|row| row.len() != total) {
+                    return Err(Exception::ValueError(format!(
+                        "transformer is defined for {total} values, but rows of other size are given"
+                    )));
+                }
+                let mut offset = 0;
+                let chunks = chunks
+                    .iter()
+                    .map(|(transformer, size)| {
+                        let range = offset..offset + size;
+                        offset += size;
+                        let mut chunk_rows: Vec<_> =
+                            rows.iter().map(|row| row[range.clone()].to_vec()).collect();
+                        let fitted = transformer.fit_transform(&mut chunk_rows)?;
+                        for (row, chunk_row) in rows.iter_mut().zip(chunk_rows) {
+                            row[range.clone()].copy_from_slice(&chunk_row);
+                        }
+                        Ok((fitted, *size))
+                    })
+                    .collect::<Res<_>>()?;
+                return Ok(Self::Chunks(chunks));
+            }
+            Self::Standardize(_) => Self::Standardize(Some(StandardScale::fit(rows)?)),
+            transformer => transformer.clone(),
+        };
+        for row in rows.iter_mut() {
+            *row = fitted.transform(std::mem::take(row));
+        }
+        Ok(fitted)
+    }
+
+    pub(crate) fn identity() -> Self {
+        Self::Stock(StockTransformer::Identity)
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        match self {
+            Self::Stock(StockTransformer::Identity) => true,
+            Self::Chain(chain) => chain.iter().all(Self::is_identity),
+            Self::Chunks(chunks) => chunks
+                .iter()
+                .all(|(transformer, _)| transformer.is_identity()),
+            _ => false,
+        }
+    }
+
+    /// Compose with other transformer applied after this one, identities are dropped
+    pub(crate) fn then(self, other: Self) -> Self {
+        let Self::Chain(mut chain) = Self::chain([self, other]) else {
+            unreachable!("chain() always returns Chain variant")
+        };
+        chain.retain(|transformer| !transformer.is_identity());
+        match chain.len() {
+            0 => Self::identity(),
+            1 => chain.pop().unwrap(),
+            _ => Self::Chain(chain),
+        }
+    }
+
+    /// Split the transformer into transformers of individual values,
+    /// [CustomTransformer::check_size] must be called before
+    pub(crate) fn per_value(&self, size: usize) -> Vec<Self> {
+        match self {
+            Self::Standardize(Some(scale)) => scale
+                .mean
+                .iter()
+                .zip(scale.std.iter())
+                .map(|(&mean, &std)| {
+                    Self::Standardize(Some(StandardScale {
+                        mean: vec![mean],
+                        std: vec![std],
+                    }))
+                })
+                .collect(),
+            Self::Chain(chain) => chain
+                .iter()
+                .fold(vec![Self::identity(); size], |acc, stage| {
+                    acc.into_iter()
+                        .zip(stage.per_value(size))
+                        .map(|(acc, stage)| acc.then(stage))
+                        .collect()
+                }),
+            Self::Chunks(chunks) => chunks
+                .iter()
+                .flat_map(|(transformer, size)| transformer.per_value(*size))
+                .collect(),
+            transformer => vec![transformer.clone(); size],
+        }
+    }
+
+    /// Inverse transformation of a single value, `None` means that the value cannot be restored
+    ///
+    /// The transformer must be a single-value one, see [CustomTransformer::per_value]. `T` is the
+    /// float type the value was transformed with, clipping thresholds depend on it.
+    pub(crate) fn inverse_value<T: lcf::Float>(&self, y: f64) -> Option<f64> {
+        if y.is_nan() {
+            return Some(y);
+        }
+        match self {
+            Self::Stock(stock) => match stock {
+                StockTransformer::Arcsinh => Some(y.sinh()),
+                StockTransformer::ClippedLg if float::<T>(y) <= T::min_positive_value().log10() => {
+                    None
+                }
+                StockTransformer::ClippedLg | StockTransformer::Lg => Some(10f64.powf(y)),
+                StockTransformer::Identity => Some(y),
+                StockTransformer::Ln1p => Some(y.exp_m1()),
+                StockTransformer::Sqrt => (y >= 0.0).then(|| y * y),
+            },
+            Self::Affine { a, b } => (*a != 0.0).then(|| (y - b) / a),
+            // Base could be less than unity, so we compare logarithms of the original values
+            Self::ClippedLog { floor, base } => (y * base.ln() > floor.ln()).then(|| base.powf(y)),
+            // Sign cannot be restored for even integer exponents, including zero
+            Self::Power { exponent } if exponent.rem_euclid(2.0) == 0.0 => None,
+            // Odd integer powers keep the sign, while negative values have no real non-integer
+            // powers
+            Self::Power { exponent } if exponent.fract() == 0.0 => {
+                Some(y.signum() * y.abs().powf(exponent.recip()))
+            }
+            Self::Power { exponent } => (y >= 0.0).then(|| y.powf(exponent.recip())),
+            Self::Standardize(Some(scale)) => Some(y * scale.std[0] + scale.mean[0]),
+            Self::Standardize(None) | Self::Chunks(_) => None,
+            Self::Chain(chain) => chain
+                .iter()
+                .rev()
+                .try_fold(y, |y, transformer| transformer.inverse_value::<T>(y)),
+        }
+    }
+}
+
+/// Feature value transformer
+///
+/// Construct instances of this class using stand-alone functions of this submodule and pass them
+/// as `transform` argument of feature evaluators. Features with such transformers cannot be
+/// underlying features of `Bins` and `Periodogram`, pass the transformer as `transform` argument
+/// of the meta-feature instead. The constructor of this class always returns identity transformer
+/// (see `transform.stock('identity')`).
+#[pyclass(name = "Transformer", module = "light_curve.light_curve_ext.transform")]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PyTransformer(pub(crate) CustomTransformer);
+
+#[pymethods]
+impl PyTransformer {
+    #[new]
+    fn __new__() -> Self {
+        Self(CustomTransformer::Stock(StockTransformer::Identity))
+    }
+
+    /// Apply transformer to a 1-D array of feature values
+    fn __call__<'py>(
+        &self,
+        py: Python<'py>,
+        values: PyArrayLike1<'py, f64, AllowTypeChange>,
+    ) -> Res<Bound<'py, PyArray1<f64>>> {
+        self.0.check_size(values.len())?;
+        let values = values.as_array().to_vec();
+        Ok(PyArray1::from_vec(py, self.0.transform(values)))
+    }
+
+    /// Fit standardization steps of the transformer
+    ///
+    /// Parameters
+    /// ----------
+    /// values : 2-D array of float
+    ///     Feature values, one row per light curve, as returned by
+    ///     `feature.many()`. Non-finite values are ignored.
+    ///
+    /// Returns
+    /// -------
+    /// Transformer
+    ///     New fitted transformer, the original one is not modified
+    fn fit(&self, values: PyArrayLike2<f64, AllowTypeChange>) -> Res<Self> {
+        let mut rows: Vec<Vec<f64>> = values
+            .as_array()
+            .rows()
+            .into_iter()
+            .map(|row| row.to_vec())
+            .collect();
+        Ok(Self(self.0.fit_transform(&mut rows)?))
+    }
+
+    /// Chain this transformer with other one, which is applied after this one
+    fn then(&self, other: &Self) -> Self {
+        Self(CustomTransformer::chain([self.0.clone(), other.0.clone()]))
+    }
+
+    /// Serialize transformer to JSON string
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Transformer({})", self.0.description())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+
+    /// Used by pickle.load / pickle.loads
+    fn __setstate__(&mut self, state: Bound<PyBytes>) -> Res<()> {
+        *self = serde_pickle::from_slice(state.as_bytes(), serde_pickle::DeOptions::new())
+            .map_err(|err| {
+                Exception::UnpicklingError(format!(
+                    r#"Error happened on the Rust side when deserializing Transformer: "{err}""#
+                ))
+            })?;
+        Ok(())
+    }
+
+    /// Used by pickle.dump / pickle.dumps
+    fn __getstate__<'py>(&self, py: Python<'py>) -> Res<Bound<'py, PyBytes>> {
+        let vec_bytes =
+            serde_pickle::to_vec(&self, serde_pickle::SerOptions::new()).map_err(|err| {
+                Exception::PicklingError(format!(
+                    r#"Error happened on the Rust side when serializing Transformer: "{err}""#
+                ))
+            })?;
+        Ok(PyBytes::new(py, &vec_bytes))
+    }
+
+    /// Used by copy.copy
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    /// Used by copy.deepcopy
+    fn __deepcopy__(&self, _memo: Bound<PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Stock transformer
+///
+/// Parameters
+/// ----------
+/// name : str
+///     One of 'arcsinh', 'clipped_lg', 'identity', 'lg', 'ln1p' or 'sqrt'
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+fn stock(name: &str) -> Res<PyTransformer> {
+    Ok(PyTransformer(CustomTransformer::Stock(name.try_into()?)))
+}
+
+/// Affine transformer, `a * x + b`
+///
+/// Parameters
+/// ----------
+/// a : float
+/// b : float, optional
+///     Default is 0
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+#[pyo3(signature = (a, b = 0.0))]
+fn affine(a: f64, b: f64) -> Res<PyTransformer> {
+    if !(a.is_finite() && b.is_finite()) {
+        return Err(Exception::ValueError("a and b must be finite".to_string()));
+    }
+    Ok(PyTransformer(CustomTransformer::Affine { a, b }))
+}
+
+/// Logarithm of the value clipped from below, `log_base(max(x, floor))`
+///
+/// NaN values are kept as is.
+///
+/// Parameters
+/// ----------
+/// floor : positive float
+///     Minimum value to apply logarithm to
+/// base : positive float, optional
+///     Logarithm base, default is 10
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+#[pyo3(signature = (floor, *, base = 10.0))]
+fn clipped_log(floor: f64, base: f64) -> Res<PyTransformer> {
+    if !(floor.is_finite() && floor > 0.0) {
+        return Err(Exception::ValueError(
+            "floor must be positive and finite".to_string(),
+        ));
+    }
+    if !(base.is_finite() && base > 0.0 && base != 1.0) {
+        return Err(Exception::ValueError(
+            "base must be positive, finite and not equal to unity".to_string(),
+        ));
+    }
+    Ok(PyTransformer(CustomTransformer::ClippedLog { floor, base }))
+}
+
+/// Power transformer, `x ** exponent`
+///
+/// Parameters
+/// ----------
+/// exponent : float
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+fn power(exponent: f64) -> Res<PyTransformer> {
+    if !exponent.is_finite() {
+        return Err(Exception::ValueError("exponent must be finite".to_string()));
+    }
+    Ok(PyTransformer(CustomTransformer::Power { exponent }))
+}
+
+/// Standardization transformer, `(x - mean) / std`
+///
+/// Mean and standard deviation are specified per feature value. If they are
+/// not given, the transformer must be fitted with `Transformer.fit()` before
+/// use.
+///
+/// Parameters
+/// ----------
+/// mean : list of float or None, optional
+/// std : list of positive float or None, optional
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+#[pyo3(signature = (mean = None, std = None))]
+fn standardize(mean: Option<Vec<f64>>, std: Option<Vec<f64>>) -> Res<PyTransformer> {
+    let scale = match (mean, std) {
+        (Some(mean), Some(std)) => Some(StandardScale::new(mean, std)?),
+        (None, None) => None,
+        _ => {
+            return Err(Exception::ValueError(
+                "mean and std must be both given or both None".to_string(),
+            ));
+        }
+    };
+    Ok(PyTransformer(CustomTransformer::Standardize(scale)))
+}
+
+/// Chain of transformers applied one after another
+///
+/// Parameters
+/// ----------
+/// transformers : list of Transformer
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+fn chain(transformers: Vec<PyTransformer>) -> PyTransformer {
+    PyTransformer(CustomTransformer::chain(
+        transformers.into_iter().map(|transformer| transformer.0),
+    ))
+}
+
+/// Deserialize transformer from JSON string
+///
+/// Parameters
+/// ----------
+/// s : str
+///     JSON string produced by `Transformer.to_json()`
+///
+/// Returns
+/// -------
+/// Transformer
+#[pyfunction]
+fn from_json(s: &str) -> Res<PyTransformer> {
+    Ok(PyTransformer(serde_json::from_str(s).map_err(|err| {
+        Exception::ValueError(format!("Cannot deserialize transformer from JSON: {err}"))
+    })?))
+}
+
+pub fn register_transform_submodule(py: Python, parent_module: Bound<PyModule>) -> PyResult<()> {
+    let m = PyModule::new(py, "transform")?;
+    m.add_class::<PyTransformer>()?;
+    m.add_function(wrap_pyfunction!(stock, &m)?)?;
+    m.add_function(wrap_pyfunction!(affine, &m)?)?;
+    m.add_function(wrap_pyfunction!(clipped_log, &m)?)?;
+    m.add_function(wrap_pyfunction!(power, &m)?)?;
+    m.add_function(wrap_pyfunction!(standardize, &m)?)?;
+    m.add_function(wrap_pyfunction!(chain, &m)?)?;
+    m.add_function(wrap_pyfunction!(from_json, &m)?)?;
+    parent_module.add_submodule(&m)?;
+    Ok(())
+}
+
+/// Transformation requested by user, "default" is resolved by the caller
+pub(crate) enum ParsedTransform {
+    Default,
+    Stock(StockTransformer),
+    Custom(CustomTransformer),
+}
+
+pub(crate) fn parse_transform_option(option: Option<Bound<PyAny>>) -> Res<Option<ParsedTransform>> {
     match option {
         None => Ok(None),
         Some(py_any) => {
             if let Ok(py_bool) = py_any.downcast::<PyBool>() {
                 if py_bool.is_true() {
-                    Ok(Some(default))
+                    Ok(Some(ParsedTransform::Default))
                 } else {
                     Ok(None)
                 }
@@ -121,10 +771,10 @@ pub(crate) fn parse_transform(
                 let cow_string = py_str.to_cow()?;
                 let s = cow_string.as_ref();
                 match s.try_into() {
-                    Ok(stock_transformer) => Ok(Some(stock_transformer)),
+                    Ok(stock_transformer) => Ok(Some(ParsedTransform::Stock(stock_transformer))),
                     _ => {
                         if s == "default" {
-                            Ok(Some(default))
+                            Ok(Some(ParsedTransform::Default))
                         } else {
                             Err(Exception::ValueError(format!(
                                 "Unknown transformation: {s}"
@@ -132,9 +782,16 @@ pub(crate) fn parse_transform(
                         }
                     }
                 }
+            } else if let Ok(py_transformer) = py_any.downcast::<PyTransformer>() {
+                Ok(Some(match &py_transformer.borrow().0 {
+                    CustomTransformer::Stock(stock_transformer) => {
+                        ParsedTransform::Stock(*stock_transformer)
+                    }
+                    transformer => ParsedTransform::Custom(transformer.clone()),
+                }))
             } else {
                 Err(Exception::ValueError(format!(
-                    "transform must be None, a bool or a str, not {}",
+                    "transform must be None, a bool, a str or a transform.Transformer, not {}",
                     py_any.get_type().qualname()?
                 )))
             }
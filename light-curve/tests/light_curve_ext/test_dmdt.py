@@ -1,4 +1,5 @@
 import copy
+import json
 import pickle
 from itertools import product
 
@@ -26,6 +27,8 @@ DM_DT = [
     DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=32, dm_size=32, norm=["dt"]),
     DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=32, dm_size=32, norm=["max"]),
     DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=32, dm_size=32, norm=["dt", "max"]),
+    DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=32, dm_size=32, norm=["pairs", "log1p"]),
+    DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=32, dm_size=32, norm=["row", "l2"]),
 ]
 
 
@@ -373,3 +376,682 @@ def test_copy_deepcopy(dmdt):
     dmdt_deepcopy = copy.deepcopy(dmdt)
     deepcopy_values = dmdt_deepcopy.gausses(*data)
     assert_array_equal(values, deepcopy_values)
+
+
+def random_band(n, bands=("g", "r"), rng=None):
+    rng = np.random.default_rng(rng)
+    return rng.choice(np.asarray(bands), n)
+
+
+def test_dmdt_points_band_channels():
+    dmdt = DM_DT[0]
+    t, m = random_lc(101, sigma=False, rng=0)
+    band = random_band(t.size, rng=1)
+
+    actual = dmdt.points(t, m, band=band)
+    assert actual.shape == (2,) + dmdt.shape
+    for map_, b in zip(actual, ["g", "r"]):
+        assert_array_equal(map_, dmdt.points(t[band == b], m[band == b]))
+
+
+def test_dmdt_points_color_channel():
+    dmdt = DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=8, dm_size=16, norm=[])
+    t, m = random_lc(51, sigma=False, rng=0)
+    band = random_band(t.size, rng=1)
+
+    (actual,) = dmdt.points(t, m, band=band, channels=[("g", "r")])
+
+    t_g, m_g = t[band == "g"], m[band == "g"]
+    t_r, m_r = t[band == "r"], m[band == "r"]
+    lgdt = np.log10(np.abs(t_r[None, :] - t_g[:, None])).reshape(-1)
+    dm = (m_r[None, :] - m_g[:, None]).reshape(-1)
+    desired, _, _ = np.histogram2d(lgdt, dm, bins=[np.linspace(-1.0, 1.0, 9), np.linspace(-2.0, 2.0, 17)])
+    assert_array_equal(actual, desired)
+
+
+def test_dmdt_gausses_band_channels():
+    dmdt = DM_DT[4]
+    t, m, sigma = random_lc(101, rng=0)
+    band = random_band(t.size, bands=(1, 2, 3), rng=1)
+
+    actual = dmdt.gausses(t, m, sigma, band=band, channels=[3, 1, (1, 3)])
+    assert actual.shape == (3,) + dmdt.shape
+    for map_, b in zip(actual[:2], [3, 1]):
+        idx = band == b
+        assert_allclose(map_, dmdt.gausses(t[idx], m[idx], sigma[idx]))
+
+
+def test_dmdt_many_band_channels():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in (51, 101)]
+    bands = [random_band(lc[0].size, rng=i) for i, lc in enumerate(lcs)]
+    channels = ["g", "r", ("g", "r")]
+
+    points = dmdt.points_many([lc[:2] for lc in lcs], bands=bands, channels=channels)
+    assert points.shape == (2, 3) + dmdt.shape
+    for actual, lc, band in zip(points, lcs, bands):
+        assert_array_equal(actual, dmdt.points(*lc[:2], band=band, channels=channels))
+
+    gausses = dmdt.gausses_many(lcs, bands=bands, channels=channels)
+    assert gausses.shape == (2, 3) + dmdt.shape
+    for actual, lc, band in zip(gausses, lcs, bands):
+        assert_allclose(actual, dmdt.gausses(*lc, band=band, channels=channels))
+
+
+def test_dmdt_batches_band_channels():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in (51, 101, 31)]
+    bands = [random_band(lc[0].size, rng=i) for i, lc in enumerate(lcs)]
+
+    desired = dmdt.gausses_many(lcs, bands=bands)
+    actual = np.concatenate(list(dmdt.gausses_batches(lcs, bands=bands, batch_size=2)))
+    assert_allclose(actual, desired)
+
+    desired = dmdt.points_many([lc[:2] for lc in lcs], bands=bands)
+    actual = np.concatenate(list(dmdt.points_batches([lc[:2] for lc in lcs], bands=bands, batch_size=2)))
+    assert_array_equal(actual, desired)
+
+
+def test_dmdt_band_channels_wrong_arguments():
+    dmdt = DM_DT[0]
+    t, m = random_lc(11, sigma=False)
+    with pytest.raises(ValueError):
+        dmdt.points(t, m, channels=["g"])
+    with pytest.raises(ValueError):
+        dmdt.points(t, m, band=random_band(t.size - 1))
+    with pytest.raises(ValueError):
+        dmdt.points(t, m, band=random_band(t.size), channels=[])
+    with pytest.raises(ValueError):
+        dmdt.points_many([(t, m)], bands=[])
+
+
+def test_dmdt_mismatched_lengths():
+    dmdt = DM_DT[0]
+    t, m, sigma = random_lc(11)
+    band = random_band(t.size)
+    for lc in [(t, m[:-1], sigma), (t, m, sigma[:-1])]:
+        with pytest.raises(ValueError):
+            dmdt.gausses(*lc)
+        with pytest.raises(ValueError):
+            dmdt.gausses(*lc, band=band)
+        with pytest.raises(ValueError):
+            dmdt.gausses_many([lc])
+        with pytest.raises(ValueError):
+            dmdt.gausses_batches([lc])
+    with pytest.raises(ValueError):
+        dmdt.points(t, m[:-1])
+    with pytest.raises(ValueError):
+        dmdt.points(t, m[:-1], band=band)
+    with pytest.raises(ValueError):
+        dmdt.points_many([(t, m[:-1])])
+    with pytest.raises(ValueError):
+        dmdt.points_batches([(t, m[:-1])])
+
+
+@pytest.mark.parametrize("prefetch,workers", [(1, 2), (4, 1), (3, 3), (8, 5)])
+def test_dmdt_batches_prefetch_workers(prefetch, workers):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(11, 111, 10)]
+    kwargs = dict(batch_size=3, yield_index=True, shuffle=True, drop_nobs=0.25)
+
+    desired = list(dmdt.gausses_batches(lcs, random_seed=0, **kwargs))
+    actual = list(dmdt.gausses_batches(lcs, random_seed=0, prefetch=prefetch, workers=workers, **kwargs))
+    assert len(actual) == len(desired)
+    for (actual_index, actual_maps), (desired_index, desired_maps) in zip(actual, desired):
+        assert_array_equal(actual_index, desired_index)
+        assert_array_equal(actual_maps, desired_maps)
+
+
+def test_dmdt_batches_prefetch_early_stop():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(101, sigma=False, rng=i) for i in range(20)]
+    it = iter(dmdt.points_batches(lcs, prefetch=4, workers=2))
+    next(it)
+    del it
+
+
+@pytest.mark.parametrize("kwargs", [dict(batch_size=0), dict(prefetch=0), dict(workers=0)])
+def test_dmdt_batches_prefetch_workers_wrong_arguments(kwargs):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(11, sigma=False)]
+    with pytest.raises(ValueError):
+        dmdt.points_batches(lcs, **kwargs)
+
+
+AUGMENTATIONS = [
+    dict(noise=1.0),
+    dict(time_shift=0.1),
+    dict(time_stretch=0.5),
+    dict(crop=0.5),
+    dict(noise=0.5, time_shift=0.05, time_stretch=0.2, mag_offset=0.1, crop=0.2, drop_nobs=0.1),
+]
+
+
+@pytest.mark.parametrize("augmentation", AUGMENTATIONS)
+def test_dmdt_batches_augmentation_reproducible(augmentation):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(21, 121, 20)]
+
+    original = np.concatenate(list(dmdt.gausses_batches(lcs, batch_size=2)))
+    first = np.concatenate(list(dmdt.gausses_batches(lcs, batch_size=2, random_seed=0, **augmentation)))
+    second = np.concatenate(list(dmdt.gausses_batches(lcs, batch_size=2, random_seed=0, **augmentation)))
+    assert_array_equal(first, second)
+    assert first.shape == original.shape
+    assert not np.array_equal(first, original)
+
+
+def test_dmdt_batches_augmentation_zero():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(21, 121, 20)]
+
+    desired = np.concatenate(list(dmdt.gausses_batches(lcs)))
+    actual = np.concatenate(
+        list(
+            dmdt.gausses_batches(
+                lcs, noise=0.0, time_shift=0.0, time_stretch=0.0, mag_offset=0.0, crop=0.0, random_seed=0
+            )
+        )
+    )
+    assert_array_equal(actual, desired)
+
+
+def test_dmdt_batches_crop():
+    dmdt = DmDt.from_borders(min_lgdt=-2.0, max_lgdt=1.0, max_abs_dm=10.0, lgdt_size=8, dm_size=8, norm=[])
+    lcs = [random_lc(n, sigma=False, rng=n) for n in range(21, 121, 20)]
+
+    original = np.concatenate(list(dmdt.points_batches(lcs)))
+    cropped = np.concatenate(list(dmdt.points_batches(lcs, crop=0.5, random_seed=0)))
+    assert np.all(cropped.sum(axis=(1, 2)) < original.sum(axis=(1, 2)))
+
+
+@pytest.mark.parametrize(
+    "kwargs", [dict(noise=-1.0), dict(time_shift=np.inf), dict(time_stretch=np.nan), dict(crop=1.0), dict(crop=-0.1)]
+)
+def test_dmdt_batches_augmentation_wrong_arguments(kwargs):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(11, sigma=False)]
+    with pytest.raises(ValueError):
+        dmdt.points_batches(lcs, **kwargs)
+
+
+@pytest.mark.parametrize("world_size", [1, 2, 3])
+def test_dmdt_batches_shard(world_size):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(11, 111, 10)]
+    kwargs = dict(batch_size=2, yield_index=True, shuffle=True, random_seed=0)
+
+    full = dmdt.gausses_batches(lcs, **kwargs)
+    full_index = np.concatenate([index for index, _maps in full])
+    shard_indexes = []
+    for rank in range(world_size):
+        batches = dmdt.gausses_batches(lcs, shard=(rank, world_size), **kwargs)
+        for index, maps in batches:
+            assert_allclose(maps, dmdt.gausses_many([lcs[i] for i in index]))
+            shard_indexes.append(index)
+    shard_index = np.concatenate(shard_indexes)
+    assert_array_equal(np.sort(shard_index), np.arange(len(lcs)))
+    assert_array_equal(np.sort(shard_index), np.sort(full_index))
+
+
+def test_dmdt_batches_epoch():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(11, 111, 10)]
+    kwargs = dict(batch_size=3, yield_index=True, shuffle=True, drop_nobs=2, noise=0.5, random_seed=0)
+
+    batches = dmdt.gausses_batches(lcs, epoch=0, **kwargs)
+    epochs = [list(batches) for _ in range(3)]
+    assert not np.array_equal(epochs[1][0][0], epochs[2][0][0])
+    for epoch, desired in enumerate(epochs):
+        actual = list(dmdt.gausses_batches(lcs, epoch=epoch, **kwargs))
+        for (actual_index, actual_maps), (desired_index, desired_maps) in zip(actual, desired):
+            assert_array_equal(actual_index, desired_index)
+            assert_array_equal(actual_maps, desired_maps)
+
+
+@pytest.mark.parametrize("workers", [1, 2])
+def test_dmdt_batches_resume(workers):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in range(11, 111, 10)]
+    kwargs = dict(batch_size=3, yield_index=True, shuffle=True, drop_nobs=0.2, random_seed=0, workers=workers)
+
+    desired = list(dmdt.gausses_batches(lcs, epoch=5, **kwargs))
+
+    it = iter(dmdt.gausses_batches(lcs, epoch=5, **kwargs))
+    assert it.epoch == 5
+    next(it)
+    next(it)
+    position = it.position
+    assert position == 2
+    del it
+
+    resumed = iter(dmdt.gausses_batches(lcs, epoch=5, start=position, **kwargs))
+    assert resumed.position == position
+    actual = list(resumed)
+    assert len(actual) == len(desired) - position
+    for (actual_index, actual_maps), (desired_index, desired_maps) in zip(actual, desired[position:]):
+        assert_array_equal(actual_index, desired_index)
+        assert_array_equal(actual_maps, desired_maps)
+
+
+@pytest.mark.parametrize("kwargs", [dict(shard=(2, 2)), dict(shard=(0, 0)), dict(shard=(0, 2), shuffle=True)])
+def test_dmdt_batches_shard_wrong_arguments(kwargs):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(11, sigma=False)]
+    with pytest.raises(ValueError):
+        dmdt.points_batches(lcs, **kwargs)
+
+
+def test_dmdt_flux_ratio_points():
+    dt_grid, dm_grid = np.linspace(0.0, 10.0, 11), np.linspace(0.0, 4.0, 17)
+    dmdt = DmDt(dt=dt_grid, dm=dm_grid, dm_kind="flux-ratio", norm=[])
+    assert dmdt.dm_kind == "flux-ratio"
+    rng = np.random.default_rng(0)
+    t = np.sort(rng.uniform(0.0, 10.0, 51))
+    f = rng.uniform(1.0, 3.0, t.size)
+
+    actual = dmdt.points(t, f)
+
+    i, j = np.triu_indices(t.size, k=1)
+    desired, _, _ = np.histogram2d(t[j] - t[i], f[j] / f[i], bins=[dt_grid, dm_grid])
+    assert_array_equal(actual, desired)
+
+
+def norm_cdf(x, mu, sigma):
+    from math import erf
+
+    return np.array([0.5 * (1.0 + erf((x_i - mu) / (sigma * np.sqrt(2.0)))) for x_i in x])
+
+
+def test_dmdt_flux_ratio_gausses():
+    dmdt = DmDt(dt=np.linspace(0.0, 10.0, 11), dm=np.linspace(0.0, 4.0, 17), dm_kind="flux-ratio", norm=[])
+    t = np.array([0.0, 1.5])
+    f = np.array([1.0, 2.0])
+    sigma = np.array([0.1, 0.2])
+
+    actual = dmdt.gausses(t, f, sigma)
+
+    ratio_sigma = 2.0 * np.hypot(0.1 / 1.0, 0.2 / 2.0)
+    desired = np.zeros(dmdt.shape)
+    desired[1] = np.diff(norm_cdf(dmdt.dm_grid, 2.0, ratio_sigma))
+    assert_allclose(actual, desired, atol=1e-12)
+
+
+def test_dmdt_flux_ratio_mag_offset():
+    dmdt = DmDt(dt=np.linspace(0.0, 10.0, 11), dm=np.linspace(0.0, 4.0, 17), dm_kind="flux-ratio", norm=[])
+    rng = np.random.default_rng(0)
+    t = np.sort(rng.uniform(0.0, 10.0, 51))
+    f = rng.uniform(1.0, 3.0, t.size)
+    sigma = rng.uniform(0.01, 0.1, t.size)
+    lcs = [(t, f, sigma)]
+
+    original = np.concatenate(list(dmdt.gausses_batches(lcs)))
+    offset = np.concatenate(list(dmdt.gausses_batches(lcs, mag_offset=1.0, random_seed=0)))
+    # Fluxes and errors are multiplied by the same factor, so flux ratios don't change
+    assert_allclose(offset, original, rtol=1e-10, atol=1e-12)
+
+
+def test_dmdt_norm_flux_diff():
+    kwargs = dict(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, norm=["dt"])
+    dmdt = DmDt.from_borders(**kwargs, dm_kind="norm-flux-diff")
+    dmdt_mag = DmDt.from_borders(**kwargs)
+    t, f, sigma = random_lc(101, rng=0)
+    scale = np.max(np.abs(f))
+
+    assert_array_equal(dmdt.points(t, f), dmdt_mag.points(t, f / scale))
+    assert_allclose(dmdt.gausses(t, f, sigma), dmdt_mag.gausses(t, f / scale, sigma / scale))
+
+
+def test_dmdt_asinh_mag_diff():
+    kwargs = dict(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32)
+    dmdt = DmDt.from_borders(**kwargs, dm_kind="asinh-mag-diff", asinh_softening=0.5)
+    dmdt_mag = DmDt.from_borders(**kwargs)
+    t, f, sigma = random_lc(101, rng=0)
+
+    a = 2.5 * np.log10(np.e)
+    mu = -a * np.arcsinh(f / (2.0 * 0.5))
+    mu_sigma = a * sigma / np.sqrt(1.0 + f**2)
+    assert_allclose(dmdt.gausses(t, f, sigma), dmdt_mag.gausses(t, mu, mu_sigma), atol=1e-10)
+
+
+def test_dmdt_dm_kind_pickle():
+    dmdt = DmDt.from_borders(
+        min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, dm_kind="flux-ratio"
+    )
+    new_dmdt = pickle.loads(pickle.dumps(dmdt))
+    assert new_dmdt.dm_kind == "flux-ratio"
+    lc = random_lc(51)
+    assert_array_equal(new_dmdt.gausses(*lc), dmdt.gausses(*lc))
+
+
+@pytest.mark.parametrize("kwargs", [dict(dm_kind="flux"), dict(dm_kind="asinh-mag-diff", asinh_softening=0.0)])
+def test_dmdt_dm_kind_wrong_arguments(kwargs):
+    with pytest.raises(ValueError):
+        DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, **kwargs)
+
+
+def csr_to_dense(csr, shape):
+    data, indices, indptr, (n_rows, n_cols) = csr
+    assert indptr.shape == (n_rows + 1,)
+    dense = np.zeros((n_rows, n_cols), dtype=data.dtype)
+    for i in range(n_rows):
+        dense[i, indices[indptr[i] : indptr[i + 1]]] = data[indptr[i] : indptr[i + 1]]
+    return dense.reshape((n_rows,) + shape)
+
+
+@pytest.mark.parametrize("dtype", [np.float32, np.float64])
+def test_dmdt_many_sparse(dtype):
+    dmdt = DM_DT[4]
+    lcs = [random_lc(n, rng=n, dtype=dtype) for n in (3, 11, 101)]
+
+    points = dmdt.points_many([lc[:2] for lc in lcs], output="sparse")
+    assert points[0].dtype == dtype
+    assert points[0].size < lcs[0][0].size**2 + lcs[1][0].size**2 + lcs[2][0].size**2
+    assert_array_equal(csr_to_dense(points, dmdt.shape), dmdt.points_many([lc[:2] for lc in lcs]))
+
+    gausses = dmdt.gausses_many(lcs, output="sparse")
+    assert_array_equal(csr_to_dense(gausses, dmdt.shape), dmdt.gausses_many(lcs))
+
+
+def test_dmdt_many_sparse_band_channels():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n) for n in (51, 101)]
+    bands = [random_band(lc[0].size, rng=i) for i, lc in enumerate(lcs)]
+
+    desired = dmdt.gausses_many(lcs, bands=bands, channels=["g", ("g", "r")])
+    actual = dmdt.gausses_many(lcs, bands=bands, channels=["g", ("g", "r")], output="sparse")
+    assert_array_equal(csr_to_dense(actual, desired.shape[1:]), desired)
+
+
+def test_dmdt_batches_sparse():
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, sigma=False, rng=n) for n in range(11, 111, 10)]
+
+    desired = list(dmdt.points_batches(lcs, batch_size=3))
+    actual = list(dmdt.points_batches(lcs, batch_size=3, output="sparse"))
+    assert len(actual) == len(desired)
+    for csr, dense in zip(actual, desired):
+        assert_array_equal(csr_to_dense(csr, dmdt.shape), dense)
+
+
+def test_dmdt_sparse_wrong_output():
+    dmdt = DM_DT[0]
+    with pytest.raises(ValueError):
+        dmdt.points_many([random_lc(11, sigma=False)], output="csr")
+
+
+@pytest.mark.parametrize("norm", ["pairs", "row", "l2", "log1p"])
+def test_dmdt_new_norms(norm):
+    kwargs = dict(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=5.0, lgdt_size=16, dm_size=32)
+    dmdt = DmDt.from_borders(**kwargs, norm=[norm])
+    dmdt_raw = DmDt.from_borders(**kwargs, norm=[])
+    lc = random_lc(101, rng=0)
+
+    for method in ["points", "gausses"]:
+        actual = getattr(dmdt, method)(*lc[: 2 if method == "points" else 3])
+        raw = getattr(dmdt_raw, method)(*lc[: 2 if method == "points" else 3])
+        if norm == "pairs":
+            desired = raw / dmdt_raw.count_dt(lc[0]).sum()
+        elif norm == "row":
+            row_sum = raw.sum(axis=1, keepdims=True)
+            desired = raw / np.where(row_sum == 0.0, 1.0, row_sum)
+        elif norm == "l2":
+            desired = raw / np.sqrt(np.sum(raw**2))
+        else:
+            desired = np.log1p(raw)
+        assert_allclose(actual, desired, rtol=1e-10, atol=1e-15)
+
+
+def test_dmdt_norm_pairs_probability():
+    dmdt = DmDt.from_borders(min_lgdt=-3.0, max_lgdt=2.0, max_abs_dm=10.0, lgdt_size=16, dm_size=32, norm=["pairs"])
+    t, m = random_lc(51, sigma=False, rng=0)
+    assert_allclose(dmdt.points(t, m).sum(), 1.0)
+
+
+def test_dmdt_norm_wrong_name():
+    with pytest.raises(ValueError, match="max"):
+        DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, norm=["norm"])
+
+
+def test_dmdt_grid_types():
+    assert DM_DT[0].dt_type == "log"
+    assert DM_DT[0].dm_type == "linear"
+    dmdt = DmDt(dt=np.array([1.0, 2.0, 5.0]), dm=np.linspace(-1.0, 1.0, 5), dm_type="asinh")
+    assert dmdt.dt_type == "asis"
+    assert dmdt.dm_type == "asinh"
+
+
+def test_dmdt_asinh_grid():
+    dmdt = DmDt(dt=np.logspace(-1.0, 1.0, 9), dm=np.linspace(-10.0, 10.0, 17), dm_type="asinh")
+    desired = np.sinh(np.linspace(np.arcsinh(-10.0), np.arcsinh(10.0), 17))
+    assert_allclose(dmdt.dm_grid, desired, rtol=1e-12)
+    assert dmdt.shape == (8, 16)
+
+    t, m, sigma = random_lc(101, rng=0)
+    dm = m[None, :] - m[:, None]
+    dt = t[None, :] - t[:, None]
+    idx = np.triu_indices(t.size, k=1)
+    desired_map, _, _ = np.histogram2d(dt[idx], dm[idx], bins=[dmdt.dt_grid, dmdt.dm_grid])
+    assert_array_equal(dmdt.points(t, m), desired_map)
+
+
+@pytest.mark.parametrize("dmdt", DM_DT)
+def test_dmdt_json(dmdt):
+    new_dmdt = DmDt.from_json(dmdt.to_json())
+    assert new_dmdt == dmdt
+    assert new_dmdt.dt_type == dmdt.dt_type
+    assert new_dmdt.dm_type == dmdt.dm_type
+    assert_array_equal(new_dmdt.dt_grid, dmdt.dt_grid)
+    assert_array_equal(new_dmdt.dm_grid, dmdt.dm_grid)
+    lc = random_lc(51)
+    assert_array_equal(new_dmdt.gausses(*lc), dmdt.gausses(*lc))
+
+
+def test_dmdt_from_json_wrong():
+    with pytest.raises(ValueError):
+        DmDt.from_json("{}")
+
+
+def test_dmdt_eq():
+    kwargs = dict(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32)
+    assert DmDt.from_borders(**kwargs) == DmDt.from_borders(**kwargs, n_jobs=1)
+    assert DmDt.from_borders(**kwargs) != DmDt.from_borders(**kwargs, norm=["dt"])
+    assert DmDt.from_borders(**kwargs) != DmDt.from_borders(**kwargs, approx_erf=True)
+    assert DmDt.from_borders(**kwargs) != DmDt.from_borders(**{**kwargs, "dm_size": 16})
+    assert DmDt.from_borders(**kwargs) != DmDt.from_borders(**kwargs, dm_kind="flux-ratio")
+
+
+def test_dmdt_repr():
+    dmdt = DmDt.from_borders(min_lgdt=0.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, norm=["dt"])
+    assert repr(dmdt) == (
+        "DmDt(dt_type='log', min_dt=1.0, max_dt=10.0, dt_size=16, dm_type='linear', min_dm=-2.0, max_dm=2.0, "
+        "dm_size=32, norm=['dt'], dm_kind='mag-diff', approx_erf=False)"
+    )
+
+
+def test_dmdt_points_weight_array():
+    dt_grid, dm_grid = np.linspace(0.0, 10.0, 11), np.linspace(-3.0, 3.0, 25)
+    dmdt = DmDt(dt=dt_grid, dm=dm_grid, norm=[])
+    t, m = random_lc(51, sigma=False, rng=0)
+    w = np.random.default_rng(1).uniform(0.0, 2.0, t.size)
+
+    actual = dmdt.points(t, m, weight=w)
+
+    i, j = np.triu_indices(t.size, k=1)
+    desired, _, _ = np.histogram2d(t[j] - t[i], m[j] - m[i], bins=[dt_grid, dm_grid], weights=w[i] * w[j])
+    assert_allclose(actual, desired)
+
+
+def test_dmdt_points_weight_inverse_dt():
+    dt_grid, dm_grid = np.linspace(0.0, 10.0, 11), np.linspace(-3.0, 3.0, 25)
+    dmdt = DmDt(dt=dt_grid, dm=dm_grid, norm=[])
+    t, m = random_lc(51, sigma=False, rng=0)
+
+    actual = dmdt.points(t, m, weight="inverse-dt")
+
+    i, j = np.triu_indices(t.size, k=1)
+    dt = t[j] - t[i]
+    desired, _, _ = np.histogram2d(dt, m[j] - m[i], bins=[dt_grid, dm_grid], weights=1.0 / dt)
+    assert_allclose(actual, desired)
+
+
+@pytest.mark.parametrize("dmdt", DM_DT)
+def test_dmdt_unity_weights(dmdt):
+    t, m, sigma = random_lc(101)
+    w = np.ones_like(t)
+    assert_allclose(dmdt.points(t, m, weight=w), dmdt.points(t, m), atol=1e-12)
+    assert_allclose(dmdt.gausses(t, m, sigma, weight=w), dmdt.gausses(t, m, sigma), atol=1e-12)
+
+
+def test_dmdt_gausses_weight_inverse_variance_snr():
+    dmdt = DmDt(dt=np.linspace(0.0, 10.0, 11), dm=np.linspace(-2.0, 2.0, 17), norm=[])
+    t = np.array([0.0, 1.5])
+    m = np.array([0.0, 0.5])
+    sigma = np.array([0.1, 0.2])
+    dm_sigma = np.hypot(0.1, 0.2)
+    smeared = np.diff(norm_cdf(dmdt.dm_grid, 0.5, dm_sigma))
+
+    desired = np.zeros(dmdt.shape)
+    desired[1] = smeared / dm_sigma**2
+    assert_allclose(dmdt.gausses(t, m, sigma, weight="inverse-variance"), desired, atol=1e-10)
+
+    snr = 2.5 * np.log10(np.e) / sigma
+    desired[1] = smeared / np.sqrt(np.sum(1.0 / snr**2))
+    assert_allclose(dmdt.gausses(t, m, sigma, weight="snr"), desired, atol=1e-10)
+
+
+def test_dmdt_weight_norm_dt():
+    dt_grid, dm_grid = np.linspace(0.0, 10.0, 11), np.linspace(-1.0, 1.0, 9)
+    dmdt = DmDt(dt=dt_grid, dm=dm_grid, norm=["dt"])
+    dmdt_no_norm = DmDt(dt=dt_grid, dm=dm_grid, norm=[])
+    t, m = random_lc(51, sigma=False, rng=0)
+    w = np.random.default_rng(1).uniform(0.0, 2.0, t.size)
+
+    actual = dmdt.points(t, m, weight=w)
+
+    i, j = np.triu_indices(t.size, k=1)
+    # Pairs out of the dm range contribute to the total weight of their dt cell
+    dt_weights, _ = np.histogram(t[j] - t[i], bins=dt_grid, weights=w[i] * w[j])
+    assert_allclose(actual, dmdt_no_norm.points(t, m, weight=w) / dt_weights[:, None])
+
+
+def test_dmdt_weights_many():
+    dmdt = DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, norm=["dt"])
+    lcs = [random_lc(n, rng=n) for n in range(10, 20)]
+    w_ = [np.random.default_rng(n).uniform(0.0, 1.0, n) for n in range(10, 20)]
+
+    assert_allclose(
+        dmdt.gausses_many(lcs, weights="snr"),
+        [dmdt.gausses(*lc, weight="snr") for lc in lcs],
+    )
+    assert_allclose(
+        dmdt.points_many([lc[:2] for lc in lcs], weights=w_),
+        [dmdt.points(*lc[:2], weight=w) for lc, w in zip(lcs, w_)],
+    )
+    assert_allclose(
+        csr_to_dense(dmdt.gausses_many(lcs, weights=w_, output="sparse"), dmdt.shape),
+        [dmdt.gausses(*lc, weight=w) for lc, w in zip(lcs, w_)],
+    )
+
+
+def test_dmdt_weight_band_channels():
+    dmdt = DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32, norm=[])
+    t, m, sigma = random_lc(101, rng=0)
+    band = np.where(np.arange(t.size) % 2 == 0, "g", "r")
+    w = np.random.default_rng(1).uniform(0.0, 1.0, t.size)
+
+    actual = dmdt.gausses(t, m, sigma, band=band, channels=["r"], weight=w)
+
+    idx = band == "r"
+    assert_allclose(actual[0], dmdt.gausses(t[idx], m[idx], sigma[idx], weight=w[idx]))
+
+
+@pytest.mark.parametrize(
+    "weight",
+    ["inverse-variance", "snr", "sigma", np.ones(10), np.full(11, -1.0)],
+)
+def test_dmdt_weight_wrong_arguments(weight):
+    dmdt = DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32)
+    t, m = random_lc(11, sigma=False)
+    with pytest.raises(ValueError):
+        dmdt.points(t, m, weight=weight)
+
+
+def test_dmdt_weights_many_wrong_length():
+    dmdt = DmDt.from_borders(min_lgdt=-1.0, max_lgdt=1.0, max_abs_dm=2.0, lgdt_size=16, dm_size=32)
+    lcs = [random_lc(11, sigma=False) for _ in range(3)]
+    with pytest.raises(ValueError):
+        dmdt.points_many(lcs, weights=[np.ones(11)] * 2)
+
+
+@pytest.mark.parametrize("sigma,workers", [(False, 1), (True, 1), (True, 3)])
+def test_dmdt_write_dataset_npy(tmp_path, sigma, workers):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, sigma=sigma, rng=n) for n in range(11, 111, 10)]
+    kwargs = dict(batch_size=3, shuffle=True, random_seed=0)
+    batches = dmdt.gausses_batches if sigma else dmdt.points_batches
+    desired = list(batches(lcs, yield_index=True, **kwargs))
+
+    dmdt.write_dataset(lcs, tmp_path, workers=workers, **kwargs)
+
+    with open(tmp_path / "index.json") as fh:
+        index = json.load(fh)
+    assert index["format"] == "npy"
+    assert index["dtype"] == "<f8"
+    assert index["map_shape"] == list(dmdt.shape)
+    assert index["n_lcs"] == len(lcs)
+    assert DmDt.from_json(json.dumps(index["dmdt"])) == dmdt
+    assert len(index["batches"]) == len(desired)
+    for batch, (desired_index, desired_maps) in zip(index["batches"], desired):
+        assert_array_equal(batch["index"], desired_index)
+        assert_array_equal(np.load(tmp_path / batch["file"]), desired_maps)
+
+
+def test_dmdt_write_dataset_npz(tmp_path):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, rng=n, dtype=np.float32) for n in range(11, 51, 10)]
+    bands = [np.where(np.arange(lc[0].size) % 2 == 0, "g", "r") for lc in lcs]
+    kwargs = dict(bands=bands, channels=["g", "r", ("g", "r")], batch_size=3)
+    desired = list(dmdt.gausses_batches(lcs, yield_index=True, **kwargs))
+
+    dmdt.write_dataset(lcs, tmp_path / "dataset", format="npz", **kwargs)
+
+    with open(tmp_path / "dataset" / "index.json") as fh:
+        index = json.load(fh)
+    assert index["dtype"] == "<f4"
+    assert index["map_shape"] == [3, *dmdt.shape]
+    assert [batch["file"] for batch in index["batches"]] == ["batch_000000.npz", "batch_000001.npz"]
+    for batch, (desired_index, desired_maps) in zip(index["batches"], desired):
+        with np.load(tmp_path / "dataset" / batch["file"]) as npz:
+            assert_array_equal(npz["index"], desired_index)
+            assert npz["maps"].dtype == np.float32
+            assert_array_equal(npz["maps"], desired_maps)
+
+
+def test_dmdt_write_dataset_removes_stale_files(tmp_path):
+    dmdt = DM_DT[0]
+    lcs = [random_lc(n, sigma=False, rng=n) for n in range(11, 111, 10)]
+    (tmp_path / "readme.txt").write_text("not a part of the dataset")
+
+    dmdt.write_dataset(lcs, tmp_path, batch_size=1, format="npz")
+    dmdt.write_dataset(lcs, tmp_path, batch_size=4)
+
+    with open(tmp_path / "index.json") as fh:
+        index = json.load(fh)
+    files = sorted(path.name for path in tmp_path.iterdir())
+    assert files == sorted([batch["file"] for batch in index["batches"]] + ["index.json", "readme.txt"])
+    assert len(index["batches"]) == 3
+
+
+@pytest.mark.parametrize(
+    "lcs,kwargs",
+    [
+        ([random_lc(11)], dict(format="hdf5")),
+        ([random_lc(11)], dict(batch_size=0)),
+        ([random_lc(11)[:1]], {}),
+        ([], {}),
+    ],
+)
+def test_dmdt_write_dataset_wrong_arguments(tmp_path, lcs, kwargs):
+    dmdt = DM_DT[0]
+    with pytest.raises(ValueError):
+        dmdt.write_dataset(lcs, tmp_path, **kwargs)
@@ -0,0 +1,156 @@
+import pickle
+
+import numpy as np
+import pytest
+from numpy.testing import assert_allclose, assert_array_equal
+
+from light_curve.light_curve_ext import Extractor, FourierDecomposition, Periodogram
+
+PERIOD = 0.7
+AMPLITUDES = [0.5, 0.2, 0.1]
+PHASES = [0.3, 1.0, 2.0]
+
+
+def gen_lc(rng, n=200, noise=0.0):
+    t = np.sort(rng.uniform(0.0, 30.0, n))
+    m = 10.0 + sum(
+        a * np.sin(2.0 * np.pi * k * t / PERIOD + phi) for k, (a, phi) in enumerate(zip(AMPLITUDES, PHASES), 1)
+    )
+    sigma = np.full_like(t, 0.01)
+    return t, m + noise * rng.normal(size=n), sigma
+
+
+def expected_values(order=3):
+    amplitudes = AMPLITUDES + [0.0] * (order - len(AMPLITUDES))
+    phases = PHASES + [0.0] * (order - len(PHASES))
+    values = [PERIOD]
+    for a, phi in zip(amplitudes, phases):
+        values += [a, phi]
+    for k in range(2, order + 1):
+        values += [amplitudes[k - 1] / amplitudes[0], (phases[k - 1] - k * phases[0]) % (2.0 * np.pi)]
+    return np.array(values)
+
+
+def test_names():
+    fourier = FourierDecomposition(1.0, order=3)
+    assert fourier.names == [
+        "fourier_period",
+        "fourier_amplitude_1",
+        "fourier_phase_1",
+        "fourier_amplitude_2",
+        "fourier_phase_2",
+        "fourier_amplitude_3",
+        "fourier_phase_3",
+        "fourier_r21",
+        "fourier_phi21",
+        "fourier_r31",
+        "fourier_phi31",
+    ]
+    assert len(fourier.descriptions) == len(fourier.names)
+    assert len(FourierDecomposition(1.0, order=1).names) == 3
+
+
+def test_fixed_period():
+    rng = np.random.default_rng(0)
+    t, m, sigma = gen_lc(rng)
+    fourier = FourierDecomposition(PERIOD, order=3)
+    assert_allclose(fourier(t, m, sigma), expected_values(), rtol=1e-8, atol=1e-10)
+    assert_allclose(fourier(t, m), expected_values(), rtol=1e-8, atol=1e-10)
+
+
+def test_periodogram_period():
+    rng = np.random.default_rng(1)
+    t, m, sigma = gen_lc(rng, noise=0.01)
+    freq = 2.0 * np.pi / PERIOD
+    periodogram = Periodogram(peaks=1, freqs=np.linspace(0.5 * freq, 1.5 * freq, 2001), fast=False)
+    fourier = FourierDecomposition(periodogram, order=3)
+
+    values = fourier(t, m, sigma)
+    assert_allclose(values[0], periodogram(t, m, sigma)[0])
+    assert_allclose(values, expected_values(), rtol=2e-2, atol=2e-2)
+
+
+def test_fold():
+    rng = np.random.default_rng(2)
+    t, m, _sigma = gen_lc(rng)
+    fourier = FourierDecomposition(PERIOD)
+
+    phase, folded_m = fourier.fold(t, m)
+    assert np.all(np.diff(phase) >= 0.0)
+    assert np.all((phase >= 0.0) & (phase < 1.0))
+    idx = np.argsort((t / PERIOD) % 1.0)
+    assert_allclose(phase, (t[idx] / PERIOD) % 1.0)
+    assert_array_equal(folded_m, m[idx])
+
+    phase_2, folded_m_2 = fourier.fold(t, m, 2.0 * PERIOD)
+    assert_allclose(phase_2, np.sort((t / (2.0 * PERIOD)) % 1.0))
+    assert folded_m_2.shape == m.shape
+
+    phase_f32, _ = fourier.fold(t.astype(np.float32), m.astype(np.float32))
+    assert phase_f32.dtype == np.float32
+
+
+def test_fold_sigma():
+    rng = np.random.default_rng(5)
+    t, m, sigma = gen_lc(rng, noise=0.01)
+    freq = 2.0 * np.pi / PERIOD
+    periodogram = Periodogram(peaks=1, freqs=np.linspace(0.5 * freq, 1.5 * freq, 2001), fast=False)
+    fourier = FourierDecomposition(periodogram, order=3)
+
+    # The weighted periodogram gives the period
+    period = fourier(t, m, sigma)[0]
+    phase, folded_m, folded_sigma = fourier.fold(t, m, sigma=sigma)
+    idx = np.argsort((t / period) % 1.0)
+    assert_allclose(phase, (t[idx] / period) % 1.0)
+    assert_array_equal(folded_m, m[idx])
+    assert_array_equal(folded_sigma, sigma[idx])
+
+    shuffled = rng.permutation(t.size)
+    with pytest.raises(ValueError, match="ascending"):
+        fourier.fold(t[shuffled], m[shuffled], sigma=sigma[shuffled])
+    # Sorting is not required for a fixed period
+    assert len(fourier.fold(t[shuffled], m[shuffled], PERIOD, sigma=sigma[shuffled])) == 3
+
+
+def test_transform():
+    rng = np.random.default_rng(3)
+    t, m, sigma = gen_lc(rng)
+    fourier = FourierDecomposition(PERIOD)
+    values = fourier(t, m, sigma)
+
+    transformed = FourierDecomposition(PERIOD, transform="default")(t, m, sigma)
+    assert_allclose(transformed[0], np.log10(values[0]))
+    assert_allclose(transformed[1:], values[1:])
+    assert_allclose(FourierDecomposition(PERIOD, transform="arcsinh")(t, m, sigma), np.arcsinh(values))
+
+
+def test_pickle_and_extractor():
+    rng = np.random.default_rng(4)
+    t, m, sigma = gen_lc(rng)
+    fourier = FourierDecomposition(Periodogram(peaks=1, fast=False), order=2)
+    restored = pickle.loads(pickle.dumps(fourier))
+    assert restored.names == fourier.names
+    assert_array_equal(restored(t, m, sigma), fourier(t, m, sigma))
+    assert_array_equal(restored.fold(t, m), fourier.fold(t, m))
+
+    extractor = Extractor(FourierDecomposition(PERIOD, order=2), Periodogram(peaks=1))
+    assert extractor.names[:7] == FourierDecomposition(PERIOD, order=2).names
+
+
+def test_wrong_arguments():
+    with pytest.raises(TypeError):
+        FourierDecomposition()
+    with pytest.raises(TypeError):
+        FourierDecomposition("period")
+    with pytest.raises(ValueError):
+        FourierDecomposition(-1.0)
+    with pytest.raises(ValueError):
+        FourierDecomposition(1.0, order=0)
+
+    t = np.linspace(0.0, 1.0, 6)
+    fourier = FourierDecomposition(1.0, order=3)
+    with pytest.raises(ValueError):
+        fourier(t, t)
+    assert_array_equal(fourier(t, t, fill_value=-1.0), -1.0)
+    with pytest.raises(ValueError):
+        fourier.fold(t, t, 0.0)
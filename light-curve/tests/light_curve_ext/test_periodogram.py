@@ -1,9 +1,12 @@
+import pickle
+
 import numpy as np
 import pytest
-from numpy.testing import assert_allclose
+from numpy.testing import assert_allclose, assert_array_equal
 from scipy.signal import lombscargle
+from scipy.special import gammaln
 
-from light_curve.light_curve_ext import Periodogram
+from light_curve.light_curve_ext import Bins, Periodogram, feature_from_json
 
 
 def test_vs_lombscargle():
@@ -106,3 +109,631 @@ def test_failure_for_wrong_freq_grids():
     with pytest.raises(ValueError):
         # Doesn't start with 0.0
         Periodogram(freqs=np.linspace(1.0, 100.0, 257), fast=True)
+
+
+def generalized_lomb_scargle(t, m, sigma, freqs, nterms=1):
+    """Reference implementation fitting floating-mean Fourier series with least squares"""
+    w = sigma**-2
+    chi2_0 = np.sum(w * (m - np.average(m, weights=w)) ** 2)
+    power = []
+    for freq in freqs:
+        harmonics = [f(k * freq * t) for k in range(1, nterms + 1) for f in (np.cos, np.sin)]
+        design = np.stack([np.ones_like(t), *harmonics], axis=1)
+        coeffs, *_ = np.linalg.lstsq(design * np.sqrt(w)[:, None], m * np.sqrt(w), rcond=None)
+        chi2 = np.sum(w * (m - design @ coeffs) ** 2)
+        power.append(0.5 * (t.size - 1) * (1.0 - chi2 / chi2_0))
+    return np.array(power)
+
+
+def gen_heteroscedastic_lc(rng, n=100):
+    t = np.sort(rng.uniform(0.0, 20.0, n))
+    sigma = 10 ** rng.uniform(-2.0, 0.0, n)
+    m = 0.5 * np.sin(2.1 * t) + 3.0 + sigma * rng.normal(size=n)
+    return t, m, sigma
+
+
+def test_weighted_vs_least_squares():
+    rng = np.random.default_rng(0)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.geomspace(0.1, 10.0, 1000)
+
+    licu_freqs, licu_power = Periodogram(freqs=freqs, fast=False).freq_power(t, m, sigma)
+    assert_allclose(licu_freqs, freqs)
+    desired = generalized_lomb_scargle(t, m, sigma, freqs)
+    assert_allclose(licu_power, desired, rtol=1e-8, atol=1e-8 * desired.max())
+    assert_allclose(Periodogram(freqs=freqs, fast=False).power(t, m, sigma), licu_power)
+
+
+def test_weighted_direct_vs_fft():
+    rng = np.random.default_rng(1)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.linspace(0.0, 20.0, 2**12 + 1)
+
+    direct = Periodogram(freqs=freqs, fast=False).power(t, m, sigma)
+    fft = Periodogram(freqs=freqs, fast=True).power(t, m, sigma)
+    assert_allclose(fft, direct, atol=1e-3 * direct.max())
+    assert np.argmax(fft) == np.argmax(direct)
+
+    dynamic_direct = Periodogram(fast=False)
+    dynamic_fft = Periodogram(fast=True)
+    freq_direct, power_direct = dynamic_direct.freq_power(t, m, sigma)
+    freq_fft, power_fft = dynamic_fft.freq_power(t, m, sigma)
+    assert_allclose(
+        freq_fft[np.argmax(power_fft)],
+        freq_direct[np.argmax(power_direct)],
+        rtol=1e-2,
+    )
+
+
+def test_weighted_feature():
+    rng = np.random.default_rng(2)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.linspace(0.1, 10.0, 1000)
+    periodogram = Periodogram(peaks=1, freqs=freqs, fast=False)
+
+    period, _s_to_n = periodogram(t, m, sigma)
+    power = periodogram.power(t, m, sigma)
+    assert_allclose(period, 2.0 * np.pi / freqs[np.argmax(power)])
+    assert_allclose(period, 2.0 * np.pi / 2.1, rtol=1e-2)
+
+    # Without errors the classic periodogram is used
+    classic_period, _s_to_n = periodogram(t, m)
+    assert_allclose(classic_period, 2.0 * np.pi / freqs[np.argmax(periodogram.power(t, m))])
+
+
+def test_constant_sigma():
+    rng = np.random.default_rng(3)
+    t, m, _sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.linspace(0.1, 10.0, 1000)
+    periodogram = Periodogram(peaks=1, freqs=freqs, fast=False)
+
+    # Constant errors are equivalent to each other whatever their value is
+    desired_power = periodogram.power(t, m, np.ones_like(t))
+    desired_features = periodogram(t, m, np.ones_like(t))
+    for sigma in [0.1, 0.99, 10.0]:
+        sigma = np.full_like(t, sigma)
+        assert_allclose(periodogram.power(t, m, sigma), desired_power, rtol=1e-10)
+        assert_allclose(periodogram(t, m, sigma), desired_features, rtol=1e-10)
+
+
+def test_classic_vs_generalized():
+    rng = np.random.default_rng(5)
+    t, m, _sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.geomspace(0.1, 10.0, 1000)
+    periodogram = Periodogram(freqs=freqs, fast=False)
+
+    # Without errors the classic periodogram is used, which subtracts the sample mean
+    classic = periodogram.power(t, m)
+    scipy_y = (m - m.mean()) / m.std(ddof=1)
+    scipy_power = lombscargle(t, scipy_y, freqs=freqs, precenter=True, normalize=False)
+    assert_allclose(classic, scipy_power, rtol=1e-8, atol=1e-8 * scipy_power.max())
+
+    # Equal errors give the generalized periodogram, which fits the mean, so the powers differ
+    generalized = periodogram.power(t, m, np.ones_like(t))
+    desired = generalized_lomb_scargle(t, m, np.ones_like(t), freqs)
+    assert_allclose(generalized, desired, rtol=1e-8, atol=1e-8 * desired.max())
+    assert not np.allclose(classic, generalized, rtol=1e-3)
+
+
+@pytest.mark.parametrize(
+    "periodogram",
+    [Periodogram(peaks=2), Periodogram(peaks=2, fap="baluev"), Periodogram(peaks=2, nterms=2)],
+)
+def test_json_round_trip(periodogram):
+    rng = np.random.default_rng(4)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+
+    # Periodograms are written in light-curve format, so deserialized ones use observation errors too
+    assert '"algorithm"' in periodogram.to_json()
+    from_json = feature_from_json(periodogram.to_json())
+    assert from_json.names == periodogram.names
+    assert_allclose(from_json(t, m, sigma), periodogram(t, m, sigma))
+    assert_allclose(from_json(t, m), periodogram(t, m))
+
+
+@pytest.mark.parametrize("with_sigma", [False, True])
+def test_multiterm_vs_least_squares(with_sigma):
+    rng = np.random.default_rng(6)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    if not with_sigma:
+        sigma = np.ones_like(t)
+    args = (t, m, sigma) if with_sigma else (t, m)
+    freqs = np.geomspace(0.1, 10.0, 1000)
+
+    # Single-term periodogram is the classic one without errors, see test_classic_vs_generalized
+    for nterms in [1, 2, 3] if with_sigma else [2, 3]:
+        licu_power = Periodogram(freqs=freqs, fast=False, nterms=nterms).power(*args)
+        desired = generalized_lomb_scargle(t, m, sigma, freqs, nterms=nterms)
+        assert_allclose(licu_power, desired, rtol=1e-8, atol=1e-8 * desired.max())
+
+
+def test_multiterm_direct_vs_fft():
+    rng = np.random.default_rng(7)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.linspace(0.0, 20.0, 2**12 + 1)
+
+    direct = Periodogram(freqs=freqs, fast=False, nterms=3).power(t, m, sigma)
+    fft = Periodogram(freqs=freqs, fast=True, nterms=3).power(t, m, sigma)
+    assert_allclose(fft, direct, atol=1e-3 * direct.max())
+    assert np.argmax(fft) == np.argmax(direct)
+
+
+def test_multiterm_feature():
+    rng = np.random.default_rng(8)
+    n = 200
+    period = 0.57
+    t = np.sort(rng.uniform(0.0, 30.0, n))
+    # Sawtooth-like light curve of RR Lyrae
+    phase = (t / period) % 1.0
+    m = np.where(phase < 0.15, phase / 0.15, (1.0 - phase) / 0.85) + 0.05 * rng.normal(size=n)
+    sigma = np.full(n, 0.05)
+    freqs = np.linspace(1.0, 30.0, 10_000)
+
+    periodogram = Periodogram(peaks=1, freqs=freqs, fast=False, nterms=4)
+    licu_period, _s_to_n = periodogram(t, m, sigma)
+    assert_allclose(licu_period, period, rtol=1e-3)
+    # Unity errors are the same as no errors for multi-term periodogram
+    assert_allclose(periodogram(t, m), periodogram(t, m, np.ones(n)))
+
+    restored = pickle.loads(pickle.dumps(periodogram))
+    assert_array_equal(restored(t, m, sigma), periodogram(t, m, sigma))
+    assert_array_equal(restored.power(t, m, sigma), periodogram.power(t, m, sigma))
+
+
+def test_multiterm_wrong_arguments():
+    with pytest.raises(ValueError):
+        Periodogram(nterms=0)
+    with pytest.raises(ValueError):
+        Periodogram(nterms=2, fap="baluev")
+    with pytest.raises(ValueError):
+        Bins([Periodogram(nterms=2)], window=1.0, offset=0.0)
+
+
+def baluev_fap(t, sigma, fmax, power):
+    """Reference Baluev (2008) approximation as implemented in astropy"""
+    n = t.size
+    z = 2.0 * power / (n - 1)
+    w = sigma**-2
+    t_var = np.average((t - np.average(t, weights=w)) ** 2, weights=w)
+    width = fmax / (2.0 * np.pi) * np.sqrt(4.0 * np.pi * t_var)
+    gamma = np.sqrt(2.0 / (n - 1)) * np.exp(gammaln(0.5 * (n - 1)) - gammaln(0.5 * (n - 2)))
+    tau = gamma * width * (1.0 - z) ** (0.5 * (n - 4)) * np.sqrt(0.5 * (n - 1) * z)
+    return 1.0 - (1.0 - (1.0 - z) ** (0.5 * (n - 3))) * np.exp(-tau)
+
+
+def test_fap_names():
+    plain = Periodogram(peaks=2)
+    with_fap = Periodogram(peaks=2, fap="baluev")
+    assert with_fap.names[2::3] == ["period_fap_0", "period_fap_1"]
+    assert [name for name in with_fap.names if "fap" not in name] == plain.names
+    assert len(with_fap.descriptions) == len(with_fap.names)
+
+
+@pytest.mark.parametrize("fast", [False, True])
+def test_fap_same_peaks(fast):
+    rng = np.random.default_rng(5)
+    t, m, _sigma = gen_heteroscedastic_lc(rng)
+    plain = Periodogram(peaks=3, fast=fast)
+    with_fap = Periodogram(peaks=3, fast=fast, fap="baluev")
+
+    # Peaks are found by the same code as of the plain periodogram
+    shared = np.array(["fap" not in name for name in with_fap.names])
+    assert_allclose(with_fap(t, m)[shared], plain(t, m))
+
+
+@pytest.mark.parametrize("with_sigma", [False, True])
+def test_fap_baluev(with_sigma):
+    rng = np.random.default_rng(3)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    if not with_sigma:
+        sigma = np.ones_like(t)
+    args = (t, m, sigma) if with_sigma else (t, m)
+    freqs = np.linspace(0.1, 10.0, 1000)
+    periodogram = Periodogram(peaks=2, freqs=freqs, fast=False, fap="baluev")
+
+    power = periodogram.power(*args)
+    values = periodogram(*args)
+    fap = values[2::3]
+    assert_allclose(fap[0], baluev_fap(t, sigma, freqs.max(), power.max()), rtol=1e-6)
+    assert np.all((fap >= 0.0) & (fap <= 1.0))
+
+    test_power = np.array([[1.0, 5.0], [10.0, power.max()]])
+    actual = periodogram.false_alarm_probability(t, m, test_power, sigma if with_sigma else None)
+    assert actual.shape == test_power.shape
+    assert_allclose(actual, baluev_fap(t, sigma, freqs.max(), test_power), rtol=1e-6)
+
+
+def test_fap_bootstrap():
+    rng = np.random.default_rng(4)
+    t, m, sigma = gen_heteroscedastic_lc(rng, n=50)
+    noise = 3.0 + sigma * rng.normal(size=t.size)
+    periodogram = Periodogram(peaks=1, fast=True, fap="bootstrap", n_bootstrap=100, random_seed=0)
+
+    _period, _s_to_n, fap_signal = periodogram(t, m, sigma)
+    assert fap_signal < 0.05
+    _period, _s_to_n, fap_noise = periodogram(t, noise, sigma)
+    assert fap_noise > fap_signal
+
+    # Maximum possible power is (N - 1) / 2
+    power = np.linspace(0.0, 0.5 * t.size, 11)
+    fap = periodogram.false_alarm_probability(t, noise, power, sigma)
+    assert fap[0] == 1.0
+    assert fap[-1] == 0.0
+    assert np.all(np.diff(fap) <= 0.0)
+    # Reproducible with fixed seed, even though resampling is parallel
+    assert_array_equal(fap, periodogram.false_alarm_probability(t, noise, power, sigma))
+    other_seed = Periodogram(peaks=1, fast=True, fap="bootstrap", n_bootstrap=100, random_seed=1)
+    assert_array_equal(other_seed(t, noise, sigma)[:2], periodogram(t, noise, sigma)[:2])
+
+
+def test_fap_pickle():
+    rng = np.random.default_rng(5)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    periodogram = Periodogram(peaks=2, fap="bootstrap", n_bootstrap=20, random_seed=42)
+    restored = pickle.loads(pickle.dumps(periodogram))
+    assert restored.names == periodogram.names
+    assert_array_equal(restored(t, m, sigma), periodogram(t, m, sigma))
+
+
+def test_fap_wrong_arguments():
+    with pytest.raises(ValueError):
+        Periodogram(fap="unknown")
+    with pytest.raises(ValueError):
+        Periodogram(fap="bootstrap", n_bootstrap=0)
+
+
+@pytest.mark.parametrize("algorithm", ["lomb-scargle", "pdm", "conditional-entropy", "bls"])
+def test_algorithm_finds_period(algorithm):
+    rng = np.random.default_rng(9)
+    n = 300
+    period = 1.7
+    t = np.sort(rng.uniform(0.0, 60.0, n))
+    phase = (t / period) % 1.0
+    m = 0.5 * np.sin(2.0 * np.pi * phase) + 0.05 * rng.normal(size=n)
+    if algorithm == "bls":
+        m = np.where(phase < 0.05, 0.1, 0.0) + 0.01 * rng.normal(size=n)
+    # Exclude sub-harmonics, which are as good as the period for phase-binned algorithms
+    freqs = np.linspace(0.6, 3.0, 5000) * 2.0 * np.pi / period
+
+    periodogram = Periodogram(peaks=1, freqs=freqs, fast=False, algorithm=algorithm)
+    values = periodogram(t, m)
+    assert_allclose(values[0], period, rtol=3e-3)
+    freq, power = periodogram.freq_power(t, m)
+    assert_allclose(freq, freqs)
+    assert_allclose(values[0], 2.0 * np.pi / freq[np.argmax(power)])
+
+    if algorithm == "bls":
+        assert periodogram.names == ["period_0", "period_s_to_n_0", "period_depth_0", "period_duration_0"]
+        _period, _s_to_n, depth, duration = values
+        assert_allclose(depth, 0.1, rtol=0.1)
+        assert_allclose(duration, 0.05 * period, rtol=0.5)
+    else:
+        assert periodogram.names == Periodogram(peaks=1).names
+
+
+def test_algorithm_pickle():
+    rng = np.random.default_rng(10)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    periodogram = Periodogram(
+        peaks=2, algorithm="bls", phase_bins=50, max_duration=0.2, fap="bootstrap", n_bootstrap=10, random_seed=0
+    )
+    assert len(periodogram.names) == 2 * 5
+    restored = pickle.loads(pickle.dumps(periodogram))
+    assert restored.names == periodogram.names
+    assert_array_equal(restored.power(t, m, sigma), periodogram.power(t, m, sigma))
+
+
+def test_algorithm_wrong_arguments():
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="unknown")
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="pdm", nterms=2)
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="pdm", max_duration=0.1)
+    with pytest.raises(ValueError):
+        Periodogram(phase_bins=10)
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="bls", max_duration=1.5)
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="conditional-entropy", fap="baluev")
+    with pytest.raises(ValueError):
+        Bins([Periodogram(algorithm="pdm")], window=1.0, offset=0.0)
+
+
+def multiband_lomb_scargle(t, m, sigma, band, freqs, nterms=1):
+    """Reference implementation fitting per-band floating-mean Fourier series of the shared frequency"""
+    w = sigma**-2
+    chi2_0 = 0.0
+    chi2 = np.zeros_like(freqs)
+    for b in np.unique(band):
+        idx = band == b
+        chi2_0 += np.sum(w[idx] * (m[idx] - np.average(m[idx], weights=w[idx])) ** 2)
+        for i, freq in enumerate(freqs):
+            tb = t[idx]
+            harmonics = [f(k * freq * tb) for k in range(1, nterms + 1) for f in (np.cos, np.sin)]
+            design = np.stack([np.ones_like(tb), *harmonics], axis=1)
+            sqrt_w = np.sqrt(w[idx])
+            coeffs, *_ = np.linalg.lstsq(design * sqrt_w[:, None], m[idx] * sqrt_w, rcond=None)
+            chi2[i] += np.sum(w[idx] * (m[idx] - design @ coeffs) ** 2)
+    return 0.5 * (t.size - 1) * (1.0 - chi2 / chi2_0)
+
+
+def gen_multiband_lc(rng, n=60, freq=2.1):
+    t = np.sort(rng.uniform(0.0, 20.0, n))
+    band = rng.choice(np.array(["g", "r", "i"]), n)
+    sigma = 10 ** rng.uniform(-2.0, -1.0, n)
+    offsets = {"g": 1.0, "r": 0.0, "i": -0.5}
+    amplitudes = {"g": 0.7, "r": 0.5, "i": 0.3}
+    m = np.array([offsets[b] + amplitudes[b] * np.sin(freq * ti + 0.3) for ti, b in zip(t, band)])
+    m += sigma * rng.normal(size=n)
+    return t, m, sigma, band
+
+
+def test_multiband_single_band():
+    rng = np.random.default_rng(11)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.geomspace(0.1, 10.0, 500)
+    periodogram = Periodogram(freqs=freqs, fast=False)
+
+    band = np.zeros(t.size, dtype=int)
+    assert_allclose(periodogram.power(t, m, sigma, band=band), periodogram.power(t, m, sigma), rtol=1e-10)
+
+
+@pytest.mark.parametrize("nterms", [1, 2])
+def test_multiband_vs_least_squares(nterms):
+    rng = np.random.default_rng(12)
+    t, m, sigma, band = gen_multiband_lc(rng)
+    freqs = np.geomspace(0.1, 10.0, 300)
+
+    licu_freqs, licu_power = Periodogram(freqs=freqs, fast=False, nterms=nterms).freq_power(t, m, sigma, band=band)
+    assert_allclose(licu_freqs, freqs)
+    desired = multiband_lomb_scargle(t, m, sigma, band, freqs, nterms=nterms)
+    assert_allclose(licu_power, desired, rtol=1e-8, atol=1e-8 * desired.max())
+
+
+def test_multiband_finds_period():
+    rng = np.random.default_rng(13)
+    freq = 2.1
+    t, m, sigma, band = gen_multiband_lc(rng, n=30, freq=freq)
+    periodogram = Periodogram(peaks=1, fast=False)
+
+    period, _s_to_n = periodogram.multiband_features(t, m, band, sigma)
+    assert_allclose(period, 2.0 * np.pi / freq, rtol=1e-2)
+    # Integer labels give the same result
+    int_band = np.unique(band, return_inverse=True)[1]
+    assert_array_equal(periodogram.multiband_features(t, m, int_band, sigma), [period, _s_to_n])
+
+    freq_grid, power = periodogram.freq_power(t, m, sigma, band=list(band))
+    assert_allclose(period, 2.0 * np.pi / freq_grid[np.argmax(power)])
+
+
+def test_multiband_wrong_arguments():
+    rng = np.random.default_rng(14)
+    t, m, sigma, band = gen_multiband_lc(rng)
+    with pytest.raises(ValueError):
+        Periodogram().power(t, m, sigma, band=band[:-1])
+    with pytest.raises(ValueError):
+        Periodogram(fap="baluev").multiband_features(t, m, band, sigma)
+    with pytest.raises(ValueError):
+        Periodogram(algorithm="pdm").freq_power(t, m, band=band)
+
+
+@pytest.mark.parametrize("dtype", [np.float32, np.float64])
+def test_power_many_fixed_freqs(dtype):
+    rng = np.random.default_rng(15)
+    lcs = [tuple(a.astype(dtype) for a in gen_heteroscedastic_lc(rng, n=50 + i)) for i in range(5)]
+    lcs[0] = (lcs[0][0], lcs[0][1], None)
+    freqs = np.linspace(0.1, 10.0, 200)
+    periodogram = Periodogram(freqs=freqs, fast=False)
+
+    power = periodogram.power_many(lcs, n_jobs=2)
+    assert power.shape == (len(lcs), freqs.size)
+    assert power.dtype == dtype
+    desired = np.stack([periodogram.power(*lc) for lc in lcs])
+    assert_array_equal(power, desired)
+
+    freq, freq_power = periodogram.freq_power_many(lcs)
+    assert_allclose(freq, freqs, rtol=1e-6)
+    assert_array_equal(freq_power, desired)
+
+
+def test_power_many_dynamic_freqs():
+    rng = np.random.default_rng(16)
+    lcs = [gen_heteroscedastic_lc(rng, n=30 + 10 * i) for i in range(4)]
+    periodogram = Periodogram(fast=True)
+
+    powers = periodogram.power_many(lcs)
+    freq_powers = periodogram.freq_power_many(lcs, n_jobs=1)
+    assert len(powers) == len(freq_powers) == len(lcs)
+    for lc, power, (freq, freq_power) in zip(lcs, powers, freq_powers):
+        desired_freq, desired_power = periodogram.freq_power(*lc)
+        assert_array_equal(power, desired_power)
+        assert_array_equal(freq, desired_freq)
+        assert_array_equal(freq_power, desired_power)
+
+
+def test_power_many_wrong_arguments():
+    rng = np.random.default_rng(17)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    periodogram = Periodogram()
+    with pytest.raises(ValueError):
+        periodogram.power_many([])
+    with pytest.raises(TypeError):
+        periodogram.freq_power_many([(t, m, sigma), (t.astype(np.float32), m.astype(np.float32), None)])
+
+
+def test_power_wrong_light_curves():
+    rng = np.random.default_rng(19)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    periodogram = Periodogram(fap="baluev")
+    m_nan = np.where(np.arange(t.size) == 5, np.nan, m)
+    sigma_nan = np.where(np.arange(t.size) == 5, np.nan, sigma)
+    wrong = [(t, m[:-1], None), (t, m, sigma[:-1]), (t[::-1], m, None), (t, m_nan, None), (t, m, sigma_nan)]
+
+    for lc in wrong:
+        with pytest.raises(ValueError):
+            periodogram.power(*lc)
+        with pytest.raises(ValueError):
+            periodogram.freq_power(*lc)
+        with pytest.raises(ValueError):
+            periodogram.false_alarm_probability(*lc[:2], np.array([1.0]), lc[2])
+        with pytest.raises(ValueError):
+            periodogram.power_many([(t, m, sigma), lc])
+        with pytest.raises(ValueError):
+            periodogram.freq_power_many([(t, m, sigma), lc])
+
+
+@pytest.mark.parametrize(
+    "grid, freqs",
+    [
+        ("log-freq", np.geomspace(2.0 * np.pi / 10.0, 2.0 * np.pi / 0.5, 300)),
+        ("linear-period", 2.0 * np.pi / np.linspace(10.0, 0.5, 300)),
+    ],
+)
+@pytest.mark.parametrize("fast", [False, True])
+def test_period_range_grid(grid, freqs, fast):
+    rng = np.random.default_rng(18)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    periodogram = Periodogram(peaks=1, grid=grid, min_period=0.5, max_period=10.0, n=300, fast=fast)
+    explicit = Periodogram(peaks=1, freqs=freqs, fast=False)
+
+    # Both weighted and unweighted periodograms are evaluated directly, whatever fast is
+    for args in [(t, m), (t, m, sigma)]:
+        licu_freqs, power = periodogram.freq_power(*args)
+        assert_allclose(licu_freqs, freqs, rtol=1e-12)
+        assert_allclose(power, explicit.power(*args), rtol=1e-10, atol=1e-10 * power.max())
+        assert_allclose(periodogram(*args), explicit(*args), rtol=1e-10)
+
+    power_f32 = periodogram.power(t.astype(np.float32), m.astype(np.float32), sigma.astype(np.float32))
+    assert_allclose(power_f32, periodogram.power(t, m, sigma), rtol=1e-3, atol=1e-3 * power_f32.max())
+    assert periodogram.power_many([(t, m, sigma), (t, m, None)]).shape == (2, freqs.size)
+
+    restored = pickle.loads(pickle.dumps(periodogram))
+    assert_array_equal(restored.freq_power(t, m, sigma), periodogram.freq_power(t, m, sigma))
+    assert {"log-freq": "LogFreq", "linear-period": "LinearPeriod"}[grid] in periodogram.to_json()
+
+
+def test_period_range_grid_wrong_arguments():
+    with pytest.raises(ValueError):
+        Periodogram(grid="log-freq", min_period=0.5, n=100, fast=False)
+    with pytest.raises(ValueError):
+        Periodogram(grid="log-freq", min_period=10.0, max_period=0.5, n=100, fast=False)
+    with pytest.raises(ValueError):
+        Periodogram(grid="log-freq", min_period=0.5, max_period=10.0, n=1, fast=False)
+    with pytest.raises(ValueError):
+        Periodogram(grid="unknown", min_period=0.5, max_period=10.0, n=100, fast=False)
+    with pytest.raises(ValueError):
+        Periodogram(min_period=0.5, max_period=10.0, n=100, fast=False)
+    with pytest.raises(ValueError):
+        Periodogram(
+            grid="linear-period", min_period=0.5, max_period=10.0, n=100, freqs=np.linspace(0.1, 1.0, 10), fast=False
+        )
+
+
+def gen_nightly_lc(rng, period, n_nights=60):
+    """Observations once per night with small jitter, so spectral window has peaks at multiples of 1/day"""
+    t = np.arange(n_nights) + 0.02 * rng.normal(size=n_nights)
+    m = np.sin(2.0 * np.pi * t / period) + 0.01 * rng.normal(size=n_nights)
+    sigma = np.full_like(t, 0.01)
+    return t, m, sigma
+
+
+def test_window_power():
+    rng = np.random.default_rng(19)
+    t, m, _sigma = gen_nightly_lc(rng, 0.37)
+    freqs = np.linspace(0.0, 3.0 * np.pi, 2**12 + 1)
+    desired = np.abs(np.mean(np.exp(1j * freqs[:, None] * t), axis=1)) ** 2
+
+    for fast in [False, True]:
+        periodogram = Periodogram(freqs=freqs, fast=fast)
+        window = periodogram.window_power(t)
+        assert window.shape == freqs.shape
+        assert_allclose(window[0], 1.0)
+        assert_allclose(window, desired, atol=1e-3 if fast else 1e-10)
+
+    # Default grid is the same as of freq_power
+    periodogram = Periodogram()
+    freq, _power = periodogram.freq_power(t, m)
+    assert periodogram.window_power(t).shape == freq.shape
+    assert periodogram.window_power(t.astype(np.float32)).dtype == np.float32
+
+
+@pytest.mark.parametrize("period, alias", [(1.0, True), (2.7, False)])
+def test_aliases(period, alias):
+    rng = np.random.default_rng(20)
+    t, m, sigma = gen_nightly_lc(rng, period)
+    freqs = np.linspace(0.5, 8.0, 10_000)
+    plain = Periodogram(peaks=1, freqs=freqs, fast=False)
+    flag = Periodogram(peaks=1, freqs=freqs, fast=False, aliases="flag")
+    suppress = Periodogram(peaks=1, freqs=freqs, fast=False, aliases="suppress")
+
+    assert flag.names == ["period_0", "period_s_to_n_0", "period_alias_0"]
+    assert suppress.names == plain.names
+
+    for args in [(t, m), (t, m, sigma)]:
+        plain_values = plain(*args)
+        assert_allclose(plain_values[0], period, rtol=1e-2)
+        flag_values = flag(*args)
+        assert_allclose(flag_values[:2], plain_values, rtol=1e-6)
+        assert flag_values[2] == float(alias)
+        suppress_values = suppress(*args)
+        if alias:
+            assert not np.isclose(suppress_values[0], period, rtol=1e-2)
+        else:
+            assert_allclose(suppress_values, plain_values, rtol=1e-6)
+
+    restored = pickle.loads(pickle.dumps(flag))
+    assert_array_equal(restored(t, m, sigma), flag(t, m, sigma))
+
+
+def test_aliases_wrong_arguments():
+    with pytest.raises(ValueError):
+        Periodogram(aliases="unknown")
+    with pytest.raises(ValueError):
+        Periodogram(aliases="flag", alias_tolerance=-1.0)
+
+
+def test_peak_widths():
+    rng = np.random.default_rng(21)
+    t, m, sigma = gen_heteroscedastic_lc(rng)
+    freqs = np.linspace(0.1, 20.0, 5000)
+    plain = Periodogram(peaks=2, freqs=freqs, fast=False)
+    periodogram = Periodogram(peaks=2, freqs=freqs, fast=False, peak_widths=True)
+
+    assert periodogram.names == [
+        "period_0",
+        "period_s_to_n_0",
+        "period_width_0",
+        "period_error_0",
+        "period_1",
+        "period_s_to_n_1",
+        "period_width_1",
+        "period_error_1",
+    ]
+    assert len(periodogram.descriptions) == len(periodogram.names)
+
+    for args in [(t, m), (t, m, sigma)]:
+        values = periodogram(*args)
+        assert_allclose(values[[0, 1, 4, 5]], plain(*args), rtol=1e-6)
+
+        freq, power = periodogram.freq_power(*args)
+        i = np.argmin(np.abs(freq - 2.0 * np.pi / values[0]))
+        above = freq[power >= 0.5 * power[i]]
+        peak = above[(above > freq[i] - 0.5) & (above < freq[i] + 0.5)]
+        step = freq[1] - freq[0]
+        # Half width is between the grid-based estimates
+        assert 0.5 * (peak.max() - peak.min()) - step <= values[2] <= 0.5 * (peak.max() - peak.min()) + 2.0 * step
+        assert_allclose(values[3], values[0] ** 2 * values[2] / (2.0 * np.pi), rtol=1e-2)
+        assert np.all(values[[2, 3, 6, 7]] > 0.0)
+
+
+def test_peak_widths_resolution():
+    rng = np.random.default_rng(22)
+    periodogram = Periodogram(peaks=1, freqs=np.linspace(0.1, 20.0, 10_000), fast=False, peak_widths=True)
+    widths = []
+    for duration in [10.0, 40.0]:
+        t = np.sort(rng.uniform(0.0, duration, 300))
+        m = np.sin(2.0 * np.pi * t / 0.9) + 0.01 * rng.normal(size=t.size)
+        widths.append(periodogram(t, m)[2])
+    # Peak width is inversely proportional to the time series duration
+    assert_allclose(widths[0] / widths[1], 4.0, rtol=0.1)
@@ -0,0 +1,233 @@
+import copy
+import pickle
+
+import numpy as np
+import pytest
+from numpy.testing import assert_allclose, assert_array_equal
+
+import light_curve.light_curve_ext as lc
+from light_curve.light_curve_ext import transform
+
+TRANSFORMERS = [
+    transform.Transformer(),
+    transform.stock("lg"),
+    transform.affine(2.0, -1.0),
+    transform.clipped_log(1e-3),
+    transform.clipped_log(1e-3, base=np.e),
+    transform.power(0.5),
+    transform.standardize([1.0, 2.0], [3.0, 4.0]),
+    transform.chain([transform.affine(2.0), transform.power(2.0)]),
+]
+
+
+@pytest.mark.parametrize("tr", TRANSFORMERS)
+@pytest.mark.parametrize("pickle_protocol", tuple(range(2, pickle.HIGHEST_PROTOCOL + 1)))
+def test_pickle(tr, pickle_protocol):
+    b = pickle.dumps(tr, protocol=pickle_protocol)
+    assert pickle.loads(b) == tr
+
+
+@pytest.mark.parametrize("tr", TRANSFORMERS)
+def test_copy(tr):
+    assert copy.copy(tr) == tr
+    assert copy.deepcopy(tr) == tr
+
+
+@pytest.mark.parametrize("tr", TRANSFORMERS)
+def test_json(tr):
+    assert transform.from_json(tr.to_json()) == tr
+
+
+def test_values():
+    x = np.array([0.5, 4.0])
+    assert_allclose(transform.affine(2.0, -1.0)(x), 2.0 * x - 1.0)
+    assert_allclose(transform.clipped_log(1.0)(x), [0.0, np.log10(4.0)])
+    assert_allclose(transform.clipped_log(1e-3, base=2.0)(x), np.log2(x))
+    assert_allclose(transform.power(3.0)(x), x**3)
+    assert_allclose(transform.standardize([1.0, 2.0], [0.5, 4.0])(x), [-1.0, 0.5])
+    assert_allclose(transform.affine(2.0).then(transform.power(2.0))(x), (2.0 * x) ** 2)
+    assert_allclose(transform.stock("lg")(x), np.log10(x))
+    assert np.isnan(transform.clipped_log(1.0)(np.array([np.nan]))[0])
+
+
+def test_fit_standardize():
+    rng = np.random.default_rng(0)
+    values = rng.normal([1.0, -2.0], [3.0, 0.5], size=(1000, 2))
+    tr = transform.chain([transform.affine(2.0), transform.standardize()]).fit(values)
+    transformed = np.stack([tr(row) for row in values])
+    assert_allclose(transformed.mean(axis=0), 0.0, atol=1e-12)
+    assert_allclose(transformed.std(axis=0), 1.0)
+
+
+def test_unfitted_standardize():
+    with pytest.raises(ValueError, match="fitted"):
+        transform.standardize()(np.array([1.0]))
+    with pytest.raises(ValueError, match="fitted"):
+        lc.Amplitude(transform=transform.standardize())
+
+
+@pytest.mark.parametrize("cls,args", [(lc.Amplitude, ()), (lc.BeyondNStd, (1.5,)), (lc.LinearFit, ())])
+def test_feature_transform(cls, args):
+    rng = np.random.default_rng(0)
+    t = np.sort(rng.uniform(0.0, 10.0, 100))
+    m = rng.normal(size=t.size)
+    sigma = np.full_like(t, 0.1)
+
+    feature = cls(*args)
+    values = feature(t, m, sigma)
+    tr = transform.affine(3.0, 1.0).then(transform.standardize(list(values), [2.0] * values.size))
+    transformed_feature = cls(*args, transform=tr)
+    assert transformed_feature.names == [f"standardized_affine_{name}" for name in feature.names]
+    assert_allclose(transformed_feature(t, m, sigma), tr(values))
+
+    b = pickle.dumps(transformed_feature)
+    assert_array_equal(pickle.loads(b)(t, m, sigma), transformed_feature(t, m, sigma))
+    from_json = lc.feature_from_json(transformed_feature.to_json())
+    assert_array_equal(from_json(t, m, sigma), transformed_feature(t, m, sigma))
+
+
+def test_fit_feature_transform():
+    t = np.linspace(-50.0, 120.0, 100)
+    flux = lc.BazinFit.model(t, np.array([10.0, -2.0, 10.0, 10.0, 25.0]))
+    fluxerr = np.ones_like(t)
+    tr = transform.affine(2.0)
+    fit = lc.BazinFit("mcmc", mcmc_niter=10)
+    transformed_fit = lc.BazinFit("mcmc", mcmc_niter=10, transform=tr)
+    assert_allclose(transformed_fit(t, flux, fluxerr), 2.0 * fit(t, flux, fluxerr))
+    derived_fit = lc.BazinFit("mcmc", mcmc_niter=10, transform=tr, derived=True)
+    n_params = len(fit.names)
+    assert_allclose(derived_fit(t, flux, fluxerr)[:n_params], 2.0 * fit(t, flux, fluxerr))
+
+
+def test_wrong_size():
+    with pytest.raises(ValueError, match="fitted for 2 values"):
+        lc.Amplitude(transform=transform.standardize([0.0, 0.0], [1.0, 1.0]))
+
+
+@pytest.mark.parametrize(
+    "transform_arg", ["arcsinh", "lg", "ln1p", "sqrt", transform.affine(2.0, 1.0).then(transform.power(3.0))]
+)
+def test_inverse_transform(transform_arg):
+    rng = np.random.default_rng(0)
+    lcs = []
+    for _ in range(4):
+        t = np.sort(rng.uniform(0.0, 10.0, 100))
+        lcs.append((t, rng.normal(size=t.size), np.full_like(t, 0.1)))
+
+    feature = lc.Extractor(lc.Amplitude(), lc.StandardDeviation())
+    transformed_feature = lc.Extractor(lc.Amplitude(), lc.StandardDeviation(), transform=transform_arg)
+    values = feature.many(lcs)
+    assert_allclose(transformed_feature.inverse_transform(transformed_feature.many(lcs)), values)
+    assert_allclose(transformed_feature.inverse_transform(transformed_feature(*lcs[0])), values[0])
+
+
+def test_inverse_transform_default():
+    feature = lc.Extractor(lc.Amplitude(), lc.Mean(), transform="default")
+    assert list(feature.transformers.values()) == [transform.stock("identity"), transform.stock("identity")]
+    bins = lc.Bins([lc.Amplitude(), lc.Kurtosis()], window=1.0, offset=0.0, transform="default")
+    assert list(bins.transformers.values()) == [transform.stock("identity"), transform.stock("arcsinh")]
+    periodogram = lc.Periodogram(peaks=1, features=[lc.Amplitude()], transform="default")
+    assert list(periodogram.transformers.values()) == [
+        transform.stock("lg"),
+        transform.stock("identity"),
+        transform.stock("identity"),
+    ]
+
+
+@pytest.mark.parametrize(
+    "feature",
+    [
+        lc.Extractor(lc.Amplitude(transform="lg"), lc.Kurtosis(transform="arcsinh"), lc.Mean()),
+        lc.Bins([lc.Amplitude(), lc.Kurtosis()], window=1.0, offset=0.0, transform="default"),
+        lc.Periodogram(peaks=1, features=[lc.Amplitude(transform="lg")]),
+    ],
+)
+def test_transformers_json(feature):
+    from_json = lc.feature_from_json(feature.to_json())
+    assert from_json.transformers == feature.transformers
+
+
+def test_inverse_transform_clipped():
+    feature = lc.Amplitude(transform=transform.clipped_log(1.0))
+    assert_allclose(feature.inverse_transform(np.array([1.0])), [10.0])
+    with pytest.raises(ValueError, match="fill_value"):
+        feature.inverse_transform(np.array([0.0]))
+    assert_array_equal(feature.inverse_transform(np.array([0.0]), fill_value=-1.0), [-1.0])
+
+
+def test_inverse_transform_clipped_base_below_unity():
+    feature = lc.Amplitude(transform=transform.clipped_log(1.0, base=0.5))
+    assert_allclose(feature.inverse_transform(np.array([-2.0])), [4.0])
+    # Clipped values are non-negative for such a base
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        feature.inverse_transform(np.array([0.0]))
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        feature.inverse_transform(np.array([1.0]))
+
+
+@pytest.mark.parametrize("dtype", [np.float32, np.float64])
+def test_inverse_transform_clipped_lg(dtype):
+    feature = lc.Amplitude(transform="clipped_lg")
+    t = np.linspace(0.0, 1.0, 10, dtype=dtype)
+    values = feature(t, np.ones_like(t))
+    assert values.dtype == dtype
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        feature.inverse_transform(values)
+    # Values above the float64 clipping threshold are restored
+    assert_allclose(feature.inverse_transform(np.array([-100.0])), [1e-100])
+
+
+def test_inverse_transform_power():
+    odd = lc.Mean(transform=transform.power(3.0))
+    assert_allclose(odd.inverse_transform(np.array([-8.0, 27.0])), [-2.0, 3.0])
+    even = lc.Mean(transform=transform.power(2.0))
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        even.inverse_transform(np.array([4.0]))
+    assert_array_equal(even.inverse_transform(np.array([4.0]), fill_value=np.nan), [np.nan])
+    fractional = lc.Mean(transform=transform.power(0.5))
+    assert_allclose(fractional.inverse_transform(np.array([3.0])), [9.0])
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        fractional.inverse_transform(np.array([-3.0]))
+
+
+def test_inverse_transform_fit():
+    fit = lc.BazinFit("mcmc", transform=True, derived=True)
+    transformers = list(fit.transformers.values())
+    assert all(tr is None for tr in transformers[:-4])
+    assert transformers[-4:] == [transform.stock("identity")] * 4
+    values = np.ones(len(fit.names))
+    with pytest.raises(ValueError, match="cannot be inverse transformed"):
+        fit.inverse_transform(values)
+    inverse = fit.inverse_transform(values, fill_value=np.nan)
+    assert np.all(np.isnan(inverse[:-4]))
+    assert_array_equal(inverse[-4:], values[-4:])
+
+    stock_fit = lc.BazinFit("mcmc", transform="lg")
+    assert_allclose(stock_fit.inverse_transform(np.zeros(len(stock_fit.names))), 1.0)
+
+
+def test_inverse_transform_wrong_shape():
+    feature = lc.Extractor(lc.Amplitude(), lc.Mean())
+    with pytest.raises(ValueError, match="last dimension of size 2"):
+        feature.inverse_transform(np.zeros(3))
+
+
+@pytest.mark.parametrize(
+    "meta_feature",
+    [
+        lambda feature: lc.Bins([feature], window=1.0, offset=0.0),
+        lambda feature: lc.Periodogram(features=[feature]),
+    ],
+)
+def test_custom_transformed_in_meta_feature(meta_feature):
+    feature = lc.Amplitude(transform=transform.affine(2.0))
+    with pytest.raises(ValueError, match="custom transformer"):
+        meta_feature(feature)
+
+
+def test_custom_transform_of_meta_feature():
+    t = np.linspace(0.0, 10.0, 101)
+    m = np.sin(t)
+    plain = lc.Bins([lc.Amplitude()], window=1.0, offset=0.0)
+    transformed = lc.Bins([lc.Amplitude()], window=1.0, offset=0.0, transform=transform.affine(2.0))
+    assert_allclose(transformed(t, m), 2.0 * plain(t, m))